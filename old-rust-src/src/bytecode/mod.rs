@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+
+use crate::{interpreter::{typed_float, value::Value}, parser::ast::{BinaryOperator, Declaration, Expression, LoopType, Program, Statement, UnaryOperator, VariableMutability}};
+
+pub mod vm;
+
+/// A single instruction for the stack-based `VM`.
+/// Every expression compiles to instructions that leave exactly one value on the stack.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction {
+    /// Pushes a constant.
+    Constant(Value),
+    /// Discards the top of the stack.
+    Pop,
+    /// Pushes the value of a local variable slot.
+    LoadLocal(usize),
+    /// Stores the top of the stack in a local variable slot, leaving it on the stack.
+    StoreLocal(usize),
+    /// Pops two operands and pushes the result of the operator.
+    Binary(BinaryOperator),
+    /// Pops an operand and pushes the result of the operator.
+    Unary(UnaryOperator),
+    /// Continues at an instruction index.
+    Jump(usize),
+    /// Pops a condition, and continues at an instruction index if it's false.
+    JumpIfFalse(usize),
+    /// Pops the arguments and calls a function by its index, pushing its result.
+    Call {
+        function: usize,
+        arg_count: usize
+    },
+    /// Pops the return value and returns from the current function.
+    Return,
+    /// Pops and prints some number of values, then pushes nil.
+    Print(usize)
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Constant(value) => write!(f, "CONSTANT {:?}", value),
+            Instruction::Pop => write!(f, "POP"),
+            Instruction::LoadLocal(slot) => write!(f, "LOAD_LOCAL {}", slot),
+            Instruction::StoreLocal(slot) => write!(f, "STORE_LOCAL {}", slot),
+            Instruction::Binary(operator) => write!(f, "BINARY {}", operator),
+            Instruction::Unary(operator) => write!(f, "UNARY {}", operator),
+            Instruction::Jump(target) => write!(f, "JUMP {}", target),
+            Instruction::JumpIfFalse(target) => write!(f, "JUMP_IF_FALSE {}", target),
+            Instruction::Call { function, arg_count } => write!(f, "CALL {} {}", function, arg_count),
+            Instruction::Return => write!(f, "RETURN"),
+            Instruction::Print(count) => write!(f, "PRINT {}", count)
+        }
+    }
+}
+
+/// A function compiled to a flat list of instructions. Its parameters occupy the first local slots.
+#[derive(Debug, PartialEq)]
+pub struct CompiledFunction {
+    pub name: String,
+    pub arity: usize,
+    pub local_count: usize,
+    pub code: Vec<Instruction>
+}
+
+/// A whole program compiled for the `VM`.
+#[derive(Debug, PartialEq)]
+pub struct Bytecode {
+    pub functions: Vec<CompiledFunction>,
+    /// The index of the `main` function, if there is one
+    pub main: Option<usize>
+}
+
+impl std::fmt::Display for Bytecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            writeln!(f, "func {} {} ({} params, {} locals):", index, function.name, function.arity, function.local_count)?;
+            for (offset, instruction) in function.code.iter().enumerate() {
+                writeln!(f, "  {:04} {}", offset, instruction)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compiles a program to bytecode.
+/// Only a subset of the language is supported so far: top-level functions called by name, local variables,
+/// arithmetic, blocks, `if`, loops other than iterator loops, and `print`. Anything else is a compile error.
+pub fn compile_program(program: &Program) -> Result<Bytecode, String> {
+    let mut function_indices = HashMap::new();
+    let mut declarations = Vec::new();
+    for declaration in &program.declarations {
+        match declaration {
+            Declaration::Function { name, params, body, .. } => {
+                function_indices.insert(name.clone(), declarations.len());
+                declarations.push((name, params, body));
+            },
+            // Types don't affect how a program runs
+            Declaration::Struct { .. } | Declaration::Enum { .. } | Declaration::Interface { .. } | Declaration::TypeDeclaration { .. } => {},
+            Declaration::Import { .. } => return Err("Imports aren't supported in bytecode yet".to_string()),
+            Declaration::GlobalVariable { name, .. } => return Err(format!("Global variables aren't supported in bytecode yet: {}", name))
+        }
+    }
+
+    let mut functions = Vec::with_capacity(declarations.len());
+    for (name, params, body) in declarations {
+        let mut compiler = FunctionCompiler::new(&function_indices);
+        compiler.begin_scope();
+        for param in params {
+            compiler.declare_local(&param.name, VariableMutability::Mutable);
+        }
+        compiler.compile_expression(body)?;
+        compiler.emit(Instruction::Return);
+
+        functions.push(CompiledFunction {
+            name: name.clone(),
+            arity: params.len(),
+            local_count: compiler.local_count,
+            code: compiler.code
+        });
+    }
+
+    Ok(Bytecode {
+        functions,
+        main: function_indices.get("main").copied()
+    })
+}
+
+struct Local {
+    name: String,
+    slot: usize,
+    mutability: VariableMutability
+}
+
+/// Jumps out of a loop that still need their targets filled in once the loop is compiled.
+struct LoopJumps {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+    /// The stack height when the loop started, which `break` and `continue` unwind to
+    stack_height: usize
+}
+
+struct FunctionCompiler<'a> {
+    function_indices: &'a HashMap<String, usize>,
+    code: Vec<Instruction>,
+    scopes: Vec<Vec<Local>>,
+    local_count: usize,
+    loops: Vec<LoopJumps>,
+    /// How many values are on the stack at this point in the function, so jumps out of loops can discard temporaries
+    stack_height: usize
+}
+
+impl<'a> FunctionCompiler<'a> {
+    fn new(function_indices: &'a HashMap<String, usize>) -> Self {
+        FunctionCompiler {
+            function_indices,
+            code: Vec::new(),
+            scopes: Vec::new(),
+            local_count: 0,
+            loops: Vec::new(),
+            stack_height: 0
+        }
+    }
+
+    /// Adds an instruction, returning its index so jumps can be patched later.
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.stack_height = match &instruction {
+            Instruction::Constant(_) | Instruction::LoadLocal(_) => self.stack_height + 1,
+            Instruction::Pop | Instruction::Binary(_) | Instruction::JumpIfFalse(_) | Instruction::Return => self.stack_height - 1,
+            Instruction::Call { arg_count, .. } => self.stack_height + 1 - arg_count,
+            Instruction::Print(count) => self.stack_height + 1 - count,
+            Instruction::StoreLocal(_) | Instruction::Unary(_) | Instruction::Jump(_) => self.stack_height
+        };
+        self.code.push(instruction);
+        self.code.len() - 1
+    }
+
+    /// Points a previously emitted jump at the next instruction.
+    fn patch_jump(&mut self, jump: usize) {
+        let target = self.code.len();
+        match &mut self.code[jump] {
+            Instruction::Jump(destination) | Instruction::JumpIfFalse(destination) => *destination = target,
+            instruction => unreachable!("Tried to patch {} as a jump", instruction)
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Gives a variable its own slot. Slots aren't reused, so every variable in a function has its own.
+    fn declare_local(&mut self, name: &str, mutability: VariableMutability) -> usize {
+        let slot = self.local_count;
+        self.local_count += 1;
+        self.scopes.last_mut().expect("Locals are always declared in a scope").push(Local { name: name.to_string(), slot, mutability });
+        slot
+    }
+
+    fn find_local(&self, name: &str) -> Option<&Local> {
+        self.scopes.iter().rev().flat_map(|scope| scope.iter().rev()).find(|local| local.name == name)
+    }
+
+    /// Discards anything the current loop left on the stack, then jumps out of it.
+    /// The code after a jump is unreachable, so the stack height is left as it was for the rest of the block.
+    fn emit_loop_jump(&mut self, is_break: bool) -> Result<(), String> {
+        let Some(loop_jumps) = self.loops.last() else {
+            return Err(format!("Cannot {} outside of a loop", if is_break { "break" } else { "continue" }));
+        };
+        let height = self.stack_height;
+        for _ in loop_jumps.stack_height..height {
+            self.emit(Instruction::Pop);
+        }
+        let jump = self.emit(Instruction::Jump(0));
+        self.stack_height = height;
+
+        let loop_jumps = self.loops.last_mut().unwrap();
+        if is_break {
+            loop_jumps.breaks.push(jump);
+        } else {
+            loop_jumps.continues.push(jump);
+        }
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), String> {
+        let height = self.stack_height;
+        match statement {
+            Statement::Expression { expression, .. } => {
+                self.compile_expression(expression)?;
+                self.emit(Instruction::Pop);
+            },
+            Statement::VariableDeclaration { mutability, name, value, .. } => {
+                // The value is compiled first so that it can't refer to the variable being declared
+                self.compile_expression(value)?;
+                let slot = self.declare_local(name, *mutability);
+                self.emit(Instruction::StoreLocal(slot));
+                self.emit(Instruction::Pop);
+            },
+            Statement::Return(value) => {
+                match value {
+                    Some(value) => self.compile_expression(value)?,
+                    None => { self.emit(Instruction::Constant(Value::Nil)); }
+                }
+                self.emit(Instruction::Return);
+            },
+            Statement::Break => self.emit_loop_jump(true)?,
+            Statement::Continue => self.emit_loop_jump(false)?,
+            Statement::Declaration(_) => return Err("Nested declarations aren't supported in bytecode yet".to_string())
+        }
+        // Statements never leave anything on the stack, and code after a return is unreachable
+        self.stack_height = height;
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), String> {
+        match expression {
+            Expression::IntegerLiteral(n) => { self.emit(Instruction::Constant(Value::Integer(*n))); },
+            Expression::NumberLiteral(n) => { self.emit(Instruction::Constant(Value::Float(*n))); },
+            Expression::TypedIntegerLiteral(n, _) => { self.emit(Instruction::Constant(Value::Integer(*n))); },
+            Expression::TypedNumberLiteral(n, literal_type) => { self.emit(Instruction::Constant(typed_float(*n, literal_type))); },
+            Expression::StringLiteral(s) => { self.emit(Instruction::Constant(Value::String(s.clone()))); },
+            Expression::CharLiteral(c) => { self.emit(Instruction::Constant(Value::Char(*c))); },
+            Expression::BooleanLiteral(b) => { self.emit(Instruction::Constant(Value::Boolean(*b))); },
+
+            Expression::Variable { name, .. } => {
+                let Some(local) = self.find_local(name.as_str()) else {
+                    return Err(format!("Only local variables are supported in bytecode, got {}", name));
+                };
+                self.emit(Instruction::LoadLocal(local.slot));
+            },
+            Expression::Assignment { name, value, .. } => {
+                let (slot, mutability) = match self.find_local(name.as_str()) {
+                    Some(local) => (local.slot, local.mutability),
+                    None => return Err(format!("Only local variables are supported in bytecode, got {}", name))
+                };
+                if mutability == VariableMutability::Immutable {
+                    return Err(format!("Cannot assign to constant {}", name));
+                }
+                self.compile_expression(value)?;
+                self.emit(Instruction::StoreLocal(slot));
+            },
+
+            Expression::BinaryOperation { left, operator, right, .. } => {
+                self.compile_expression(left)?;
+                self.compile_expression(right)?;
+                self.emit(Instruction::Binary(operator.clone()));
+            },
+            Expression::UnaryOperation { operator, operand, .. } => {
+                self.compile_expression(operand)?;
+                self.emit(Instruction::Unary(operator.clone()));
+            },
+
+            Expression::FunctionCall { callee, args, .. } => {
+                let Expression::Variable { name, .. } = callee.as_ref() else {
+                    return Err("Only functions called by name are supported in bytecode".to_string());
+                };
+                let name = name.as_str();
+                // Like the interpreter, `print` is built in
+                let function = match self.function_indices.get(name) {
+                    _ if name == "print" => None,
+                    Some(&function) if self.find_local(name).is_none() => Some(function),
+                    _ => return Err(format!("Only top-level functions can be called in bytecode, got {}", name))
+                };
+
+                for arg in args {
+                    self.compile_expression(arg)?;
+                }
+                match function {
+                    Some(function) => self.emit(Instruction::Call { function, arg_count: args.len() }),
+                    None => self.emit(Instruction::Print(args.len()))
+                };
+            },
+
+            Expression::Block(statements) => {
+                self.begin_scope();
+                let mut has_result = false;
+                for statement in statements {
+                    if let Statement::Expression { result: true, expression } = statement {
+                        self.compile_expression(expression)?;
+                        has_result = true;
+                        break;
+                    }
+                    self.compile_statement(statement)?;
+                }
+                if !has_result {
+                    self.emit(Instruction::Constant(Value::Nil));
+                }
+                self.end_scope();
+            },
+
+            Expression::If { condition, then_branch, else_branch } => {
+                self.compile_expression(condition)?;
+                let else_jump = self.emit(Instruction::JumpIfFalse(0));
+                self.compile_expression(then_branch)?;
+                let end_jump = self.emit(Instruction::Jump(0));
+
+                // Only one branch runs, so the else branch starts from the same stack height as the then branch
+                self.stack_height -= 1;
+                self.patch_jump(else_jump);
+                match else_branch {
+                    Some(else_branch) => self.compile_expression(else_branch)?,
+                    None => { self.emit(Instruction::Constant(Value::Nil)); }
+                }
+                self.patch_jump(end_jump);
+            },
+
+            Expression::Loop(LoopType::While { condition, body }) => {
+                let start = self.code.len();
+                self.begin_loop();
+                self.compile_expression(condition)?;
+                let exit_jump = self.emit(Instruction::JumpIfFalse(0));
+                self.compile_expression(body)?;
+                self.emit(Instruction::Pop);
+                self.emit(Instruction::Jump(start));
+                self.patch_jump(exit_jump);
+                self.end_loop(start);
+            },
+            Expression::Loop(LoopType::Infinite { body }) => {
+                let start = self.code.len();
+                self.begin_loop();
+                self.compile_expression(body)?;
+                self.emit(Instruction::Pop);
+                self.emit(Instruction::Jump(start));
+                self.end_loop(start);
+            },
+            Expression::Loop(LoopType::DoWhile { body, condition }) => {
+                let start = self.code.len();
+                self.begin_loop();
+                self.compile_expression(body)?;
+                self.emit(Instruction::Pop);
+                // Continuing still checks the condition before the next iteration
+                let condition_start = self.code.len();
+                self.compile_expression(condition)?;
+                let exit_jump = self.emit(Instruction::JumpIfFalse(0));
+                self.emit(Instruction::Jump(start));
+                self.patch_jump(exit_jump);
+                self.end_loop(condition_start);
+            },
+
+            _ => return Err(format!("Unsupported expression in bytecode: {:?}", expression))
+        }
+        Ok(())
+    }
+
+    fn begin_loop(&mut self) {
+        self.loops.push(LoopJumps {
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            stack_height: self.stack_height
+        });
+    }
+
+    /// Points the loop's `continue`s at `continue_target` and its `break`s here, then pushes the loop's nil value.
+    fn end_loop(&mut self, continue_target: usize) {
+        let loop_jumps = self.loops.pop().expect("Loops are ended after they begin");
+        for jump in loop_jumps.continues {
+            self.code[jump] = Instruction::Jump(continue_target);
+        }
+        for jump in loop_jumps.breaks {
+            self.patch_jump(jump);
+        }
+        self.emit(Instruction::Constant(Value::Nil));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::{self, Write}, rc::Rc};
+
+    use super::{vm::VM, *};
+    use crate::{interpreter::{resolver::Resolver, Interpreter, InterpreterResult}, parser::Parser, tokenizer::Tokenizer};
+
+    /// Collects printed output so a test can read it afterward.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl SharedOutput {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Runs `main` with both the tree-walking interpreter and the VM, returning what each returned and printed.
+    fn run_both(source: &str) -> ((InterpreterResult, String), (InterpreterResult, String)) {
+        let mut tokenizer = Tokenizer::new(source.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program().unwrap();
+
+        let interpreter_output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(interpreter_output.clone()));
+        Resolver::new(&mut interpreter).resolve_program(&program).unwrap();
+        interpreter.load(&program).unwrap();
+        let expected = interpreter.call_function("main", vec![]);
+
+        let bytecode = compile_program(&program).unwrap();
+        let vm_output = SharedOutput::default();
+        let actual = VM::with_output(&bytecode, Box::new(vm_output.clone())).run();
+        ((expected, interpreter_output.contents()), (actual, vm_output.contents()))
+    }
+
+    #[test]
+    fn test_matches_interpreter() {
+        let programs = [
+            "func main() -> f64 { 1 + 2 * 3 - -4 ~/ 3 + 7 / 2 }",
+            "func main() -> bool { !(1 < 2) || 3 % 2 == 1 && 2.5 >= 2 }",
+            r#"func main() -> str { let s: str = "a"; s += "b"; let t: str = { let s: str = "c"; s }; t + s }"#,
+            r#"
+                func fib(n: i32) -> i32 {
+                    if (n < 2) { return n; };
+                    fib(n - 1) + fib(n - 2)
+                }
+                func main() -> i32 { fib(15) }
+            "#,
+            r#"
+                func add(a: i32, b: i32) -> i32 { a + b }
+                func main() -> i32 {
+                    let total: i32 = 0;
+                    let i: i32 = 0;
+                    loop (i < 10) {
+                        i += 1;
+                        if (i % 2 == 0) { continue; };
+                        if (i > 7) { break; };
+                        total += i;
+                    };
+                    loop {
+                        total *= 2;
+                        // Breaking and continuing discard the arguments that were already evaluated
+                        total = add(total, if (total > 100) { break; } else { add(1, if (total < 50) { continue; } else { 0 }) });
+                    };
+                    loop { i -= 1; } while (i > 0);
+                    total + i
+                }
+            "#,
+            "func main() -> i32 { let x: i32 = 1; let y: i32 = if (x > 0) { x } else { 0 - x }; y }",
+            "func main() -> i32 { 1 ~/ 0 }",
+            "func main() -> i32 { 9223372036854775807 + 1 }",
+            r#"func main() -> nil { print(1 + 2, "three"); let x: nil = print(4.5); print(x); }"#
+        ];
+
+        for program in programs {
+            let (expected, actual) = run_both(program);
+            assert_eq!(actual, expected, "{}", program);
+        }
+    }
+
+    #[test]
+    fn test_print() {
+        let (_, (result, output)) = run_both(r#"func main() -> i32 { print(1, "two"); loop (false) { print(3); }; print('c'); 0 }"#);
+        assert_eq!(result, Ok(Value::Integer(0)));
+        assert_eq!(output, "1\ntwo\nc\n");
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut tokenizer = Tokenizer::new("func main() -> [i32] { [1, 2] }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        assert!(compile_program(&program).is_err());
+    }
+}
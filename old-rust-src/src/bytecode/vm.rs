@@ -0,0 +1,140 @@
+use std::io::{self, Write};
+
+use crate::interpreter::{binary_operation, condition_boolean, unary_operation, value::Value, InterpreterControl, InterpreterResult, DEFAULT_MAX_DEPTH};
+
+use super::{Bytecode, Instruction};
+
+/// A function call in progress.
+struct Frame {
+    function: usize,
+    ip: usize,
+    locals: Vec<Value>,
+    /// Where this call's values start on the shared stack
+    stack_base: usize
+}
+
+/// Runs compiled bytecode, as an alternative to the tree-walking `Interpreter`.
+/// Operators behave exactly like they do in the interpreter, including their runtime errors.
+pub struct VM<'a> {
+    bytecode: &'a Bytecode,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    output: Box<dyn Write>
+}
+
+impl<'a> VM<'a> {
+    pub fn new(bytecode: &'a Bytecode) -> Self {
+        VM {
+            bytecode,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            output: Box::new(io::stdout())
+        }
+    }
+
+    /// Creates a VM whose `print` writes to `output` instead of stdout, for capturing or redirecting it.
+    pub fn with_output(bytecode: &'a Bytecode, output: Box<dyn Write>) -> Self {
+        let mut vm = VM::new(bytecode);
+        vm.output = output;
+        vm
+    }
+
+    /// Replaces where `print` writes to, which is stdout by default.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Runs the program's `main` function, if it has one, and returns its result.
+    pub fn run(&mut self) -> InterpreterResult {
+        match self.bytecode.main {
+            Some(main) => self.call_function(main, vec![]),
+            None => Ok(Value::Nil)
+        }
+    }
+
+    /// Calls a compiled function by its index and runs it to completion.
+    pub fn call_function(&mut self, function: usize, args: Vec<Value>) -> InterpreterResult {
+        self.stack.clear();
+        self.frames.clear();
+        self.stack.extend(args);
+        self.push_frame(function, self.stack.len())?;
+        self.execute()
+    }
+
+    /// Starts a call to a function whose arguments are the top `arg_count` values on the stack.
+    fn push_frame(&mut self, function: usize, arg_count: usize) -> InterpreterResult<()> {
+        let compiled = &self.bytecode.functions[function];
+        if arg_count != compiled.arity {
+            return Err(InterpreterControl::RuntimeError(format!("{} expected {} arguments but got {}", compiled.name, compiled.arity, arg_count)));
+        }
+        if self.frames.len() >= DEFAULT_MAX_DEPTH {
+            return Err(InterpreterControl::RuntimeError("Maximum recursion depth exceeded".to_string()));
+        }
+
+        let stack_base = self.stack.len() - arg_count;
+        let mut locals: Vec<Value> = self.stack.drain(stack_base..).collect();
+        locals.resize(compiled.local_count, Value::Nil);
+        self.frames.push(Frame { function, ip: 0, locals, stack_base });
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("The compiler keeps the stack balanced")
+    }
+
+    fn execute(&mut self) -> InterpreterResult {
+        let bytecode = self.bytecode;
+        loop {
+            let frame = self.frames.last_mut().expect("There's always a frame while executing");
+            let instruction = &bytecode.functions[frame.function].code[frame.ip];
+            frame.ip += 1;
+
+            match instruction {
+                Instruction::Constant(value) => self.stack.push(value.clone()),
+                Instruction::Pop => { self.pop(); },
+                Instruction::LoadLocal(slot) => {
+                    let value = frame.locals[*slot].clone();
+                    self.stack.push(value);
+                },
+                Instruction::StoreLocal(slot) => {
+                    frame.locals[*slot] = self.stack.last().expect("The compiler keeps the stack balanced").clone();
+                },
+                Instruction::Binary(operator) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(binary_operation(operator, left, right)?);
+                },
+                Instruction::Unary(operator) => {
+                    let operand = self.pop();
+                    self.stack.push(unary_operation(operator, operand)?);
+                },
+                Instruction::Jump(target) => frame.ip = *target,
+                Instruction::JumpIfFalse(target) => {
+                    let target = *target;
+                    if !condition_boolean(self.pop())? {
+                        self.frames.last_mut().unwrap().ip = target;
+                    }
+                },
+                Instruction::Call { function, arg_count } => self.push_frame(*function, *arg_count)?,
+                Instruction::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.stack.truncate(frame.stack_base);
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                },
+                Instruction::Print(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count);
+                    for value in values {
+                        if let Err(e) = writeln!(self.output, "{}", value) {
+                            return Err(InterpreterControl::RuntimeError(format!("Failed to write output: {}", e)));
+                        }
+                    }
+                    self.stack.push(Value::Nil);
+                }
+            }
+        }
+    }
+}
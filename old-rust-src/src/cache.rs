@@ -0,0 +1,174 @@
+use std::{collections::HashMap, fs, hash::{Hash, Hasher}, path::PathBuf};
+
+use crate::{interpreter::{resolver::Resolver, Interpreter}, lint::LintWarning, parser::{ast::{ast_hash, Expression, FnvHasher, ExpressionId, Program}, visitor::{walk_expression, Visitor}}};
+
+/// The version of the cache's format and of what the resolver records in it.
+/// Bump this whenever either changes.
+const CACHE_VERSION: u32 = 4;
+
+/// Caches what the resolver finds in a program on disk, so running an unchanged program again can skip resolving it.
+/// Entries are keyed by the program's structure, how its expressions are numbered and where they are, and the contents of the files it
+/// imports, so changing any of them invalidates them.
+pub struct ResolutionCache {
+    dir: PathBuf,
+    /// How many programs were resolved from the cache instead of by the resolver
+    pub hits: usize
+}
+
+impl ResolutionCache {
+    pub fn new(dir: PathBuf) -> Self {
+        ResolutionCache { dir, hits: 0 }
+    }
+
+    /// Where the command line keeps its cache: the user's cache directory, so other users can't write entries for them.
+    /// Returns None if there's no such directory.
+    pub fn default_dir() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(dir.join("saffron"))
+    }
+
+    /// Resolves a program with the interpreter, returning the resolver's warnings. If neither the program nor its imports
+    /// have changed since it was last resolved, the resolver doesn't run, and its resolutions and warnings are read from the cache.
+    /// Programs that fail to resolve aren't cached.
+    pub fn resolve(&mut self, interpreter: &mut Interpreter, program: &Program, imports: &[PathBuf]) -> Result<Vec<LintWarning>, String> {
+        let path = self.dir.join(format!("{:016x}.resolved", cache_key(interpreter, program, imports)));
+        if let Some(entry) = fs::read_to_string(&path).ok().and_then(|contents| parse_entry(&contents)) {
+            for (expression_id, depth) in entry.locals {
+                interpreter.resolve(expression_id, depth);
+            }
+            self.hits += 1;
+            return Ok(entry.warnings);
+        }
+
+        let mut resolver = Resolver::new(interpreter);
+        resolver.resolve_program(program)?;
+        let warnings = resolver.take_warnings();
+        // Failing to write the cache only means the next run resolves the program again
+        let _ = fs::create_dir_all(&self.dir).and_then(|_| fs::write(&path, format_entry(interpreter.locals(), &warnings)));
+        Ok(warnings)
+    }
+}
+
+fn cache_key(interpreter: &Interpreter, program: &Program, imports: &[PathBuf]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    // A new version of Saffron might resolve the same program differently
+    CACHE_VERSION.hash(&mut hasher);
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    ast_hash(program).hash(&mut hasher);
+    // The cached resolutions refer to expressions by ID and the warnings give positions, so unlike `ast_hash`,
+    // this has to include how expressions are numbered and where they are
+    ExpressionIds { interpreter, hasher: &mut hasher }.visit_program(program);
+    for import in imports {
+        import.hash(&mut hasher);
+        fs::read(import).ok().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Hashes the ID and position of every expression in a program that has an ID.
+struct ExpressionIds<'a> {
+    interpreter: &'a Interpreter,
+    hasher: &'a mut FnvHasher
+}
+
+impl Visitor for ExpressionIds<'_> {
+    fn visit_expression(&mut self, expression: &Expression) {
+        if let Some(expression_id) = expression.id() {
+            expression_id.hash(self.hasher);
+            if let Some(span) = self.interpreter.span(expression_id) {
+                (span.line, span.column).hash(self.hasher);
+            }
+        }
+        walk_expression(self, expression);
+    }
+}
+
+/// What the resolver found in a program.
+struct Entry {
+    /// How many scopes up each resolved variable is, by the expression that refers to it
+    locals: Vec<(ExpressionId, usize)>,
+    warnings: Vec<LintWarning>
+}
+
+/// Writes each resolved variable as a line with its expression ID and scope depth, followed by a line for each warning.
+fn format_entry(locals: &HashMap<ExpressionId, usize>, warnings: &[LintWarning]) -> String {
+    let mut lines: Vec<(u32, usize)> = locals.iter().map(|(expression_id, depth)| (expression_id.0, *depth)).collect();
+    lines.sort();
+    let locals: String = lines.iter().map(|(expression_id, depth)| format!("{} {}\n", expression_id, depth)).collect();
+    let warnings: String = warnings.iter().map(|warning| format!("warning {}\n", warning.message)).collect();
+    locals + &warnings
+}
+
+/// Reads the format written by `format_entry`, returning None if the file is malformed.
+fn parse_entry(contents: &str) -> Option<Entry> {
+    let mut locals = Vec::new();
+    let mut warnings = Vec::new();
+    for line in contents.lines() {
+        if let Some(message) = line.strip_prefix("warning ") {
+            warnings.push(LintWarning { message: message.to_string() });
+            continue;
+        }
+        let (expression_id, depth) = line.split_once(' ')?;
+        locals.push((ExpressionId(expression_id.parse().ok()?), depth.parse().ok()?));
+    }
+    Some(Entry { locals, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{interpreter::value::Value, parser::Parser, tokenizer::Tokenizer};
+
+    use super::*;
+
+    #[test]
+    fn test_cache_hit() {
+        let dir = std::env::temp_dir().join(format!("saffron-cache-test-{}", std::process::id()));
+        let import = dir.join("util.saffron");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&import, "func helper() -> i32 { 1 }").unwrap();
+
+        let mut tokenizer = Tokenizer::new("func main() -> i32 { let x: i32 = 2; let unused: i32 = 0; { x * 3 } }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        // The same program with its expressions numbered differently, like it would be after an earlier file
+        let mut parser = Parser::new(tokens);
+        parser.start_ids_after(100);
+        let renumbered = parser.parse_program().unwrap();
+
+        let cache_dir = dir.join("cache");
+        let mut cache = ResolutionCache::new(cache_dir.clone());
+        let imports = [import.clone()];
+        let run = |cache: &mut ResolutionCache, program: &Program| {
+            let mut interpreter = Interpreter::new();
+            let warnings = cache.resolve(&mut interpreter, program, &imports).unwrap();
+            interpreter.load(program).unwrap();
+            let warnings: Vec<String> = warnings.iter().map(|warning| warning.to_string()).collect();
+            (interpreter.call_function("main", vec![]), warnings)
+        };
+        let unused = "Warning: Variable unused is never read; start its name with an underscore if that's intended";
+
+        assert_eq!(run(&mut cache, &program), (Ok(Value::Integer(6)), vec![unused.to_string()]));
+        assert_eq!(cache.hits, 0);
+
+        // A hit doesn't run the resolver, so it returns whatever warnings the entry has
+        let entry = fs::read_dir(&cache_dir).unwrap().next().unwrap().unwrap().path();
+        let contents = fs::read_to_string(&entry).unwrap();
+        fs::write(&entry, contents.replace("Variable unused", "Variable cached")).unwrap();
+        assert_eq!(run(&mut cache, &program), (Ok(Value::Integer(6)), vec![unused.replace("unused", "cached")]));
+        assert_eq!(cache.hits, 1);
+
+        // Resolutions for differently numbered expressions aren't reused
+        assert_eq!(run(&mut cache, &renumbered), (Ok(Value::Integer(6)), vec![unused.to_string()]));
+        assert_eq!(cache.hits, 1);
+
+        // Changing an import invalidates the cached result
+        fs::write(&import, "func helper() -> i32 { 2 }").unwrap();
+        assert_eq!(run(&mut cache, &program), (Ok(Value::Integer(6)), vec![unused.to_string()]));
+        let hits = cache.hits;
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(hits, 1);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{collections::HashSet, io::{self, BufRead, Write}};
+
+use crate::{interpreter::{EvaluationHook, Interpreter}, parser::ast::{Expression, Span}};
+
+/// A line-based debugger. It pauses before running code on a breakpoint line, shows the variables in scope,
+/// and reads a command: `step` (or `s`) pauses again at the next line, and `continue` (or `c`) runs to the next breakpoint.
+pub struct Debugger {
+    breakpoints: HashSet<usize>,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    /// If we should pause at the next line, whether or not it has a breakpoint
+    stepping: bool,
+    /// The line of the most recently evaluated expression, so each line only pauses once each time it's reached
+    current_line: Option<usize>
+}
+
+enum Command {
+    Step,
+    Continue
+}
+
+impl Debugger {
+    pub fn new(breakpoints: impl IntoIterator<Item = usize>, input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        Debugger {
+            breakpoints: breakpoints.into_iter().collect(),
+            input,
+            output,
+            stepping: false,
+            current_line: None
+        }
+    }
+
+    fn pause(&mut self, interpreter: &Interpreter, span: Span) -> io::Result<Command> {
+        writeln!(self.output, "Paused at line {}", span)?;
+        for (name, value) in interpreter.current_bindings() {
+            writeln!(self.output, "  {} = {}", name, value)?;
+        }
+
+        loop {
+            write!(self.output, "(debug) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                // Without any more input, there's no way to resume after pausing, so stop pausing
+                self.breakpoints.clear();
+                return Ok(Command::Continue);
+            }
+            match line.trim() {
+                "s" | "step" => return Ok(Command::Step),
+                "c" | "continue" => return Ok(Command::Continue),
+                _ => writeln!(self.output, "Commands: step (s), continue (c)")?
+            }
+        }
+    }
+}
+
+impl EvaluationHook for Debugger {
+    fn before(&mut self, interpreter: &Interpreter, expression: &Expression) {
+        let Some(span) = expression.id().and_then(|id| interpreter.span(id)) else {
+            return;
+        };
+        if self.current_line == Some(span.line) {
+            return;
+        }
+        self.current_line = Some(span.line);
+
+        if self.stepping || self.breakpoints.contains(&span.line) {
+            // If the output or input fails, there's no one to debug for, so just keep running
+            self.stepping = matches!(self.pause(interpreter, span), Ok(Command::Step));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    use crate::{interpreter::resolver::Resolver, parser::Parser, tokenizer::Tokenizer};
+
+    use super::*;
+
+    /// Collects the debugger's output so the test can read it afterward.
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let source = "func main() -> i32 {\n    let x: i32 = 1;\n    x += 1;\n    let y: i32 = x * 2;\n    y\n}\n";
+        let mut tokenizer = Tokenizer::new(source.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program().unwrap();
+
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter).resolve_program(&program).unwrap();
+        interpreter.add_spans(parser.take_spans());
+        interpreter.set_hook(Box::new(Debugger::new(
+            [3],
+            Box::new(Cursor::new("bogus\nstep\ncontinue\n")),
+            Box::new(SharedOutput(output.clone()))
+        )));
+        interpreter.run(&program).unwrap();
+
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        assert_eq!(output, [
+            "Paused at line 3:5",
+            "  x = 1",
+            "(debug) Commands: step (s), continue (c)",
+            "(debug) Paused at line 4:20",
+            "  x = 2",
+            "(debug) "
+        ].join("\n"));
+    }
+}
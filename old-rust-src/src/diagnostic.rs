@@ -0,0 +1,101 @@
+use crate::parser::{ast::Span, ParseError};
+
+/// An error message that points at the code it's about, rendered like this:
+///
+/// ```text
+/// Error: Unexpected character: '$'
+///  --> main.saffron:2:13
+///   |
+/// 2 |     let x = $;
+///   |             ^
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// Where the problem starts, if it's known
+    pub span: Option<Span>,
+    /// How many characters to underline
+    pub length: usize
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Option<Span>, length: usize) -> Self {
+        Diagnostic {
+            message: message.into(),
+            span,
+            length
+        }
+    }
+
+    /// Creates a diagnostic underlining the token a parse error is about.
+    pub fn from_parse_error(error: &ParseError) -> Self {
+        match error.token() {
+            Some(token) => Diagnostic::new(
+                error.message(),
+                Some(Span { line: token.line, column: token.column }),
+                token.token_type.reverse_format().chars().count()
+            ),
+            None => Diagnostic::new(error.message(), None, 0)
+        }
+    }
+
+    /// Renders the diagnostic with the line of `source` it points at. `path` is only used to label the location.
+    pub fn render(&self, source: &str, path: &str) -> String {
+        let mut rendered = format!("Error: {}", self.message);
+        let Some(span) = self.span else {
+            return rendered;
+        };
+        rendered.push_str(&format!("\n --> {}:{}", path, span));
+
+        let Some(line) = source.lines().nth(span.line - 1) else {
+            return rendered;
+        };
+        let gutter = " ".repeat(span.line.to_string().len());
+        // Tabs are kept in the indentation so the underline lines up however wide they're displayed
+        let indentation: String = line.chars().take(span.column - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        rendered.push_str(&format!("\n{} |\n{} | {}\n{} | {}{}", gutter, span.line, line, gutter, indentation, "^".repeat(self.length.max(1))));
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::Parser, tokenizer::Tokenizer};
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let source = "func main() -> i32 {\n    let x: i32 = 1 +;\n    x\n}\n";
+        let mut tokenizer = Tokenizer::new(source.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        assert_eq!(parser.parse_program(), None);
+
+        let diagnostic = Diagnostic::from_parse_error(&parser.errors()[0]);
+        assert_eq!(diagnostic.render(source, "main.saffron"), [
+            "Error: Unexpected token: Semicolon. Expected an expression",
+            " --> main.saffron:2:21",
+            "  |",
+            "2 |     let x: i32 = 1 +;",
+            "  |                     ^"
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_tokenize_error() {
+        let source = "func main() -> i32 {\n\tlet x: i32 = $;\n}";
+        let mut tokenizer = Tokenizer::new(source.to_string());
+        let error = tokenizer.tokenize().unwrap_err();
+
+        let diagnostic = Diagnostic::new(error.to_string(), Some(error.span()), 1);
+        assert_eq!(diagnostic.render(source, "main.saffron"), [
+            "Error: Unexpected character: '$'",
+            " --> main.saffron:2:15",
+            "  |",
+            "2 | \tlet x: i32 = $;",
+            "  | \t             ^"
+        ].join("\n"));
+        assert_eq!(Diagnostic::new("No position", None, 0).render(source, "main.saffron"), "Error: No position");
+    }
+}
@@ -0,0 +1,136 @@
+use std::{cell::RefCell, collections::{HashMap, HashSet}, rc::Rc};
+
+use crate::parser::ast::{Type, VariableMutability};
+
+use super::value::Value;
+
+#[derive(Debug)]
+struct Binding {
+    value: Value,
+    mutability: VariableMutability,
+    /// The type the variable was declared with, if it was declared with one
+    variable_type: Option<Type>
+}
+
+/// A single scope of variables, linked to the scope enclosing it.
+/// Scopes are shared so that closures can keep the environment they were created in alive.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Binding>,
+    enclosing: Option<Rc<RefCell<Environment>>>
+}
+
+impl Environment {
+    pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing
+        }
+    }
+
+    /// Defines a variable in this scope, shadowing any existing variable with the same name.
+    pub fn define(&mut self, name: String, value: Value, mutability: VariableMutability) {
+        self.define_typed(name, value, mutability, None);
+    }
+
+    /// Defines a variable along with the type it was declared with, so assignments to it can be checked against the type.
+    pub fn define_typed(&mut self, name: String, value: Value, mutability: VariableMutability, variable_type: Option<Type>) {
+        self.values.insert(name, Binding { value, mutability, variable_type });
+    }
+
+    /// Looks up a variable in this scope or any enclosing scope.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(binding) => Some(binding.value.clone()),
+            None => self.enclosing.as_ref()?.borrow().get(name)
+        }
+    }
+
+    /// Looks up a variable in the scope `depth` levels above this one, as computed by the resolver.
+    pub fn get_at(environment: &Rc<RefCell<Environment>>, depth: usize, name: &str) -> Option<Value> {
+        let ancestor = Self::ancestor(environment, depth)?;
+        let value = ancestor.borrow().values.get(name).map(|binding| binding.value.clone());
+        value
+    }
+
+    /// Looks up the declared type of a variable in this scope or any enclosing scope.
+    pub fn type_of(&self, name: &str) -> Option<Type> {
+        match self.values.get(name) {
+            Some(binding) => binding.variable_type.clone(),
+            None => self.enclosing.as_ref()?.borrow().type_of(name)
+        }
+    }
+
+    /// Looks up the declared type of a variable in the scope `depth` levels above this one, as computed by the resolver.
+    pub fn type_at(environment: &Rc<RefCell<Environment>>, depth: usize, name: &str) -> Option<Type> {
+        let ancestor = Self::ancestor(environment, depth)?;
+        let variable_type = ancestor.borrow().values.get(name).and_then(|binding| binding.variable_type.clone());
+        variable_type
+    }
+
+    /// Assigns to an existing variable in this scope or any enclosing scope.
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
+        match self.values.get_mut(name) {
+            Some(binding) => Self::assign_binding(name, binding, value),
+            None => match &self.enclosing {
+                Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+                None => Err(format!("Undefined variable: {}", name))
+            }
+        }
+    }
+
+    /// Assigns to an existing variable in the scope `depth` levels above this one, as computed by the resolver.
+    pub fn assign_at(environment: &Rc<RefCell<Environment>>, depth: usize, name: &str, value: Value) -> Result<(), String> {
+        let Some(ancestor) = Self::ancestor(environment, depth) else {
+            return Err(format!("Undefined variable: {}", name));
+        };
+        let mut ancestor = ancestor.borrow_mut();
+        match ancestor.values.get_mut(name) {
+            Some(binding) => Self::assign_binding(name, binding, value),
+            None => Err(format!("Undefined variable: {}", name))
+        }
+    }
+
+    fn assign_binding(name: &str, binding: &mut Binding, value: Value) -> Result<(), String> {
+        if binding.mutability == VariableMutability::Immutable {
+            return Err(format!("Cannot assign to constant {}", name));
+        }
+        binding.value = value;
+        Ok(())
+    }
+
+    /// Returns the variables defined directly in this scope.
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.values.iter().map(|(name, binding)| (name, &binding.value))
+    }
+
+    /// Returns the scope enclosing this one, if there is one.
+    pub fn enclosing(&self) -> Option<Rc<RefCell<Environment>>> {
+        self.enclosing.clone()
+    }
+
+    /// Returns roughly how many bytes the values of the variables in this scope and its enclosing scopes have
+    /// allocated, skipping scopes and values already in `seen`.
+    pub fn reachable_size(environment: &Rc<RefCell<Environment>>, seen: &mut HashSet<*const ()>) -> usize {
+        let mut size = 0;
+        let mut current = Some(environment.clone());
+        while let Some(environment) = current {
+            if !seen.insert(Rc::as_ptr(&environment) as *const ()) {
+                break;
+            }
+            let environment = environment.borrow();
+            size += environment.values.values().map(|binding| binding.value.reachable_size(seen)).sum::<usize>();
+            current = environment.enclosing.clone();
+        }
+        size
+    }
+
+    fn ancestor(environment: &Rc<RefCell<Environment>>, depth: usize) -> Option<Rc<RefCell<Environment>>> {
+        let mut current = environment.clone();
+        for _ in 0..depth {
+            let next = current.borrow().enclosing.clone()?;
+            current = next;
+        }
+        Some(current)
+    }
+}
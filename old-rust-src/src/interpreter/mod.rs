@@ -1,11 +1,14 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::{HashMap, HashSet}, io::{self, BufRead, BufReader, Write}, rc::Rc};
 
-use value::Value;
+use environment::Environment;
+use resolver::{substitute_generics, Resolver};
+use value::{EnumValue, Function, InterfaceValue, MapEntries, MapHasher, NativeFunction, StructValue, Value};
 
-use crate::parser::ast::{BinaryOperator, Declaration, Expression, ExpressionId, LoopType, Program, Statement, Type, UnaryOperator};
+use crate::{parser::{ast::{BinaryOperator, Declaration, EnumVariant, Expression, ExpressionId, LoopType, Pattern, Program, Span, Statement, StructElement, Type, UnaryOperator, VariableMutability}, ParseError, Parser}, stack::{StackLimit, DEFAULT_MAX_STACK}, tokenizer::{TokenizeError, Tokenizer}};
 
-mod value;
-mod resolver;
+pub mod value;
+pub mod resolver;
+mod environment;
 
 #[derive(Debug, PartialEq)]
 pub enum InterpreterControl {
@@ -17,6 +20,30 @@ pub enum InterpreterControl {
 
 pub type InterpreterResult<T = Value> = Result<T, InterpreterControl>;
 
+/// An error produced while evaluating a source snippet with `Interpreter::eval_source`.
+#[derive(Debug, PartialEq)]
+pub enum EvalError {
+    Tokenize(TokenizeError),
+    Parse(Vec<ParseError>),
+    Resolve(String),
+    Runtime(InterpreterControl)
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::Tokenize(message) => write!(f, "Error: {}", message),
+            EvalError::Parse(errors) => {
+                let errors: Vec<String> = errors.iter().map(|error| format!("Error: {}", error)).collect();
+                write!(f, "{}", errors.join("\n"))
+            },
+            EvalError::Resolve(message) => write!(f, "{}", message),
+            EvalError::Runtime(InterpreterControl::RuntimeError(message)) => write!(f, "Runtime error: {}", message),
+            EvalError::Runtime(control) => write!(f, "Error: Unexpected control flow: {:?}", control)
+        }
+    }
+}
+
 macro_rules! runtime_error {
     ($msg:expr) => {
         Err(InterpreterControl::RuntimeError($msg.to_string()))
@@ -26,52 +53,659 @@ macro_rules! runtime_error {
     };
 }
 
+mod natives;
+
+/// The default limit on nested expression evaluation, including through function calls.
+/// Evaluation also fails once it's used more than the stack limit (see `set_max_stack`), which is what keeps
+/// runaway recursion from overflowing the stack on threads too small for this many levels.
+pub const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+/// Observes expressions as they're evaluated, e.g. so a debugger can implement breakpoints and stepping.
+/// Expressions that can be located in the source have an id (see `Expression::id`), and hooks can inspect the
+/// interpreter's state, like the variables in scope, through the interpreter they're given.
+pub trait EvaluationHook {
+    /// Called before an expression is evaluated.
+    fn before(&mut self, _interpreter: &Interpreter, _expression: &Expression) {}
+    /// Called after an expression is evaluated, with its value or the error or control flow it produced.
+    fn after(&mut self, _interpreter: &Interpreter, _expression: &Expression, _result: &InterpreterResult) {}
+}
+
 pub struct Interpreter {
-    locals: HashMap<ExpressionId, usize>
+    locals: HashMap<ExpressionId, usize>,
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    /// The environments of the calls and blocks that are waiting for the current one to finish
+    suspended_environments: Vec<Rc<RefCell<Environment>>>,
+    /// Source positions of expressions, from the parser
+    spans: HashMap<ExpressionId, Span>,
+    /// The innermost expression with a known position where the most recent runtime error happened
+    error_expression: Option<ExpressionId>,
+    /// How many expressions are currently being evaluated inside each other
+    depth: usize,
+    max_depth: usize,
+    stack: StackLimit,
+    hook: Option<Box<dyn EvaluationHook>>,
+    /// Where `read_line` reads from
+    input: Box<dyn BufRead>,
+    /// Where `print` writes to
+    output: Box<dyn Write>,
+    /// Where `eprint` and `eprintln` write to
+    error_output: Box<dyn Write>,
+    /// If programs can read input and write output, which embedders can turn off to sandbox them
+    io_allowed: bool,
+    /// The field names of each declared struct, in the order they're declared
+    structs: HashMap<String, Vec<String>>,
+    /// The methods of each declared struct, by name
+    methods: HashMap<String, HashMap<String, Rc<Function>>>,
+    /// The interfaces each declared struct implements
+    implements: HashMap<String, Vec<String>>,
+    /// The names of the methods each declared interface requires
+    interfaces: HashMap<String, Vec<String>>,
+    /// The variants of each declared enum
+    enums: HashMap<String, Vec<EnumVariant>>,
+    /// The generic parameters and aliased type of each declared type alias
+    type_aliases: HashMap<String, (Vec<String>, Type)>,
+    /// If integers that don't fit in the type they're stored as are clamped to it instead of being an error
+    saturating: bool,
+    /// Roughly how many bytes the strings, arrays, and other values in use have allocated. Values that have been
+    /// dropped stay counted until the limit is reached and what's still in use is measured again.
+    memory_used: usize,
+    max_memory: Option<usize>,
+    /// Hashes the keys of the maps the program creates
+    map_hasher: MapHasher
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        let mut globals = Environment::default();
+        natives::define_natives(&mut globals);
+        let globals = Rc::new(RefCell::new(globals));
         Interpreter {
-            locals: HashMap::new()
+            locals: HashMap::new(),
+            environment: globals.clone(),
+            suspended_environments: Vec::new(),
+            globals,
+            spans: HashMap::new(),
+            error_expression: None,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            stack: StackLimit::new(DEFAULT_MAX_STACK),
+            hook: None,
+            input: Box::new(BufReader::new(io::stdin())),
+            output: Box::new(io::stdout()),
+            error_output: Box::new(io::stderr()),
+            io_allowed: true,
+            structs: HashMap::new(),
+            methods: HashMap::new(),
+            implements: HashMap::new(),
+            interfaces: HashMap::new(),
+            enums: HashMap::new(),
+            type_aliases: HashMap::new(),
+            saturating: false,
+            memory_used: 0,
+            max_memory: None,
+            map_hasher: MapHasher::default()
+        }
+    }
+
+    /// Creates an interpreter whose `print` writes to `output` instead of stdout, for capturing or redirecting it.
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let mut interpreter = Interpreter::new();
+        interpreter.output = output;
+        interpreter
+    }
+
+    /// Sets how deeply expression evaluation can nest before failing with a runtime error.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Sets how many bytes of stack evaluating a program can use before failing, which is
+    /// `DEFAULT_MAX_STACK` by default. The interpreter should run on a thread with more stack than this.
+    pub fn set_max_stack(&mut self, max_stack: usize) {
+        self.stack.set_max(max_stack);
+    }
+
+    /// Sets whether integers that don't fit in a variable's type or a function's return type are clamped to the
+    /// type's bounds. By default, they're a runtime error.
+    pub fn set_saturating(&mut self, saturating: bool) {
+        self.saturating = saturating;
+    }
+
+    /// Sets how many bytes the values a program has in use at once can allocate before it fails with a runtime error.
+    /// Values a program can no longer reach don't count towards the limit. There's no limit by default.
+    pub fn set_max_memory(&mut self, max_memory: Option<usize>) {
+        self.max_memory = max_memory;
+    }
+
+    /// Returns roughly how many bytes the values in use have allocated. This can include values that have been dropped
+    /// since the memory in use was last measured.
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    /// Counts memory a value is about to allocate, failing if it would go over the limit.
+    fn allocate(&mut self, bytes: usize) -> InterpreterResult<()> {
+        let mut memory_used = self.memory_used.saturating_add(bytes);
+        if let Some(max_memory) = self.max_memory {
+            if memory_used > max_memory {
+                // Some of what's been counted may belong to values that have since been dropped, so measure again
+                memory_used = self.reachable_memory().saturating_add(bytes);
+                if memory_used > max_memory {
+                    return runtime_error!("Memory limit exceeded");
+                }
+            }
+        }
+        self.memory_used = memory_used;
+        Ok(())
+    }
+
+    /// Returns roughly how many bytes the values the program can still reach have allocated: its globals, the
+    /// variables of every call and block that's running, and everything they refer to.
+    fn reachable_memory(&self) -> usize {
+        let mut seen = HashSet::new();
+        [&self.globals, &self.environment].into_iter().chain(&self.suspended_environments)
+            .map(|environment| Environment::reachable_size(environment, &mut seen))
+            .sum()
+    }
+
+    /// Counts the memory a newly created value allocated.
+    fn track(&mut self, value: Value) -> InterpreterResult {
+        self.allocate(value.allocated_size())?;
+        Ok(value)
+    }
+
+    /// Sets the seed maps hash their keys with. Maps iterate in the same order whenever the seed is the same,
+    /// so this is only worth changing to check that a program doesn't depend on that order.
+    pub fn set_map_seed(&mut self, seed: u64) {
+        self.map_hasher = MapHasher::new(seed);
+    }
+
+    /// Replaces where `read_line` reads from, which is stdin by default.
+    pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+        self.input = input;
+    }
+
+    /// Replaces where `print` writes to, which is stdout by default.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    /// Replaces where `eprint` and `eprintln` write to, which is stderr by default.
+    pub fn set_error_output(&mut self, error_output: Box<dyn Write>) {
+        self.error_output = error_output;
+    }
+
+    /// Sets whether programs can use `print`, `eprint`, `eprintln`, and `read_line`. They're allowed by default.
+    pub fn set_io_allowed(&mut self, io_allowed: bool) {
+        self.io_allowed = io_allowed;
+    }
+
+    /// Fails with a runtime error if IO isn't allowed, naming the function that tried to use it.
+    pub(crate) fn require_io(&self, function: &str) -> InterpreterResult<()> {
+        if self.io_allowed {
+            Ok(())
+        } else {
+            runtime_error!("{} needs the IO capability, which isn't allowed here", function)
         }
     }
 
+    /// Sets a hook that's called around every expression evaluation, replacing any existing one.
+    pub fn set_hook(&mut self, hook: Box<dyn EvaluationHook>) {
+        self.hook = Some(hook);
+    }
+
+    /// Removes the evaluation hook, returning it if there was one.
+    pub fn take_hook(&mut self) -> Option<Box<dyn EvaluationHook>> {
+        self.hook.take()
+    }
+
     pub fn resolve(&mut self, expr_id: ExpressionId, depth: usize) {
         self.locals.insert(expr_id, depth);
     }
 
+    /// Returns how many scopes up each resolved local variable is, by the expression that refers to it.
+    pub fn locals(&self) -> &HashMap<ExpressionId, usize> {
+        &self.locals
+    }
+
+    /// Adds source positions for expressions, which are used to locate runtime errors.
+    pub fn add_spans(&mut self, spans: HashMap<ExpressionId, Span>) {
+        self.spans.extend(spans);
+    }
+
+    /// Returns where an expression is in the source, if it's known.
+    pub fn span(&self, expression_id: ExpressionId) -> Option<Span> {
+        self.spans.get(&expression_id).copied()
+    }
+
+    /// Returns the variables visible from the current scope, innermost first, excluding globals.
+    /// Variables shadowed by an inner scope aren't included.
+    pub fn current_bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = Vec::new();
+        let mut scope = Some(self.environment.clone());
+        while let Some(environment) = scope {
+            if Rc::ptr_eq(&environment, &self.globals) {
+                break;
+            }
+
+            let environment = environment.borrow();
+            let mut scope_bindings: Vec<(String, Value)> = environment.bindings()
+                .filter(|(name, _)| !bindings.iter().any(|(existing, _)| existing == *name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            scope_bindings.sort_by(|(a, _), (b, _)| a.cmp(b));
+            bindings.extend(scope_bindings);
+            scope = environment.enclosing();
+        }
+        bindings
+    }
+
+    /// Returns where the most recent runtime error happened, if its position is known.
+    pub fn error_span(&self) -> Option<Span> {
+        self.error_expression.and_then(|id| self.span(id))
+    }
+
+    /// Returns the expression the most recent runtime error happened in, if its position is known.
+    /// Programs parsed from several files give each file its own range of IDs, so this tells which file it's in.
+    pub fn error_expression(&self) -> Option<ExpressionId> {
+        self.error_expression
+    }
+
     pub fn run(&mut self, program: &Program) -> InterpreterResult<()> {
         // Initialize the interpreter state
+        self.load(program)?;
 
-        self.interpret_program(program)?;
+        if self.globals.borrow().get("main").is_some() {
+            self.call_function("main", vec![])?;
+        }
 
         Ok(())
     }
 
-    fn interpret_program(&mut self, program: &Program) -> InterpreterResult<()> {
-        for statement in &program.declarations {
-            self.interpret_declaration(statement)?;
+    /// Defines the program's top-level declarations without running `main`.
+    pub fn load(&mut self, program: &Program) -> InterpreterResult<()> {
+        // Global variables are initialized after everything else is defined, so they can call functions declared after them
+        let (variables, others): (Vec<&Declaration>, Vec<&Declaration>) = program.declarations.iter()
+            .partition(|declaration| matches!(declaration, Declaration::GlobalVariable { .. }));
+        for declaration in others.into_iter().chain(variables) {
+            self.interpret_declaration(declaration)?;
         }
         Ok(())
     }
+
+    /// Calls a global function, such as one defined by a loaded program, by name.
+    pub fn call_function(&mut self, name: &str, args: Vec<Value>) -> InterpreterResult {
+        self.error_expression = None;
+        let callee = self.globals.borrow().get(name);
+        match callee {
+            Some(callee) => self.call(callee, args),
+            None => runtime_error!("Undefined function: {}", name)
+        }
+    }
+
+    /// Evaluates a single expression and returns its value.
+    /// Variables in the expression should already be resolved through a `Resolver` using this interpreter.
+    pub fn eval_expression(&mut self, expression: &Expression) -> InterpreterResult {
+        self.error_expression = None;
+        self.interpret_expression(expression)
+    }
+
+    /// Tokenizes, parses, resolves, and evaluates a snippet containing a single expression, returning its value.
+    /// This lets hosts use Saffron as an expression language.
+    pub fn eval_source(&mut self, source: &str) -> Result<Value, EvalError> {
+        let mut tokenizer = Tokenizer::new(source.to_string());
+        let tokens = tokenizer.tokenize().map_err(EvalError::Tokenize)?;
+
+        let mut parser = Parser::new(tokens);
+        let expression = parser.parse_snippet().map_err(EvalError::Parse)?;
+        self.add_spans(parser.take_spans());
+
+        Resolver::new(self).resolve_snippet(&expression).map_err(EvalError::Resolve)?;
+        self.eval_expression(&expression).map_err(EvalError::Runtime)
+    }
+
+    fn call(&mut self, callee: Value, args: Vec<Value>) -> InterpreterResult {
+        let function = match callee {
+            Value::Function(function) => function,
+            Value::NativeFunction(native) => {
+                if let Some(arity) = native.arity {
+                    if args.len() != arity {
+                        return runtime_error!("{} expected {} arguments but got {}", native.name, arity, args.len());
+                    }
+                }
+                let result = (native.function)(self, args)?;
+                return self.track(result);
+            },
+            _ => return runtime_error!("Can only call functions, got {}", callee)
+        };
+        if args.len() != function.params.len() {
+            return runtime_error!("{} expected {} arguments but got {}", function.name, function.params.len(), args.len());
+        }
+
+        let mut environment = Environment::new(Some(function.closure.clone()));
+        for (index, (param, arg)) in function.params.iter().zip(args).enumerate() {
+            let param_type = function.param_types.as_ref().and_then(|types| types.get(index));
+            environment.define_typed(param.clone(), self.as_interface(arg, param_type)?, VariableMutability::Mutable, param_type.cloned());
+        }
+
+        let result = self.with_environment(Rc::new(RefCell::new(environment)), |interpreter| {
+            interpreter.interpret_expression(&function.body)
+        });
+        match result {
+            Ok(value) | Err(InterpreterControl::Return(value)) => self.fit_to_type(value, function.return_type.as_ref()),
+            Err(InterpreterControl::Break) => runtime_error!("Cannot break outside of a loop"),
+            Err(InterpreterControl::Continue) => runtime_error!("Cannot continue outside of a loop"),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Runs `f` with `environment` as the current scope, restoring the previous scope afterward.
+    fn with_environment<T>(&mut self, environment: Rc<RefCell<Environment>>, f: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        self.suspended_environments.push(previous);
+        let result = f(self);
+        if let Some(previous) = self.suspended_environments.pop() {
+            self.environment = previous;
+        }
+        result
+    }
+
+    /// Returns a struct's method with `self` bound to it, or None if the value isn't a struct or has no such method.
+    fn method(&self, value: &Value, name: &str) -> Option<Value> {
+        match value {
+            Value::Struct(instance) => self.methods.get(&instance.name)?.get(name).map(|method| bind_method(method, instance)),
+            Value::Interface(value) => value.vtable.get(name).map(|method| bind_method(method, &value.instance)),
+            _ => None
+        }
+    }
+
+    /// Applies a binary operator, calling the left operand's method for it if it's a struct that overloads the operator,
+    /// so `a + b` is `a.add(b)`.
+    fn binary_operation(&mut self, operator: &BinaryOperator, left_value: Value, right_value: Value) -> InterpreterResult {
+        if let Some(method) = operator.method_name().and_then(|name| self.method(&left_value, name)) {
+            return self.call(method, vec![right_value]);
+        }
+        binary_operation(operator, left_value, right_value)
+    }
+
+    /// Evaluates a call's arguments in order, expanding each spread array into its elements.
+    fn interpret_arguments(&mut self, args: &[Expression]) -> Result<Vec<Value>, InterpreterControl> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            if let Expression::Spread(array) = arg {
+                match self.interpret_expression(array)? {
+                    Value::Vector(elements) => values.extend(elements.borrow().iter().cloned()),
+                    value => return runtime_error!("Cannot spread {} into arguments; only arrays can be spread", value)
+                }
+            } else {
+                values.push(self.interpret_expression(arg)?);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Wraps a struct passed where an interface is expected, so calling its methods dispatches through the interface.
+    /// Values of any other type, or going anywhere an interface isn't expected, are returned as they are.
+    fn as_interface(&self, value: Value, value_type: Option<&Type>) -> InterpreterResult {
+        let Some(Type::Identifier { name: interface, .. }) = value_type.map(Type::non_optional) else {
+            return Ok(value);
+        };
+        let Some(required) = self.interfaces.get(interface) else {
+            return Ok(value);
+        };
+        let instance = match &value {
+            Value::Struct(instance) => instance.clone(),
+            Value::Interface(value) => value.instance.clone(),
+            _ => return Ok(value)
+        };
+        if !self.implements.get(&instance.name).is_some_and(|interfaces| interfaces.contains(interface)) {
+            return runtime_error!("Struct {} doesn't implement interface {}", instance.name, interface);
+        }
+
+        let methods = &self.methods[&instance.name];
+        let vtable = required.iter().filter_map(|method| Some((method.clone(), methods.get(method)?.clone()))).collect();
+        Ok(Value::Interface(Rc::new(InterfaceValue { interface: interface.clone(), instance, vtable })))
+    }
+
+    /// If `object.member` names a variant of a declared enum, like `Shape.Circle`, returns the enum's name and the variant.
+    /// Variables shadow enums, so this only applies to names that don't resolve to a local variable.
+    fn enum_variant(&self, object: &Expression, member: &str) -> InterpreterResult<Option<(String, EnumVariant)>> {
+        let Expression::Variable { name, expression_id } = object else {
+            return Ok(None);
+        };
+        if self.locals.contains_key(expression_id) {
+            return Ok(None);
+        }
+        let Some(variants) = self.enums.get(name.as_str()) else {
+            return Ok(None);
+        };
+        match variants.iter().find(|variant| variant.name == member) {
+            Some(variant) => Ok(Some((name.to_string(), variant.clone()))),
+            None => runtime_error!("Enum {} has no variant {}", name, member)
+        }
+    }
+
+    /// Creates a value of an enum variant, checking it's given one value for each of its fields.
+    fn enum_value(enum_name: String, variant: EnumVariant, values: Vec<Value>) -> InterpreterResult {
+        if values.len() != variant.fields.len() {
+            return runtime_error!("{}.{} expected {} arguments but got {}", enum_name, variant.name, variant.fields.len(), values.len());
+        }
+        Ok(Value::Enum(Rc::new(EnumValue { enum_name, variant: variant.name, values })))
+    }
+
+    /// Checks that an integer fits in the type it's being stored as, clamping it to the type's bounds if saturation is on.
+    /// Values of other types aren't checked.
+    fn fit_to_type(&self, value: Value, value_type: Option<&Type>) -> InterpreterResult {
+        let expanded;
+        let value_type = match value_type {
+            Some(value_type) if !self.type_aliases.is_empty() => {
+                expanded = self.expand_alias(value_type);
+                Some(&expanded)
+            },
+            value_type => value_type
+        };
+        let value = self.as_interface(value, value_type)?;
+        let (Value::Integer(integer), Some(value_type)) = (&value, value_type.map(Type::non_optional)) else {
+            return Ok(value);
+        };
+        let Some((min, max)) = value_type.integer_range() else {
+            return Ok(value);
+        };
+        if (min..=max).contains(integer) {
+            Ok(value)
+        } else if self.saturating {
+            Ok(Value::Integer((*integer).clamp(min, max)))
+        } else {
+            runtime_error!("{} is out of range for {}", integer, value_type)
+        }
+    }
+
+    /// Returns the numeric type an expression was declared with, if it has one: the type of a variable or parameter,
+    /// the suffix of a literal like `5u8`, or the target of a cast. Aliases and optionals are looked through.
+    fn declared_number_type(&self, expression: &Expression) -> Option<Type> {
+        let declared = match expression {
+            Expression::Variable { name, expression_id } => match self.locals.get(expression_id) {
+                Some(depth) => Environment::type_at(&self.environment, *depth, name.as_str()),
+                None => self.globals.borrow().type_of(name.as_str())
+            }?,
+            Expression::TypedIntegerLiteral(_, declared) |
+            Expression::TypedNumberLiteral(_, declared) |
+            Expression::Cast { target: declared, .. } => declared.clone(),
+            _ => return None
+        };
+        let declared = self.expand_alias(&declared).non_optional().clone();
+        (declared.integer_range().is_some() || matches!(declared, Type::F32 | Type::F64)).then_some(declared)
+    }
+
+    /// Evaluates what a loop or comprehension iterates over. Ranges are iterated without building an array of their
+    /// values first, so a loop over `0..9223372036854775807` can run until it breaks.
+    fn iterate(&mut self, iterable: &Expression) -> InterpreterResult<Box<dyn Iterator<Item = Value>>> {
+        if let Expression::Range { start, end, inclusive } = iterable {
+            return self.range(start, end, *inclusive);
+        }
+        match self.interpret_expression(iterable)? {
+            // Iterate over a snapshot so the body can modify the array
+            Value::Vector(values) => Ok(Box::new(values.borrow().clone().into_iter())),
+            iterable => runtime_error!("Cannot iterate over {}", iterable)
+        }
+    }
+
+    /// Evaluates a range's endpoints, returning the values in it.
+    fn range(&mut self, start: &Expression, end: &Expression, inclusive: bool) -> InterpreterResult<Box<dyn Iterator<Item = Value>>> {
+        let start = self.interpret_expression(start)?;
+        let end = self.interpret_expression(end)?;
+        match (start, end) {
+            (Value::Integer(start), Value::Integer(end)) if inclusive => Ok(Box::new((start..=end).map(Value::Integer))),
+            (Value::Integer(start), Value::Integer(end)) => Ok(Box::new((start..end).map(Value::Integer))),
+            // Code points that aren't characters, like surrogates, are skipped
+            (Value::Char(start), Value::Char(end)) if inclusive => Ok(Box::new((start..=end).map(Value::Char))),
+            (Value::Char(start), Value::Char(end)) => Ok(Box::new((start..end).map(Value::Char))),
+            (start, end) => runtime_error!("Range endpoints must both be integers or both be chars, got {} and {}", start.type_name(), end.type_name())
+        }
+    }
+
+    /// Replaces a type that's an alias, or an optional of one, with the type it stands for, like `u8` for `Byte`.
+    /// The resolver has already rejected aliases that refer to themselves.
+    fn expand_alias(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Optional(inner) => Type::Optional(Box::new(self.expand_alias(inner))),
+            Type::Identifier { name, generics } => match self.type_aliases.get(name) {
+                Some((params, alias)) if params.len() == generics.len() => {
+                    self.expand_alias(&substitute_generics(alias, &params.iter().cloned().zip(generics.iter().cloned()).collect()))
+                },
+                _ => ty.clone()
+            },
+            ty => ty.clone()
+        }
+    }
+
+    fn get_index(&self, object: &Value, index: &Value) -> InterpreterResult {
+        match (object, index) {
+            (Value::Vector(values), Value::Integer(i)) => {
+                let values = values.borrow();
+                match usize::try_from(*i).ok().and_then(|i| values.get(i)) {
+                    Some(value) => Ok(value.clone()),
+                    None => runtime_error!("Index {} out of bounds for length {}", i, values.len())
+                }
+            },
+            (Value::Tuple(values), Value::Integer(i)) => {
+                match usize::try_from(*i).ok().and_then(|i| values.get(i)) {
+                    Some(value) => Ok(value.clone()),
+                    None => runtime_error!("Index {} out of bounds for length {}", i, values.len())
+                }
+            },
+            (Value::Map(map), key) => {
+                if let Err(message) = key.check_hashable() {
+                    return runtime_error!(message);
+                }
+                let value = map.borrow().get(key).cloned();
+                match value {
+                    Some(value) => Ok(value),
+                    None => runtime_error!("Key not found in map: {}", key)
+                }
+            },
+            _ => runtime_error!("Cannot index {} with {}", object, index)
+        }
+    }
+
+    /// Assigns to an array element or map entry. Assigning to a missing map key inserts it.
+    fn set_index(&mut self, object: &Value, index: Value, value: Value) -> InterpreterResult<()> {
+        match (object, &index) {
+            (Value::Vector(values), Value::Integer(i)) => {
+                let mut values = values.borrow_mut();
+                let length = values.len();
+                match usize::try_from(*i).ok().and_then(|i| values.get_mut(i)) {
+                    Some(element) => {
+                        *element = value;
+                        Ok(())
+                    },
+                    None => runtime_error!("Index {} out of bounds for length {}", i, length)
+                }
+            },
+            (Value::Map(map), key) => {
+                if let Err(message) = key.check_hashable() {
+                    return runtime_error!(message);
+                }
+                // A new key grows the map in place
+                if !map.borrow().contains_key(&index) {
+                    self.allocate(2 * std::mem::size_of::<Value>())?;
+                }
+                map.borrow_mut().insert(index, value);
+                Ok(())
+            },
+            (Value::Tuple(_), _) => runtime_error!("Cannot assign to an element of a tuple"),
+            _ => runtime_error!("Cannot index {} with {}", object, index)
+        }
+    }
+
+    fn look_up_variable(&self, name: &str, expression_id: ExpressionId) -> InterpreterResult {
+        let value = match self.locals.get(&expression_id) {
+            Some(depth) => Environment::get_at(&self.environment, *depth, name),
+            None => self.globals.borrow().get(name)
+        };
+        match value {
+            Some(value) => Ok(value),
+            None => runtime_error!("Undefined variable: {}", name)
+        }
+    }
+
     fn interpret_declaration(&mut self, declaration: &Declaration) -> InterpreterResult<()> {
         match declaration {
-            Declaration::Function { name, params, return_type, body, generic_args } => {
-                // TODO: Functions
-                // TEMPORARY
-                if name == "main" {
-                    self.interpret_expression(body)?;
-                }
+            Declaration::Function { name, params, return_type, body, .. } => {
+                let function = Function {
+                    name: name.clone(),
+                    params: params.iter().map(|param| param.name.clone()).collect(),
+                    param_types: Some(params.iter().map(|param| param.param_type.clone()).collect()),
+                    return_type: Some(return_type.clone()),
+                    body: body.clone(),
+                    closure: self.environment.clone()
+                };
+                self.environment.borrow_mut().define(name.clone(), Value::Function(Rc::new(function)), VariableMutability::Immutable);
             },
-            Declaration::Import { path } => {
+            Declaration::Import { .. } => {
                 // TODO: Imports
             },
-            Declaration::Struct { name, elements: declarations, generic_args } => {
+            Declaration::Struct { name, elements, interfaces, .. } => {
+                let fields = elements.iter().filter_map(|element| match element {
+                    StructElement::Field { name, .. } => Some(name.clone()),
+                    StructElement::Declaration(_) => None
+                }).collect();
+                self.structs.insert(name.clone(), fields);
 
+                let methods = elements.iter().filter_map(|element| match element {
+                    StructElement::Declaration(Declaration::Function { name: method, params, return_type, body, .. }) => Some((method.clone(), Rc::new(Function {
+                        name: format!("{}.{}", name, method),
+                        params: params.iter().map(|param| param.name.clone()).collect(),
+                        param_types: Some(params.iter().map(|param| param.param_type.clone()).collect()),
+                        return_type: Some(return_type.clone()),
+                        body: body.clone(),
+                        closure: self.environment.clone()
+                    }))),
+                    _ => None
+                }).collect();
+                self.methods.insert(name.clone(), methods);
+                self.implements.insert(name.clone(), interfaces.clone());
             },
-            Declaration::TypeDeclaration { name, alias, generic_args } => {
-
+            Declaration::Interface { name, methods } => {
+                self.interfaces.insert(name.clone(), methods.iter().map(|method| method.name.clone()).collect());
+            },
+            Declaration::Enum { name, variants, .. } => {
+                self.enums.insert(name.clone(), variants.clone());
+            },
+            Declaration::TypeDeclaration { name, generic_args, alias } => {
+                self.type_aliases.insert(name.clone(), (generic_args.clone(), alias.clone()));
+            },
+            Declaration::GlobalVariable { mutability, name, variable_type, value } => {
+                let value = self.interpret_expression(value)?;
+                let value = self.fit_to_type(value, Some(variable_type))?;
+                self.environment.borrow_mut().define_typed(name.clone(), value, *mutability, Some(variable_type.clone()));
             }
         }
         Ok(())
@@ -80,155 +714,290 @@ impl Interpreter {
         match statement {
             Statement::Declaration(declaration) => {
                 self.interpret_declaration(declaration)?;
-                return Ok(());
+                Ok(())
             },
             Statement::Break => {
-                return Err(InterpreterControl::Break);
+                Err(InterpreterControl::Break)
             },
             Statement::Continue => {
-                return Err(InterpreterControl::Continue);
+                Err(InterpreterControl::Continue)
             },
             Statement::Return(value) => {
-                return Err(InterpreterControl::Return(value
+                Err(InterpreterControl::Return(value
                     .as_ref()
-                    .map(|v| self.interpret_expression(&v))
+                    .map(|v| self.interpret_expression(v))
                     .unwrap_or(Ok(Value::Nil))?
-                ));
+                ))
             },
 
             Statement::Expression { expression, result } => {
                 let value = self.interpret_expression(expression)?;
                 if *result {
-                    return Err(InterpreterControl::Return(value));
+                    Err(InterpreterControl::Return(value))
                 } else {
-                    return Ok(());
+                    Ok(())
                 }
             },
 
-            Statement::VariableDeclaration { mutability, name, variable_type, value } => {
-                todo!()
+            Statement::VariableDeclaration { mutability, name, variable_type, value, .. } => {
+                let value = self.interpret_expression(value)?;
+                let value = self.fit_to_type(value, Some(variable_type))?;
+                self.environment.borrow_mut().define_typed(name.clone(), value, *mutability, Some(variable_type.clone()));
+                Ok(())
             }
-        };
+        }
     }
     fn interpret_expression(&mut self, expression: &Expression) -> InterpreterResult {
+        if self.depth == 0 {
+            self.stack.restart();
+        }
+        if self.depth >= self.max_depth || self.stack.exceeded() {
+            return runtime_error!("Maximum recursion depth exceeded");
+        }
+
+        // The hook is taken out while it runs so that it can be given the interpreter
+        if let Some(mut hook) = self.hook.take() {
+            hook.before(self, expression);
+            self.hook = Some(hook);
+        }
+
+        self.depth += 1;
+        let result = self.interpret_expression_inner(expression);
+        self.depth -= 1;
+
+        if let Some(mut hook) = self.hook.take() {
+            hook.after(self, expression, &result);
+            self.hook = Some(hook);
+        }
+
+        // Errors are located at the innermost expression with a known position
+        if let Err(InterpreterControl::RuntimeError(_)) = result {
+            if self.error_expression.is_none() {
+                self.error_expression = expression.id().filter(|id| self.spans.contains_key(id));
+            }
+        }
+        result
+    }
+
+    fn interpret_expression_inner(&mut self, expression: &Expression) -> InterpreterResult {
         match expression {
             Expression::CharLiteral(c) => {
                 Ok(Value::Char(*c))
             },
             Expression::StringLiteral(s) => {
-                Ok(Value::String(s.clone()))
+                self.track(Value::String(s.clone()))
+            },
+            Expression::IntegerLiteral(n) => {
+                Ok(Value::Integer(*n))
             },
             Expression::NumberLiteral(n) => {
-                Ok(Value::Number(*n))
+                Ok(Value::Float(*n))
+            },
+            // The resolver already checked that the integer fits in its type
+            Expression::TypedIntegerLiteral(n, _) => {
+                Ok(Value::Integer(*n))
+            },
+            Expression::TypedNumberLiteral(n, literal_type) => {
+                Ok(typed_float(*n, literal_type))
             },
             Expression::BooleanLiteral(b) => {
                 Ok(Value::Boolean(*b))
             },
 
-            Expression::FunctionCall { callee, args } => {
-                // TODO
-                // TEMPORARY
+            Expression::FunctionCall { callee, args, .. } => {
+                // TEMPORARY: print is built in until there are native functions
                 if let Expression::Variable { name, .. } = callee.as_ref() {
-                    if name == "print" {
-                        for arg in args {
-                            let value = self.interpret_expression(arg)?;
-                            println!("{}", value);
+                    if name.as_str() == "print" {
+                        self.require_io("print")?;
+                        for value in self.interpret_arguments(args)? {
+                            if let Err(e) = writeln!(self.output, "{}", value) {
+                                return runtime_error!("Failed to write output: {}", e);
+                            }
                         }
                         return Ok(Value::default());
-                    } else {
-                        return runtime_error!("Unknown function: {}", name);
                     }
                 }
-                return runtime_error!("Unsupported function call: {:?}", expression);
-            },
 
-            Expression::BinaryOperation { left, operator, right } => {
-                // TODO: Short-circuit evaluation for logical operators
-                let left_value = self.interpret_expression(left)?;
-                let right_value = self.interpret_expression(right)?;
-                
-                match (operator, left_value, right_value) {
-                    (BinaryOperator::Add, Value::Number(l), Value::Number(r)) => {
-                        Ok(Value::Number(l + r))
-                    },
-                    (BinaryOperator::Add, Value::String(l), Value::String(r)) => {
-                        Ok(Value::String(format!("{}{}", l, r)))
-                    },
+                if let Expression::MemberAccess { object, member, .. } = callee.as_ref() {
+                    if let Some((enum_name, variant)) = self.enum_variant(object, member)? {
+                        let values = self.interpret_arguments(args)?;
+                        return Self::enum_value(enum_name, variant, values);
+                    }
+                }
 
-                    (BinaryOperator::Subtract, Value::Number(l), Value::Number(r)) => {
-                        Ok(Value::Number(l - r))
-                    },
-                    (BinaryOperator::Multiply, Value::Number(l), Value::Number(r)) => {
-                        Ok(Value::Number(l * r))
-                    },
-                    (BinaryOperator::Divide, Value::Number(l), Value::Number(r)) => {
-                        if r == 0.0 {
-                            return runtime_error!("Division by zero");
+                let optional = matches!(callee.as_ref(), Expression::MemberAccess { optional: true, .. });
+                let callee = self.interpret_expression(callee)?;
+                // Calling a method through `?.` on nil is nil too, without evaluating the arguments
+                if optional && callee == Value::Nil {
+                    return Ok(Value::Nil);
+                }
+                // `typeof` names a number by the type it was declared with, which only its argument's expression knows
+                if let (Value::NativeFunction(NativeFunction { name: "typeof", .. }), [arg]) = (&callee, args.as_slice()) {
+                    if let Some(declared) = self.declared_number_type(arg) {
+                        let value = self.interpret_expression(arg)?;
+                        if matches!(value, Value::Integer(_) | Value::Float(_)) {
+                            return Ok(Value::String(declared.to_string()));
                         }
-                        Ok(Value::Number(l / r))
-                    },
-                    (BinaryOperator::Modulus, Value::Number(l), Value::Number(r)) => {
-                        if r == 0.0 {
-                            return runtime_error!("Division by zero");
+                        return self.call(callee, vec![value]);
+                    }
+                }
+                let arg_values = self.interpret_arguments(args)?;
+                self.call(callee, arg_values)
+            },
+            // The parser only allows spreads in argument lists, which expand them themselves
+            Expression::Spread(_) => runtime_error!("Spread is only allowed in function call arguments"),
+            Expression::ArrayLiteral(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.interpret_expression(element)?);
+                }
+                self.track(Value::vector(values))
+            },
+            Expression::ArrayComprehension { element, binding, iterable, condition } => {
+                let values = self.iterate(iterable)?;
+                let mut results = Vec::new();
+                for value in values {
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+                    environment.define(binding.clone(), value, VariableMutability::Immutable);
+                    let result = self.with_environment(Rc::new(RefCell::new(environment)), |interpreter| {
+                        if let Some(condition) = condition {
+                            if !condition_boolean(interpreter.interpret_expression(condition)?)? {
+                                return Ok(None);
+                            }
                         }
-                        Ok(Value::Number(l % r))
-                    },
-                    (BinaryOperator::Equal, l, r) => {
-                        Ok(Value::Boolean(l == r))
-                    },
-                    (BinaryOperator::NotEqual, l, r) => {
-                        Ok(Value::Boolean(l != r))
-                    },
-
-                    (BinaryOperator::LessThan, Value::Number(l), Value::Number(r)) => {
-                        Ok(Value::Boolean(l < r))
-                    },
-                    (BinaryOperator::LessThanOrEqual, Value::Number(l), Value::Number(r)) => {
-                        Ok(Value::Boolean(l <= r))
-                    },
-                    (BinaryOperator::GreaterThan, Value::Number(l), Value::Number(r)) => {
-                        Ok(Value::Boolean(l > r))
-                    },
-                    (BinaryOperator::GreaterThanOrEqual, Value::Number(l), Value::Number(r)) => {
-                        Ok(Value::Boolean(l >= r))
-                    },
-                    
-                    (BinaryOperator::And, Value::Boolean(l), Value::Boolean(r)) => {
-                        Ok(Value::Boolean(l && r))
-                    },
-                    (BinaryOperator::Or, Value::Boolean(l), Value::Boolean(r)) => {
-                        Ok(Value::Boolean(l || r))
-                    },
-
-                    (_, l, r) => {
-                        return runtime_error!("Unsupported binary operation: {} {} {}", l, operator, r);
+                        interpreter.interpret_expression(element).map(Some)
+                    })?;
+                    results.extend(result);
+                }
+                self.track(Value::vector(results))
+            },
+            Expression::Range { start, end, inclusive } => {
+                let range = self.range(start, end, *inclusive)?;
+                // Counted before the array's built, so a huge range fails without allocating it
+                let (length, _) = range.size_hint();
+                self.allocate(length.saturating_mul(std::mem::size_of::<Value>()))?;
+                let mut values = Vec::new();
+                if values.try_reserve_exact(length).is_err() {
+                    return runtime_error!("A range of {} values is too large to store; loop over it directly instead", length);
+                }
+                values.extend(range);
+                Ok(Value::vector(values))
+            },
+            Expression::Tuple(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.interpret_expression(element)?);
+                }
+                self.track(Value::Tuple(values))
+            },
+            Expression::MapLiteral(entries) => {
+                let mut map = MapEntries::with_capacity_and_hasher(entries.len(), self.map_hasher);
+                for (key, value) in entries {
+                    let key = self.interpret_expression(key)?;
+                    if let Err(message) = key.check_hashable() {
+                        return runtime_error!(message);
                     }
+                    let value = self.interpret_expression(value)?;
+                    map.insert(key, value);
                 }
+                self.track(Value::Map(Rc::new(RefCell::new(map))))
+            },
+            Expression::Array { size, initial_value, .. } => {
+                let size = match self.interpret_expression(size)? {
+                    Value::Integer(size) if size >= 0 => size as usize,
+                    size => return runtime_error!("Array size must be a non-negative integer, got {}", size)
+                };
+                // Counted before the array's built, so a huge one fails without allocating it
+                self.allocate(size.saturating_mul(std::mem::size_of::<Value>()))?;
+                // The initial value is evaluated for each element so arrays of arrays don't share their elements
+                let mut values = Vec::with_capacity(size);
+                for _ in 0..size {
+                    values.push(self.interpret_expression(initial_value)?);
+                }
+                Ok(Value::vector(values))
+            },
+            Expression::Index { object, index, .. } => {
+                let object = self.interpret_expression(object)?;
+                let index = self.interpret_expression(index)?;
+                self.get_index(&object, &index)
+            },
+            Expression::IndexAssignment { object, index, operator, value, .. } => {
+                let object = self.interpret_expression(object)?;
+                let index = self.interpret_expression(index)?;
+                let mut value = self.interpret_expression(value)?;
+                if let Some(operator) = operator {
+                    let current = self.get_index(&object, &index)?;
+                    value = self.binary_operation(operator, current, value)?;
+                }
+                self.set_index(&object, index, value.clone())?;
+                Ok(value)
+            },
+            Expression::Lambda { params, body } => {
+                Ok(Value::Function(Rc::new(Function {
+                    name: "lambda".to_string(),
+                    params: params.clone(),
+                    param_types: None,
+                    return_type: None,
+                    body: body.clone(),
+                    closure: self.environment.clone()
+                })))
             },
 
-            Expression::UnaryOperation { operator, operand } => {
-                let operand_value = self.interpret_expression(operand)?;
-                match (operator, operand_value) {
-                    (UnaryOperator::Negate, Value::Number(n)) => {
-                        Ok(Value::Number(-n))
-                    },
-                    (UnaryOperator::Not, Value::Boolean(b)) => {
-                        Ok(Value::Boolean(!b))
-                    },
-                    (_, operand_value) => {
-                        return runtime_error!("Unsupported unary operation: {} {}", operator, operand_value);
-                    }
+            Expression::Variable { name, expression_id } => {
+                self.look_up_variable(name.as_str(), *expression_id)
+            },
+            Expression::Assignment { name, value, expression_id } => {
+                let value = self.interpret_expression(value)?;
+                let variable_type = match self.locals.get(expression_id) {
+                    Some(depth) => Environment::type_at(&self.environment, *depth, name.as_str()),
+                    None => self.globals.borrow().type_of(name.as_str())
+                };
+                let value = self.fit_to_type(value, variable_type.as_ref())?;
+                let result = match self.locals.get(expression_id) {
+                    Some(depth) => Environment::assign_at(&self.environment, *depth, name.as_str(), value.clone()),
+                    None => self.globals.borrow_mut().assign(name.as_str(), value.clone())
+                };
+                if let Err(message) = result {
+                    return runtime_error!(message);
                 }
+                Ok(value)
+            },
+
+            Expression::BinaryOperation { left, operator, right, .. } => {
+                // TODO: Short-circuit evaluation for logical operators
+                let left_value = self.interpret_expression(left)?;
+                let right_value = self.interpret_expression(right)?;
+                
+                let result = self.binary_operation(operator, left_value, right_value)?;
+                self.track(result)
+            },
+
+            Expression::UnaryOperation { operator, operand, .. } => {
+                let operand_value = self.interpret_expression(operand)?;
+                unary_operation(operator, operand_value)
             },
 
             Expression::Block(statements) => {
-                for statement in statements {
-                    if let Statement::Expression { result: true, expression } = statement {
-                        return Ok(self.interpret_expression(expression)?);
+                let environment = Rc::new(RefCell::new(Environment::new(Some(self.environment.clone()))));
+                self.with_environment(environment, |interpreter| {
+                    // Functions are hoisted, so they can be called from anywhere in their block like top-level functions can
+                    for statement in statements {
+                        if let Statement::Declaration(declaration @ Declaration::Function { .. }) = statement {
+                            interpreter.interpret_declaration(declaration)?;
+                        }
                     }
-                    _ = self.interpret_statement(statement)?;
-                }
-                Ok(Value::default())
+                    for statement in statements {
+                        if let Statement::Declaration(Declaration::Function { .. }) = statement {
+                            continue; // Already defined
+                        }
+                        if let Statement::Expression { result: true, expression } = statement {
+                            return interpreter.interpret_expression(expression);
+                        }
+                        interpreter.interpret_statement(statement)?;
+                    }
+                    // A block without a result expression, including an empty one, has no value
+                    Ok(Value::Nil)
+                })
             },
 
             Expression::Loop(LoopType::Infinite { body }) => {
@@ -248,18 +1017,39 @@ impl Interpreter {
                     };
                 }
             },
-            Expression::Loop(LoopType::While { condition, body }) => {
+            Expression::Loop(LoopType::DoWhile { body, condition }) => {
                 loop {
-                    let condition_value = self.interpret_expression(condition)?;
-                    if let Value::Boolean(false) = condition_value {
-                        return Ok(Value::default());
-                    }
                     match self.interpret_expression(body) {
                         Err(InterpreterControl::Break) => {
                             return Ok(Value::default());
                         },
-                        Err(InterpreterControl::Continue) => {
-                            continue;
+                        // Continuing still checks the condition before the next iteration
+                        Err(InterpreterControl::Continue) => (),
+
+                        Err(e) => {
+                            return Err(e);
+                        },
+                        Ok(_) => (),
+                    };
+
+                    let condition_value = self.interpret_expression(condition)?;
+                    if !condition_boolean(condition_value)? {
+                        return Ok(Value::default());
+                    }
+                }
+            },
+            Expression::Loop(LoopType::While { condition, body }) => {
+                loop {
+                    let condition_value = self.interpret_expression(condition)?;
+                    if !condition_boolean(condition_value)? {
+                        return Ok(Value::default());
+                    }
+                    match self.interpret_expression(body) {
+                        Err(InterpreterControl::Break) => {
+                            return Ok(Value::default());
+                        },
+                        Err(InterpreterControl::Continue) => {
+                            continue;
                         },
 
                         Err(e) => {
@@ -270,30 +1060,410 @@ impl Interpreter {
                 }
             },
             Expression::Loop(LoopType::Iterator { mutability, iterator, iterable, body }) => {
-                todo!()
+                let values = self.iterate(iterable)?;
+                for value in values {
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+                    environment.define(iterator.clone(), value, *mutability);
+                    let result = self.with_environment(Rc::new(RefCell::new(environment)), |interpreter| {
+                        interpreter.interpret_expression(body)
+                    });
+                    match result {
+                        Err(InterpreterControl::Break) => break,
+                        Err(InterpreterControl::Continue) | Ok(_) => continue,
+                        Err(e) => return Err(e)
+                    }
+                }
+                Ok(Value::default())
+            },
+            Expression::Loop(LoopType::WhileLet { mutability, binding, expr, body }) => {
+                loop {
+                    let value = self.interpret_expression(expr)?;
+                    if value == Value::Nil {
+                        break;
+                    }
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+                    environment.define(binding.clone(), value, *mutability);
+                    let result = self.with_environment(Rc::new(RefCell::new(environment)), |interpreter| {
+                        interpreter.interpret_expression(body)
+                    });
+                    match result {
+                        Err(InterpreterControl::Break) => break,
+                        Err(InterpreterControl::Continue) | Ok(_) => continue,
+                        Err(e) => return Err(e)
+                    }
+                }
+                Ok(Value::default())
             },
 
             Expression::If { condition, then_branch, else_branch } => {
                 let condition_value = self.interpret_expression(condition)?;
-                if let Value::Boolean(true) = condition_value {
-                    return self.interpret_expression(then_branch);
+                if condition_boolean(condition_value)? {
+                    self.interpret_expression(then_branch)
                 } else if let Some(else_branch) = else_branch {
-                    return self.interpret_expression(else_branch);
+                    self.interpret_expression(else_branch)
                 } else {
-                    return Ok(Value::default());
+                    Ok(Value::default())
+                }
+            },
+            Expression::StructCreation { struct_type, fields } => {
+                let Type::Identifier { name, .. } = struct_type else {
+                    return runtime_error!("Can only create structs, got {}", struct_type);
+                };
+                let Some(declared) = self.structs.get(name).cloned() else {
+                    return runtime_error!("Unknown struct: {}", name);
+                };
+
+                let mut values: Vec<Option<Value>> = vec![None; declared.len()];
+                for (field, value) in fields {
+                    let Some(index) = declared.iter().position(|declared| declared == field) else {
+                        return runtime_error!("Struct {} has no field {}", name, field);
+                    };
+                    if values[index].is_some() {
+                        return runtime_error!("Field {} of {} is given more than once", field, name);
+                    }
+                    values[index] = Some(self.interpret_expression(value)?);
+                }
+
+                let mut instance = StructValue { name: name.clone(), fields: Vec::with_capacity(declared.len()) };
+                for (field, value) in declared.into_iter().zip(values) {
+                    match value {
+                        Some(value) => instance.fields.push((field, value)),
+                        None => return runtime_error!("Missing field {} in {}", field, name)
+                    }
+                }
+                self.track(Value::Struct(Rc::new(instance)))
+            },
+            Expression::MemberAccess { object, member, optional } => {
+                if let Some((enum_name, variant)) = self.enum_variant(object, member)? {
+                    return Self::enum_value(enum_name, variant, Vec::new());
+                }
+
+                let object = self.interpret_expression(object)?;
+                if *optional && object == Value::Nil {
+                    return Ok(Value::Nil);
+                }
+                let field = match &object {
+                    Value::Struct(instance) => instance.field(member).cloned().or_else(|| self.method(&object, member)),
+                    _ => self.method(&object, member)
+                };
+                match (field, &object) {
+                    (Some(value), _) => Ok(value),
+                    (None, Value::Struct(instance)) => runtime_error!("Struct {} has no field or method {}", instance.name, member),
+                    (None, _) => runtime_error!("{} has no member {}", object, member)
                 }
             },
-            
-            _ => todo!("Unsupported expression: {:?}", expression)
+            Expression::Cast { value, target } => {
+                let value = self.interpret_expression(value)?;
+                cast(value, &self.expand_alias(target))
+            },
+            Expression::Match { value, arms } => {
+                let value = self.interpret_expression(value)?;
+                for arm in arms {
+                    let (enum_name, variant, bindings) = match &arm.pattern {
+                        Pattern::Wildcard => return self.interpret_expression(&arm.body),
+                        Pattern::Variant { enum_name, variant, bindings } => (enum_name, variant, bindings)
+                    };
+                    let Some(variants) = self.enums.get(enum_name) else {
+                        return runtime_error!("Unknown enum: {}", enum_name);
+                    };
+                    let Some(declared) = variants.iter().find(|declared| &declared.name == variant) else {
+                        return runtime_error!("Enum {} has no variant {}", enum_name, variant);
+                    };
+                    if bindings.len() != declared.fields.len() {
+                        return runtime_error!("{}.{} has {} values but the pattern binds {}", enum_name, variant, declared.fields.len(), bindings.len());
+                    }
+
+                    let Value::Enum(instance) = &value else {
+                        continue;
+                    };
+                    if &instance.enum_name != enum_name || &instance.variant != variant {
+                        continue;
+                    }
+
+                    let mut environment = Environment::new(Some(self.environment.clone()));
+                    for (binding, value) in bindings.iter().zip(&instance.values) {
+                        environment.define(binding.clone(), value.clone(), VariableMutability::Immutable);
+                    }
+                    return self.with_environment(Rc::new(RefCell::new(environment)), |interpreter| {
+                        interpreter.interpret_expression(&arm.body)
+                    });
+                }
+                runtime_error!("No match arm matches {}", value)
+            }
         }
     }
 } 
 
+/// Returns the value of a float literal with a type suffix, rounding it to an `f32`'s precision if it has one.
+pub(crate) fn typed_float(n: f64, literal_type: &Type) -> Value {
+    match literal_type {
+        Type::F32 => Value::Float(n as f32 as f64),
+        _ => Value::Float(n)
+    }
+}
+
+/// Applies a binary operator to two already-evaluated operands.
+pub(crate) fn binary_operation(operator: &BinaryOperator, left_value: Value, right_value: Value) -> InterpreterResult {
+    match (operator, left_value, right_value) {
+        (BinaryOperator::Add, Value::Integer(l), Value::Integer(r)) => {
+            checked_integer(l.checked_add(r))
+        },
+        (BinaryOperator::Subtract, Value::Integer(l), Value::Integer(r)) => {
+            checked_integer(l.checked_sub(r))
+        },
+        (BinaryOperator::Multiply, Value::Integer(l), Value::Integer(r)) => {
+            checked_integer(l.checked_mul(r))
+        },
+        (BinaryOperator::Divide, Value::Integer(l), Value::Integer(r)) => {
+            float_operation(operator, l as f64, r as f64)
+        },
+        (BinaryOperator::FloorDivide, Value::Integer(l), Value::Integer(r)) => {
+            if r == 0 {
+                return runtime_error!("Division by zero");
+            }
+            checked_integer(l.checked_div(r).map(|quotient| {
+                // Integer division truncates toward zero, so round down when the signs differ and there's a remainder
+                if l % r != 0 && (l < 0) != (r < 0) { quotient - 1 } else { quotient }
+            }))
+        },
+        (BinaryOperator::Modulus, Value::Integer(l), Value::Integer(r)) => {
+            if r == 0 {
+                return runtime_error!("Division by zero");
+            }
+            checked_integer(l.checked_rem(r))
+        },
+        (BinaryOperator::BitwiseAnd, Value::Integer(l), Value::Integer(r)) => {
+            Ok(Value::Integer(l & r))
+        },
+        (BinaryOperator::BitwiseOr, Value::Integer(l), Value::Integer(r)) => {
+            Ok(Value::Integer(l | r))
+        },
+        (BinaryOperator::BitwiseXor, Value::Integer(l), Value::Integer(r)) => {
+            Ok(Value::Integer(l ^ r))
+        },
+        (BinaryOperator::ShiftLeft, Value::Integer(l), Value::Integer(r)) => {
+            shift_amount(r).map(|amount| Value::Integer(l << amount))
+        },
+        (BinaryOperator::ShiftRight, Value::Integer(l), Value::Integer(r)) => {
+            shift_amount(r).map(|amount| Value::Integer(l >> amount))
+        },
+        (BinaryOperator::LessThan, Value::Integer(l), Value::Integer(r)) => {
+            Ok(Value::Boolean(l < r))
+        },
+        (BinaryOperator::LessThanOrEqual, Value::Integer(l), Value::Integer(r)) => {
+            Ok(Value::Boolean(l <= r))
+        },
+        (BinaryOperator::GreaterThan, Value::Integer(l), Value::Integer(r)) => {
+            Ok(Value::Boolean(l > r))
+        },
+        (BinaryOperator::GreaterThanOrEqual, Value::Integer(l), Value::Integer(r)) => {
+            Ok(Value::Boolean(l >= r))
+        },
+
+        // Mixed integer and float operands are promoted to floats
+        (operator, Value::Integer(l), Value::Float(r)) if operator.is_arithmetic_or_comparison() => {
+            float_operation(operator, l as f64, r)
+        },
+        (operator, Value::Float(l), Value::Integer(r)) if operator.is_arithmetic_or_comparison() => {
+            float_operation(operator, l, r as f64)
+        },
+        (operator, Value::Float(l), Value::Float(r)) if operator.is_arithmetic_or_comparison() => {
+            float_operation(operator, l, r)
+        },
+        (BinaryOperator::Add, Value::String(l), Value::String(r)) => {
+            Ok(Value::String(format!("{}{}", l, r)))
+        },
+
+        (BinaryOperator::Equal, l, r) => {
+            Ok(Value::Boolean(l == r))
+        },
+        (BinaryOperator::NotEqual, l, r) => {
+            Ok(Value::Boolean(l != r))
+        },
+
+        (BinaryOperator::And, Value::Boolean(l), Value::Boolean(r)) => {
+            Ok(Value::Boolean(l && r))
+        },
+        (BinaryOperator::Or, Value::Boolean(l), Value::Boolean(r)) => {
+            Ok(Value::Boolean(l || r))
+        },
+
+        (_, l, r) => {
+            runtime_error!("Unsupported binary operation: {} {} {}", l, operator, r)
+        }
+    }
+}
+
+/// Applies a unary operator to an already-evaluated operand.
+pub(crate) fn unary_operation(operator: &UnaryOperator, operand_value: Value) -> InterpreterResult {
+    match (operator, operand_value) {
+        (UnaryOperator::Negate, Value::Integer(n)) => {
+            checked_integer(n.checked_neg())
+        },
+        (UnaryOperator::Negate, Value::Float(n)) => {
+            Ok(Value::Float(-n))
+        },
+        (UnaryOperator::Not, Value::Boolean(b)) => {
+            Ok(Value::Boolean(!b))
+        },
+        (UnaryOperator::BitwiseNot, Value::Integer(n)) => {
+            Ok(Value::Integer(!n))
+        },
+        (_, operand_value) => {
+            runtime_error!("Unsupported unary operation: {} {}", operator, operand_value)
+        }
+    }
+}
+
+/// Converts a value for an `as` cast, the same way Rust does: integers wrap around to fit narrower integer types,
+/// and floats are truncated toward zero, saturating at the bounds of the integer type.
+pub(crate) fn cast(value: Value, target: &Type) -> InterpreterResult {
+    match (value, target) {
+        // Casting unwraps an optional, so `x as i32` fails if `x` is nil
+        (Value::Nil, Type::Optional(_)) => Ok(Value::Nil),
+        (value, Type::Optional(inner)) => cast(value, inner),
+        // Wrapping a negative number around to u64 gives one larger than integers can hold
+        (Value::Integer(n), Type::U64) if n < 0 => runtime_error!("Cannot cast {} to u64, which can't be negative", n),
+        (Value::Integer(n), target) if target.integer_range().is_some() => Ok(Value::Integer(wrap_integer(n, target))),
+        (Value::Char(c), target) if target.integer_range().is_some() => Ok(Value::Integer(wrap_integer(c as i64, target))),
+        (Value::Boolean(b), target) if target.integer_range().is_some() => Ok(Value::Integer(b as i64)),
+        (Value::Float(n), target) if target.integer_range().is_some() => Ok(Value::Integer(truncate_float(n, target))),
+        (Value::Integer(n), Type::F32) => Ok(Value::Float(n as f32 as f64)),
+        (Value::Integer(n), Type::F64) => Ok(Value::Float(n as f64)),
+        (Value::Float(n), Type::F32) => Ok(Value::Float(n as f32 as f64)),
+        (Value::Float(n), Type::F64) => Ok(Value::Float(n)),
+        (Value::Integer(n), Type::Character) => match u32::try_from(n).ok().and_then(char::from_u32) {
+            Some(c) => Ok(Value::Char(c)),
+            None => runtime_error!("Invalid character code: {}", n)
+        },
+        (value @ Value::Char(_), Type::Character) | (value @ Value::Boolean(_), Type::Boolean) => Ok(value),
+        // Casting a value to its own type does nothing, which is how optional strings, structs, and enums are unwrapped
+        (value @ Value::String(_), Type::Identifier { name, .. }) if name == "string" => Ok(value),
+        (Value::Struct(instance), Type::Identifier { name, .. }) if &instance.name == name => Ok(Value::Struct(instance)),
+        (Value::Enum(instance), Type::Identifier { name, .. }) if &instance.enum_name == name => Ok(Value::Enum(instance)),
+        (value, target) => runtime_error!("Cannot cast {} to {}", value.type_name(), target)
+    }
+}
+
+/// Returns a method with `self` bound to a struct instance.
+fn bind_method(method: &Function, instance: &Rc<StructValue>) -> Value {
+    let mut environment = Environment::new(Some(method.closure.clone()));
+    environment.define("self".to_string(), Value::Struct(instance.clone()), VariableMutability::Immutable);
+    Value::Function(Rc::new(Function {
+        name: method.name.clone(),
+        params: method.params.clone(),
+        param_types: method.param_types.clone(),
+        return_type: method.return_type.clone(),
+        body: method.body.clone(),
+        closure: Rc::new(RefCell::new(environment))
+    }))
+}
+
+/// Wraps an integer around to fit in an integer type.
+fn wrap_integer(n: i64, target: &Type) -> i64 {
+    match target {
+        Type::U8 => n as u8 as i64,
+        Type::U16 => n as u16 as i64,
+        Type::U32 => n as u32 as i64,
+        Type::I8 => n as i8 as i64,
+        Type::I16 => n as i16 as i64,
+        Type::I32 => n as i32 as i64,
+        // Integers are stored as i64, so u64 doesn't need to change the non-negative ones `cast` passes in
+        _ => n
+    }
+}
+
+/// Truncates a float toward zero to fit in an integer type. NaN becomes 0.
+fn truncate_float(n: f64, target: &Type) -> i64 {
+    match target {
+        Type::U8 => n as u8 as i64,
+        Type::U16 => n as u16 as i64,
+        Type::U32 => n as u32 as i64,
+        Type::U64 => (n as u64).min(i64::MAX as u64) as i64,
+        Type::I8 => n as i8 as i64,
+        Type::I16 => n as i16 as i64,
+        Type::I32 => n as i32 as i64,
+        _ => n as i64
+    }
+}
+
+/// Checks that an `if` or loop condition is a boolean, since no other values are implicitly truthy.
+pub(crate) fn condition_boolean(value: Value) -> InterpreterResult<bool> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        value => runtime_error!("Condition must be a boolean, got {}", value)
+    }
+}
+
+/// Converts the result of a checked integer operation into a value, reporting overflow as a runtime error.
+fn checked_integer(result: Option<i64>) -> InterpreterResult {
+    match result {
+        Some(value) => Ok(Value::Integer(value)),
+        None => runtime_error!("Integer overflow")
+    }
+}
+
+/// Checks that a shift moves by less than the width of an integer.
+fn shift_amount(amount: i64) -> InterpreterResult<u32> {
+    if (0..i64::BITS as i64).contains(&amount) {
+        Ok(amount as u32)
+    } else {
+        runtime_error!("Shift amount out of range: {}", amount)
+    }
+}
+
+/// Applies an arithmetic or comparison operator to two float operands.
+fn float_operation(operator: &BinaryOperator, l: f64, r: f64) -> InterpreterResult {
+    match operator {
+        BinaryOperator::Add => Ok(Value::Float(l + r)),
+        BinaryOperator::Subtract => Ok(Value::Float(l - r)),
+        BinaryOperator::Multiply => Ok(Value::Float(l * r)),
+        BinaryOperator::Divide => {
+            if r == 0.0 {
+                return runtime_error!("Division by zero");
+            }
+            Ok(Value::Float(l / r))
+        },
+        BinaryOperator::FloorDivide => {
+            if r == 0.0 {
+                return runtime_error!("Division by zero");
+            }
+            Ok(Value::Float((l / r).floor()))
+        },
+        BinaryOperator::Modulus => {
+            if r == 0.0 {
+                return runtime_error!("Division by zero");
+            }
+            Ok(Value::Float(l % r))
+        },
+        BinaryOperator::LessThan => Ok(Value::Boolean(l < r)),
+        BinaryOperator::LessThanOrEqual => Ok(Value::Boolean(l <= r)),
+        BinaryOperator::GreaterThan => Ok(Value::Boolean(l > r)),
+        BinaryOperator::GreaterThanOrEqual => Ok(Value::Boolean(l >= r)),
+        _ => runtime_error!("Unsupported binary operation: {} {} {}", l, operator, r)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{parser::{ast::{BinaryOperator, Declaration, Expression, Program, Statement, Type}, Parser}, tokenizer::Tokenizer};
 
+    macro_rules! load {
+        ($input:expr) => {
+            {
+                let mut tokenizer = Tokenizer::new($input.to_string());
+                let tokens = tokenizer.tokenize().unwrap();
+                let mut parser = Parser::new(&tokens);
+                let program = parser.parse_program().unwrap();
+                let mut interpreter = Interpreter::new();
+                Resolver::new(&mut interpreter).resolve_program(&program).unwrap();
+                interpreter.load(&program).unwrap();
+                interpreter
+            }
+        };
+    }
+
     macro_rules! parse {
         ($input:expr, $parse_fn:ident) => {
             {
@@ -315,12 +1485,14 @@ mod tests {
                     params: vec![],
                     return_type: Type::F64,
                     generic_args: vec![],
-                    body: Box::new(Expression::Block(vec![
+                    is_const: false,
+                    body: Rc::new(Expression::Block(vec![
                         Statement::Expression {
                             expression: Box::new(Expression::BinaryOperation {
                                 left: Box::new(Expression::NumberLiteral(5.0)),
                                 operator: BinaryOperator::Add,
-                                right: Box::new(Expression::NumberLiteral(3.0))
+                                right: Box::new(Expression::NumberLiteral(3.0)),
+                                expression_id: ExpressionId(1)
                             }),
                             result: true
                         }
@@ -340,6 +1512,1476 @@ mod tests {
             1 + 2 * 3 - 4 / 5 % 6
         "#, parse_expression));
 
-        assert_eq!(result, Ok(Value::Number(1.0 + 2.0 * 3.0 - 4.0 / 5.0 % 6.0)));
+        assert_eq!(result, Ok(Value::Float(1.0 + 2.0 * 3.0 - 4.0 / 5.0 % 6.0)));
+    }
+
+    #[test]
+    fn test_division() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_source("5 / 2"), Ok(Value::Float(2.5)));
+        assert_eq!(interpreter.eval_source("4 / 2"), Ok(Value::Float(2.0)));
+        assert_eq!(interpreter.eval_source("5 ~/ 2"), Ok(Value::Integer(2)));
+        assert_eq!(interpreter.eval_source("-5 ~/ 2"), Ok(Value::Integer(-3)));
+        assert_eq!(interpreter.eval_source("5 ~/ -2"), Ok(Value::Integer(-3)));
+        assert_eq!(interpreter.eval_source("5.5 ~/ 2"), Ok(Value::Float(2.0)));
+        assert!(interpreter.eval_source("5 ~/ 0").is_err());
+    }
+
+    #[test]
+    fn test_trailing_lambda() {
+        let mut interpreter = load!(r#"
+            func apply(x: i32, f: func(i32) -> i32) -> i32 {
+                f(x)
+            }
+
+            func test() -> i32 {
+                apply(20) { |x| x * 2 + 2 }
+            }
+        "#);
+
+        assert_eq!(interpreter.call_function("test", vec![]), Ok(Value::Integer(42)));
+    }
+
+    #[test]
+    fn test_closures() {
+        let mut interpreter = load!(r#"
+            func repeat(times: i32, f: func() -> nil) -> nil {
+                let i: i32 = 0;
+                loop (i < times) {
+                    f();
+                    i = i + 1;
+                }
+            }
+
+            func test() -> i32 {
+                let total: i32 = 0;
+                repeat(3) {
+                    total = total + 2;
+                };
+                total
+            }
+        "#);
+
+        assert_eq!(interpreter.call_function("test", vec![]), Ok(Value::Integer(6)));
+    }
+
+    #[test]
+    fn test_implicit_return() {
+        let mut interpreter = load!(r#"
+            func inc(x: i32) -> i32 { x + 1 }
+
+            func sign(x: i32) -> i32 {
+                if (x < 0) { return -1; };
+                if (x == 0) { 0 } else { 1 }
+            }
+
+            func nothing() -> nil {
+                inc(1);
+            }
+        "#);
+
+        assert_eq!(interpreter.call_function("inc", vec![Value::Integer(1)]), Ok(Value::Integer(2)));
+        assert_eq!(interpreter.call_function("sign", vec![Value::Integer(-5)]), Ok(Value::Integer(-1)));
+        assert_eq!(interpreter.call_function("sign", vec![Value::Integer(0)]), Ok(Value::Integer(0)));
+        assert_eq!(interpreter.call_function("sign", vec![Value::Integer(5)]), Ok(Value::Integer(1)));
+        // A trailing semicolon makes the last expression a statement, so there's no result
+        assert_eq!(interpreter.call_function("nothing", vec![]), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut interpreter = load!(r#"
+            func shadow() -> i32 {
+                let x: i32 = 1;
+                {
+                    let x: i32 = 2;
+                    x = x + 1;
+                };
+                x
+            }
+
+            func reassign_constant() -> i32 {
+                const x: i32 = 1;
+                x = 2;
+                x
+            }
+        "#);
+
+        assert_eq!(interpreter.call_function("shadow", vec![]), Ok(Value::Integer(1)));
+        assert_eq!(
+            interpreter.call_function("reassign_constant", vec![]),
+            Err(InterpreterControl::RuntimeError("Cannot assign to constant x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_zip() {
+        let mut interpreter = Interpreter::new();
+        let pair = |a: i64, b: &str| Value::Tuple(vec![Value::Integer(a), Value::String(b.to_string())]);
+        assert_eq!(
+            interpreter.eval_source(r#"zip([1, 2, 3], ["a", "b"])"#),
+            Ok(Value::vector(vec![pair(1, "a"), pair(2, "b")]))
+        );
+        assert_eq!(
+            interpreter.eval_source(r#"zip([1], "a")"#),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("zip expects two arrays, got [1] and a".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_assertions() {
+        let mut interpreter = Interpreter::new();
+        let error = |message: &str| Err(EvalError::Runtime(InterpreterControl::RuntimeError(message.to_string())));
+
+        assert_eq!(interpreter.eval_source("assert(1 < 2)"), Ok(Value::Nil));
+        assert_eq!(interpreter.eval_source("assert(2 < 1)"), error("Assertion failed"));
+        assert_eq!(interpreter.eval_source("assert_eq([1, 2], [1, 2])"), Ok(Value::Nil));
+        assert_eq!(interpreter.eval_source("assert_eq(1 + 1, 3)"), error("Assertion failed: 2 != 3"));
+        assert_eq!(interpreter.eval_source(r#"assert_eq("1", 1)"#), error("Assertion failed: 1 (string) != 1 (int)"));
+        assert_eq!(interpreter.eval_source(r#"panic("oh no")"#), error("Panic: oh no"));
+    }
+
+    #[test]
+    fn test_format() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.eval_source(r#"format("{} + {} = {}", 1, 2.5, "three")"#),
+            Ok(Value::String("1 + 2.5 = three".to_string()))
+        );
+        assert_eq!(interpreter.eval_source(r#"format("{{{}}} {{}}", 'x')"#), Ok(Value::String("{x} {}".to_string())));
+        assert_eq!(interpreter.eval_source(r#"format("none")"#), Ok(Value::String("none".to_string())));
+        assert_eq!(
+            interpreter.eval_source(r#"format("{} and {}", 1)"#),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("format template has 2 placeholders but got 1 arguments".to_string())))
+        );
+        assert_eq!(
+            interpreter.eval_source(r#"format("{}", 1, 2)"#),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("format template has 1 placeholders but got 2 arguments".to_string())))
+        );
+        assert_eq!(
+            interpreter.eval_source(r#"format("{x}", 1)"#),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Unmatched '{' in format template; use '{{' for a literal brace".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_char_conversion() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_source("ord('A')"), Ok(Value::Integer(65)));
+        assert_eq!(interpreter.eval_source("chr(65)"), Ok(Value::Char('A')));
+        assert_eq!(interpreter.eval_source("chr(ord('é'))"), Ok(Value::Char('é')));
+        for invalid in ["1114112", "55296", "-1"] {
+            assert_eq!(
+                interpreter.eval_source(&format!("chr({})", invalid)),
+                Err(EvalError::Runtime(InterpreterControl::RuntimeError(format!("Invalid code point: {}", invalid))))
+            );
+        }
+    }
+
+    #[test]
+    fn test_char_classification() {
+        let mut interpreter = Interpreter::new();
+        for (source, expected) in [
+            ("is_digit('7')", Value::Boolean(true)),
+            ("is_digit('x')", Value::Boolean(false)),
+            ("is_alpha('x')", Value::Boolean(true)),
+            ("is_alpha('é')", Value::Boolean(true)),
+            ("is_alpha('7')", Value::Boolean(false)),
+            ("to_upper('a')", Value::Char('A')),
+            ("to_upper('7')", Value::Char('7')),
+            ("to_upper('ß')", Value::Char('ß')),
+            ("to_lower('Q')", Value::Char('q')),
+            ("int_to_char(char_to_int('z') - 25)", Value::Char('a'))
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(expected), "{}", source);
+        }
+        assert_eq!(
+            interpreter.eval_source("is_digit(7)"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Expected a character, got 7".to_string())))
+        );
+        assert_eq!(
+            interpreter.eval_source("int_to_char(55296)"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Invalid code point: 55296".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_char_string_conversion() {
+        let mut interpreter = Interpreter::new();
+        for (source, expected) in [
+            ("char_to_string('é')", Value::String("é".to_string())),
+            (r#"string_to_chars("ab")"#, Value::vector(vec![Value::Char('a'), Value::Char('b')])),
+            (r#"chars_to_string(string_to_chars("héllo"))"#, Value::String("héllo".to_string())),
+            (r#"chars_to_string([to_upper(c) for c in string_to_chars("shout")])"#, Value::String("SHOUT".to_string())),
+            (r#"chars_to_string(string_to_chars(""))"#, Value::String(String::new()))
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(expected), "{}", source);
+        }
+
+        for (source, message) in [
+            (r#"char_to_string("a")"#, "Expected a character, got a"),
+            ("string_to_chars('a')", "Expected a string, got a"),
+            (r#"chars_to_string("ab")"#, "Expected an array of characters, got ab"),
+            (r#"chars_to_string(['a', "b"])"#, "Expected a character, got b")
+        ] {
+            assert_eq!(
+                interpreter.eval_source(source),
+                Err(EvalError::Runtime(InterpreterControl::RuntimeError(message.to_string()))),
+                "{}", source
+            );
+        }
+    }
+
+    #[test]
+    fn test_type_guards() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_source(r#"is_type(1, "int")"#), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval_source(r#"is_type([1], "map")"#), Ok(Value::Boolean(false)));
+        assert_eq!(interpreter.eval_source(r#"expect_type("a", "string") + "b""#), Ok(Value::String("ab".to_string())));
+        assert_eq!(
+            interpreter.eval_source(r#"expect_type(1.5, "int")"#),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Expected a value of type int, got float 1.5".to_string())))
+        );
+        assert_eq!(
+            interpreter.eval_source(r#"is_type(1, "integer")"#),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Unknown type name: integer".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_typeof() {
+        let mut interpreter = load!(r#"
+            struct Point { x: i32; y: i32; }
+            enum Shape { Circle(f64), Empty }
+            type Byte = u8;
+            func nothing() {}
+            func numbers(small: u8, ratio: f32) -> [string] {
+                let byte: Byte = 1;
+                let maybe: i16? = 2;
+                [
+                    typeof(small), typeof(ratio), typeof(byte), typeof(maybe), typeof(5u16), typeof(1.5f32),
+                    typeof(small as i64), typeof(3), typeof(small + 1), typeof(2.5)
+                ]
+            }
+            func main() -> [string] {
+                let x: i32 = 1;
+                [
+                    typeof(x), typeof(1.5), typeof("a"), typeof(true), typeof('a'), typeof([1]), typeof({ "a": 1 }),
+                    typeof((1, 2)), typeof(new Point { x: 1, y: 2 }), typeof(Shape.Circle(1.0)), typeof({ |a| a }),
+                    typeof(ord), typeof(nothing())
+                ]
+            }
+        "#);
+        let strings = |names: &[&str]| Ok(Value::vector(names.iter().map(|name| Value::String(name.to_string())).collect()));
+        assert_eq!(
+            interpreter.call_function("numbers", vec![Value::Integer(7), Value::Float(0.5)]),
+            strings(&["u8", "f32", "u8", "i16", "u16", "f32", "i64", "i64", "i64", "f64"])
+        );
+        let names = ["i32", "f64", "string", "bool", "char", "vector", "map", "tuple", "struct Point", "enum Shape", "function", "function", "nil"];
+        assert_eq!(interpreter.call_function("main", vec![]), strings(&names));
+    }
+
+    #[test]
+    fn test_array_comprehensions() {
+        let mut interpreter = Interpreter::new();
+        let integers = |values: &[i64]| Value::vector(values.iter().map(|value| Value::Integer(*value)).collect());
+        for (source, expected) in [
+            ("[x * 2 for x in [1, 2, 3]]", integers(&[2, 4, 6])),
+            ("[x for x in [-1, 2, -3, 4] if x > 0]", integers(&[2, 4])),
+            ("[x * x for x in [1, 2, 3, 4] if x % 2 == 0]", integers(&[4, 16])),
+            ("[x for x in [] if x > 0]", integers(&[])),
+            ("[row[0] for row in [[1], [2, 3], [4]]]", integers(&[1, 2, 4]))
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(expected), "{}", source);
+        }
+        assert_eq!(
+            interpreter.eval_source("[x for x in 5]"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Cannot iterate over 5".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_map_order() {
+        let keys = |seed: Option<u64>| {
+            let mut interpreter = Interpreter::new();
+            if let Some(seed) = seed {
+                interpreter.set_map_seed(seed);
+            }
+            let Ok(Value::Map(map)) = interpreter.eval_source(r#"{ "one": 1, "two": 2, "three": 3, "four": 4, 5: 5, '6': 6, true: 7 }"#) else {
+                panic!("Expected a map");
+            };
+            let keys: Vec<Value> = map.borrow().keys().cloned().collect();
+            keys
+        };
+        // Separate interpreters, like separate runs, iterate maps in the same order
+        assert_eq!(keys(None), keys(None));
+        assert_eq!(keys(Some(7)), keys(Some(7)));
+        // And the seed is what decides the order
+        assert!((1..16).any(|seed| keys(Some(seed)) != keys(None)));
+    }
+
+    #[test]
+    fn test_max_memory() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_memory(Some(1024));
+        assert_eq!(interpreter.eval_source("[1, 2, 3]"), Ok(Value::vector(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)])));
+        let used = interpreter.memory_used();
+        assert!(used > 0);
+
+        // The huge array fails before it's allocated
+        for source in ["[i32, 1000000000]{0}", "0..1000000000000", r#"{ let s: str = "x"; loop { s = s + s; }; s }"#] {
+            assert_eq!(
+                interpreter.eval_source(source),
+                Err(EvalError::Runtime(InterpreterControl::RuntimeError("Memory limit exceeded".to_string()))),
+                "{}", source
+            );
+        }
+
+        // Values that have been dropped don't count towards the limit
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_memory(Some(1024));
+        assert_eq!(
+            interpreter.eval_source(r#"{ let i: i32 = 0; loop (i < 5000) { let _s: str = "abcdefgh" + "!"; i += 1; }; i }"#),
+            Ok(Value::Integer(5000))
+        );
+
+        // But growing a map in place does
+        assert_eq!(
+            interpreter.eval_source("{ let m: Map<i32, i32> = { 0: 0 }; let i: i32 = 1; loop { m[i] = i; i += 1; }; i }"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Memory limit exceeded".to_string())))
+        );
+
+        // Without a limit, memory is still counted
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_source(r#""ab" + "cd""#), Ok(Value::String("abcd".to_string())));
+        assert_eq!(interpreter.memory_used(), 8);
+    }
+
+    #[test]
+    fn test_block_values() {
+        let mut interpreter = Interpreter::new();
+        for (source, expected) in [
+            // Statements before the result still run
+            ("{ let x: i32 = 1; x += 2; x * 10 }", Value::Integer(30)),
+            ("{ let a: [i32] = [1, 2]; a[0] = 5; a[0] + a[1] }", Value::Integer(7)),
+            ("{ let x: i32 = 1; let y: i32 = { x += 4; x }; x + y }", Value::Integer(10)),
+            ("{}", Value::Nil),
+            ("{ let x: i32 = 1; }", Value::Nil),
+            ("{ let x: i32 = 1; x += 1; }", Value::Nil)
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(expected), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_local_functions() {
+        let mut interpreter = load!(r#"
+            func main() -> i32 {
+                let first: i32 = helper(1);
+                func helper(x: i32) -> i32 { x * 10 }
+                func factorial(n: i32) -> i32 {
+                    if (n <= 1) { return 1; };
+                    n * factorial(n - 1)
+                }
+                first + helper(2) + factorial(4)
+            }
+        "#);
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::Integer(54)));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut interpreter = Interpreter::new();
+        let chars = |values: &str| Value::vector(values.chars().map(Value::Char).collect());
+        for (source, expected) in [
+            ("'a'..='c'", chars("abc")),
+            ("'a'..'c'", chars("ab")),
+            ("[c for c in 'x'..='z']", chars("xyz")),
+            ("1..4", Value::vector(vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)])),
+            ("3..1", Value::vector(vec![]))
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(expected), "{}", source);
+        }
+        assert_eq!(
+            interpreter.eval_source("'a'..=3"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Range endpoints must both be integers or both be chars, got char and int".to_string())))
+        );
+
+        let mut interpreter = load!(r#"
+            func main() -> str {
+                let letters: str = "";
+                loop (const c: 'a'..='c') {
+                    letters = format("{}{}", letters, c);
+                };
+                letters
+            }
+        "#);
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::String("abc".to_string())));
+
+        // Loops over ranges don't build an array of the values first, so they can stop early in a huge range
+        let mut interpreter = load!(r#"
+            func main() -> i32 {
+                let last: i32 = 0;
+                loop (const i: 0..9223372036854775807) {
+                    if (i > 2) { break; };
+                    last = i;
+                };
+                last
+            }
+        "#);
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::Integer(2)));
+        assert_eq!(
+            Interpreter::new().eval_source("0..9223372036854775807"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError(
+                "A range of 9223372036854775807 values is too large to store; loop over it directly instead".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_spread_arguments() {
+        let mut interpreter = load!(r#"
+            func sum3(a: i32, b: i32, c: i32) -> i32 { a * 100 + b * 10 + c }
+            func all() -> i32 { let values: [i32] = [1, 2, 3]; sum3(...values) }
+            func some() -> i32 { sum3(4, ...[5, 6]) }
+            func short() -> i32 { sum3(...[1, 2]) }
+            func not_array() -> i32 { sum3(1, 2, ...3) }
+        "#);
+        assert_eq!(interpreter.call_function("all", vec![]), Ok(Value::Integer(123)));
+        assert_eq!(interpreter.call_function("some", vec![]), Ok(Value::Integer(456)));
+        assert_eq!(
+            interpreter.call_function("short", vec![]),
+            Err(InterpreterControl::RuntimeError("sum3 expected 3 arguments but got 2".to_string()))
+        );
+        assert_eq!(
+            interpreter.call_function("not_array", vec![]),
+            Err(InterpreterControl::RuntimeError("Cannot spread 3 into arguments; only arrays can be spread".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_number_conversion() {
+        let mut interpreter = Interpreter::new();
+        for (source, expected) in [
+            (r#"parse_int("-42")"#, Value::Integer(-42)),
+            (r#"parse_float("1.5")"#, Value::Float(1.5)),
+            (r#"parse_float("2")"#, Value::Float(2.0)),
+            ("to_string(12)", Value::String("12".to_string())),
+            ("to_string(2.5)", Value::String("2.5".to_string())),
+            ("to_string(true)", Value::String("true".to_string())),
+            ("to_string('c')", Value::String("c".to_string())),
+            ("to_string((1, \"a\"))", Value::String("(1, a)".to_string())),
+            (r#"parse_int(to_string(7)) + 1"#, Value::Integer(8)),
+            ("parse_float(to_string(0.1)) == 0.1", Value::Boolean(true)),
+            ("parse_int(to_string(-9223372036854775807))", Value::Integer(-9223372036854775807))
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(expected), "{}", source);
+        }
+
+        for (source, message) in [
+            (r#"parse_int("4.5")"#, "Invalid integer: \"4.5\""),
+            (r#"parse_int("")"#, "Invalid integer: \"\""),
+            (r#"parse_float("one")"#, "Invalid float: \"one\""),
+            (r#"parse_int("9223372036854775808")"#, "Invalid integer: \"9223372036854775808\""),
+            ("parse_int(4)", "Expected a string, got 4")
+        ] {
+            assert_eq!(
+                interpreter.eval_source(source),
+                Err(EvalError::Runtime(InterpreterControl::RuntimeError(message.to_string()))),
+                "{}", source
+            );
+        }
+    }
+
+    /// Collects written output so a test can read it afterward.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl SharedOutput {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_error_output() {
+        let (output, error_output) = (SharedOutput::default(), SharedOutput::default());
+        let mut interpreter = load!(r#"
+            func main() -> i32 {
+                print("data");
+                eprint("progress:", 50);
+                eprintln("%");
+                eprintln();
+                print(1, 2);
+                0
+            }
+        "#);
+        interpreter.set_output(Box::new(output.clone()));
+        interpreter.set_error_output(Box::new(error_output.clone()));
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::Integer(0)));
+
+        assert_eq!(output.contents(), "data\n1\n2\n");
+        assert_eq!(error_output.contents(), "progress: 50%\n\n");
+    }
+
+    #[test]
+    fn test_with_output() {
+        let output = SharedOutput::default();
+        let mut interpreter = Interpreter::with_output(Box::new(output.clone()));
+        assert_eq!(interpreter.eval_source(r#"print("text", 'c', 1.5, [1, 2], (true, 2))"#), Ok(Value::Nil));
+        assert_eq!(interpreter.eval_source(r#"print(to_upper('m'))"#), Ok(Value::Nil));
+        assert_eq!(interpreter.eval_source("print([[1], []])"), Ok(Value::Nil));
+        assert_eq!(output.contents(), "text\nc\n1.5\n[1, 2]\n(true, 2)\nM\n[[1], []]\n");
+    }
+
+    #[test]
+    fn test_read_line() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input(Box::new(io::Cursor::new("first\nsecond\r\n")));
+        assert_eq!(interpreter.eval_source("read_line()"), Ok(Value::String("first".to_string())));
+        assert_eq!(interpreter.eval_source("read_line()"), Ok(Value::String("second".to_string())));
+        assert_eq!(interpreter.eval_source("read_line()"), Ok(Value::Nil));
+        assert_eq!(interpreter.eval_source("read_line()"), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn test_read_int() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input(Box::new(io::Cursor::new("42\n -7 \nseven\n")));
+        assert_eq!(interpreter.eval_source("read_int() + 1"), Ok(Value::Integer(43)));
+        assert_eq!(interpreter.eval_source("read_int()"), Ok(Value::Integer(-7)));
+        assert_eq!(
+            interpreter.eval_source("read_int()"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Invalid integer: \"seven\"".to_string())))
+        );
+        assert_eq!(interpreter.eval_source("read_int()"), Ok(Value::Nil));
+    }
+
+    #[test]
+    fn test_while_let() {
+        let mut interpreter = load!(r#"
+            func count_lines() -> i32 {
+                let count: i32 = 0;
+                loop (let line = read_line()) {
+                    if (line == "skip") { continue; };
+                    if (line == "stop") { break; };
+                    count += 1;
+                };
+                count
+            }
+        "#);
+        interpreter.set_input(Box::new(io::Cursor::new("one\nskip\ntwo\n")));
+        assert_eq!(interpreter.call_function("count_lines", vec![]), Ok(Value::Integer(2)));
+        // Breaking leaves the rest of the input unread
+        interpreter.set_input(Box::new(io::Cursor::new("one\nstop\ntwo\n")));
+        assert_eq!(interpreter.call_function("count_lines", vec![]), Ok(Value::Integer(1)));
+        assert_eq!(interpreter.eval_source("read_line()"), Ok(Value::String("two".to_string())));
+    }
+
+    #[test]
+    fn test_io_capability() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_input(Box::new(io::Cursor::new("first\nsecond\n")));
+        interpreter.set_io_allowed(false);
+        for function in ["read_line", "read_int", "print", "eprint", "eprintln"] {
+            assert_eq!(
+                interpreter.eval_source(&format!("{}()", function)),
+                Err(EvalError::Runtime(InterpreterControl::RuntimeError(format!("{} needs the IO capability, which isn't allowed here", function))))
+            );
+        }
+
+        // Nothing was read while IO was off
+        interpreter.set_io_allowed(true);
+        assert_eq!(interpreter.eval_source("read_line()"), Ok(Value::String("first".to_string())));
+        assert_eq!(interpreter.eval_source("read_line()"), Ok(Value::String("second".to_string())));
+    }
+
+    #[test]
+    fn test_array_sizes() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_source("[[i32], 2] { [i32, 0] { 1 } }"), Ok(Value::vector(vec![Value::vector(vec![]), Value::vector(vec![])])));
+
+        // Sizes are checked before running when they're constant
+        for (source, message) in [
+            ("[i32, -1] { 0 }", "Error: Array size must be a non-negative integer, got -1."),
+            ("[i32, 1.5] { 0 }", "Error: Array size must be a non-negative integer, got 1.5.")
+        ] {
+            assert_eq!(interpreter.eval_source(source), Err(EvalError::Resolve(message.to_string())), "{}", source);
+        }
+
+        // Embedders can skip the resolver, so the interpreter checks them again
+        for (source, message) in [
+            ("[i32, 0 - 2] { 0 }", "Array size must be a non-negative integer, got -2"),
+            ("[i32, 2.0] { 0 }", "Array size must be a non-negative integer, got 2")
+        ] {
+            assert_eq!(
+                interpreter.eval_expression(&parse!(source, parse_expression)),
+                Err(InterpreterControl::RuntimeError(message.to_string())),
+                "{}", source
+            );
+        }
+    }
+
+    #[test]
+    fn test_struct_equality() {
+        let mut interpreter = load!(r#"
+            struct Point {
+                x: i32;
+                y: i32;
+            }
+            struct Line {
+                from: Point;
+                to: Point;
+            }
+            func point(x: i32, y: i32) -> Point { new Point { y: y, x: x } }
+            func line(x: i32) -> Line { new Line { from: point(0, 0), to: point(x, [1, 2][1]) } }
+            func same_lines() -> bool { line(1) == line(1) }
+            func different_lines() -> bool { line(1) == line(2) }
+            func different_types() -> bool { point(1, 2) == (1, 2) }
+            func nested_arrays() -> bool { [[1, 2], [3]] == [[1, 2], [3]] }
+            func describe() -> Point { line(5).to }
+            func missing_member() -> i32 { point(1, 2).z }
+        "#);
+        assert_eq!(interpreter.call_function("same_lines", vec![]), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.call_function("different_lines", vec![]), Ok(Value::Boolean(false)));
+        assert_eq!(interpreter.call_function("different_types", vec![]), Ok(Value::Boolean(false)));
+        assert_eq!(interpreter.call_function("nested_arrays", vec![]), Ok(Value::Boolean(true)));
+        // Fields are kept in the order the struct declares them
+        assert_eq!(interpreter.call_function("describe", vec![]).unwrap().to_string(), "Point { x: 5, y: 2 }");
+
+        for (source, message) in [
+            ("new Point { x: 1 }", "Missing field y in Point"),
+            ("new Point { x: 1, y: 2, z: 3 }", "Struct Point has no field z"),
+            ("new Point { x: 1, x: 2 }", "Field x of Point is given more than once"),
+            ("new Circle { r: 1 }", "Unknown struct: Circle")
+        ] {
+            assert_eq!(
+                interpreter.eval_source(source),
+                Err(EvalError::Runtime(InterpreterControl::RuntimeError(message.to_string()))),
+                "{}", source
+            );
+        }
+        assert_eq!(
+            interpreter.call_function("missing_member", vec![]),
+            Err(InterpreterControl::RuntimeError("Struct Point has no field or method z".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_optional_member_access() {
+        let mut interpreter = load!(r#"
+            struct Node {
+                value: i32;
+                func double() -> i32 { self.value * 2 }
+            }
+            func nothing() -> Node? {}
+            func node() -> Node? { new Node { value: 4 } }
+            func missing_value() -> i32? { nothing()?.value }
+            func missing_method() -> i32? { nothing()?.double(panic("not evaluated")) }
+            func present() -> i32? { node()?.value + node()?.double() }
+            func unwrap_missing() -> i32 { let value: i32? = nothing()?.value; value as i32 }
+            func plain_access() -> i32 { nothing().value }
+        "#);
+        assert_eq!(interpreter.call_function("missing_value", vec![]), Ok(Value::Nil));
+        assert_eq!(interpreter.call_function("missing_method", vec![]), Ok(Value::Nil));
+        assert_eq!(interpreter.call_function("present", vec![]), Ok(Value::Integer(12)));
+        assert_eq!(interpreter.call_function("unwrap_missing", vec![]), runtime_error!("Cannot cast nil to i32"));
+        assert_eq!(interpreter.call_function("plain_access", vec![]), runtime_error!("nil has no member value"));
+    }
+
+    #[test]
+    fn test_struct_methods() {
+        let mut interpreter = load!(r#"
+            struct Rect {
+                width: i32;
+                height: i32;
+                func area() -> i32 { self.width * self.height }
+                func fits(other: Rect) -> bool { other.width <= self.width && other.height <= self.height }
+                func describe() -> string { format("{}x{} ({})", self.width, self.height, self.area()) }
+            }
+            func rect(width: i32, height: i32) -> Rect { new Rect { width: width, height: height } }
+            func area() -> i32 { let r: Rect = rect(3, 4); r.area() }
+            func fits() -> bool { rect(3, 4).fits(rect(2, 4)) }
+            func describe() -> string { rect(2, 5).describe() }
+            func undefined() -> i32 { rect(1, 1).perimeter() }
+        "#);
+        assert_eq!(interpreter.call_function("area", vec![]), Ok(Value::Integer(12)));
+        assert_eq!(interpreter.call_function("fits", vec![]), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.call_function("describe", vec![]), Ok(Value::String("2x5 (10)".to_string())));
+        assert_eq!(
+            interpreter.call_function("undefined", vec![]),
+            Err(InterpreterControl::RuntimeError("Struct Rect has no field or method perimeter".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_saturating_assignment() {
+        let source = r#"
+            func brighten(amount: i32) -> u8 {
+                let level: u8 = 200;
+                level = level + amount;
+                level
+            }
+            func darken() -> u8 { 10 - 20 }
+            func wide() -> i64 { let x: i64 = 300; x }
+        "#;
+        let mut interpreter = load!(source);
+        assert_eq!(interpreter.call_function("brighten", vec![Value::Integer(50)]), Ok(Value::Integer(250)));
+        assert_eq!(
+            interpreter.call_function("brighten", vec![Value::Integer(100)]),
+            Err(InterpreterControl::RuntimeError("300 is out of range for u8".to_string()))
+        );
+        assert_eq!(
+            interpreter.call_function("darken", vec![]),
+            Err(InterpreterControl::RuntimeError("-10 is out of range for u8".to_string()))
+        );
+        assert_eq!(interpreter.call_function("wide", vec![]), Ok(Value::Integer(300)));
+
+        let mut interpreter = load!(source);
+        interpreter.set_saturating(true);
+        assert_eq!(interpreter.call_function("brighten", vec![Value::Integer(100)]), Ok(Value::Integer(255)));
+        assert_eq!(interpreter.call_function("darken", vec![]), Ok(Value::Integer(0)));
+    }
+
+    #[test]
+    fn test_aliased_integer_types() {
+        let source = r#"
+            type Byte = u8;
+            type MaybeByte = Byte?;
+            func brighten(amount: i32) -> Byte {
+                let level: Byte = 200;
+                level = level + amount;
+                level
+            }
+            func maybe(amount: i32) -> MaybeByte { amount }
+            func wrap() -> i32 { 300 as Byte }
+        "#;
+        let mut interpreter = load!(source);
+        assert_eq!(interpreter.call_function("brighten", vec![Value::Integer(50)]), Ok(Value::Integer(250)));
+        assert_eq!(
+            interpreter.call_function("brighten", vec![Value::Integer(100)]),
+            Err(InterpreterControl::RuntimeError("300 is out of range for u8".to_string()))
+        );
+        assert_eq!(
+            interpreter.call_function("maybe", vec![Value::Integer(-1)]),
+            Err(InterpreterControl::RuntimeError("-1 is out of range for u8".to_string()))
+        );
+        assert_eq!(interpreter.call_function("wrap", vec![]), Ok(Value::Integer(44)));
+
+        let mut interpreter = load!(source);
+        interpreter.set_saturating(true);
+        assert_eq!(interpreter.call_function("brighten", vec![Value::Integer(100)]), Ok(Value::Integer(255)));
+        assert_eq!(interpreter.call_function("maybe", vec![Value::Integer(-1)]), Ok(Value::Integer(0)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_global_variables() {
+        let mut interpreter = load!(r#"
+            const PI: f64 = 3.5;
+            let counter: i32 = 0;
+            const ANSWER: i32 = double(21);
+
+            func area(r: f64) -> f64 { PI * r * r }
+            func bump() -> i32 { counter += 1; counter }
+            func double(n: i32) -> i32 { n * 2 }
+            func answer() -> i32 { ANSWER }
+            func change_pi() -> f64 { PI = 3.0; PI }
+        "#);
+        assert_eq!(interpreter.call_function("area", vec![Value::Float(2.0)]), Ok(Value::Float(14.0)));
+        assert_eq!(interpreter.call_function("bump", vec![]), Ok(Value::Integer(1)));
+        assert_eq!(interpreter.call_function("bump", vec![]), Ok(Value::Integer(2)));
+        // Globals can be initialized with functions declared after them
+        assert_eq!(interpreter.call_function("answer", vec![]), Ok(Value::Integer(42)));
+        assert_eq!(
+            interpreter.call_function("change_pi", vec![]),
+            Err(InterpreterControl::RuntimeError("Cannot assign to constant PI".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        let mut interpreter = load!(r#"
+            func identity<T>(x: T) -> T { x }
+            func pair<A, B>(a: A, b: B) -> (A, B) { (a, b) }
+            func integer() -> i32 { identity(42) }
+            func string() -> string { identity("forty-two") }
+            func both() -> (i32, string) { pair(identity(1), identity("one")) }
+        "#);
+        assert_eq!(interpreter.call_function("integer", vec![]), Ok(Value::Integer(42)));
+        assert_eq!(interpreter.call_function("string", vec![]), Ok(Value::String("forty-two".to_string())));
+        assert_eq!(interpreter.call_function("both", vec![]), Ok(Value::Tuple(vec![Value::Integer(1), Value::String("one".to_string())])));
+        assert_eq!(interpreter.call_function("identity", vec![Value::Boolean(true)]), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_interface_dispatch() {
+        let mut interpreter = load!(r#"
+            interface Shape {
+                func area() -> f64;
+                func scaled(by: f64) -> f64;
+            }
+            struct Square : Shape {
+                side: f64;
+                func area() -> f64 { self.side * self.side }
+                func scaled(by: f64) -> f64 { self.area() * by }
+            }
+            struct Circle : Shape {
+                radius: f64;
+                func area() -> f64 { 3.0 * self.radius * self.radius }
+                func scaled(by: f64) -> f64 { self.area() * by }
+            }
+            func total(shapes: [Shape]) -> f64 {
+                let sum: f64 = 0.0;
+                loop (const shape: shapes) {
+                    sum += shape.scaled(2.0);
+                };
+                sum
+            }
+            func main() -> f64 { total([new Square { side: 2.0 }, new Circle { radius: 1.0 }]) }
+            func wrong_arguments() -> f64 {
+                let square: Square = new Square { side: 1.0 };
+                square.scaled()
+            }
+        "#);
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::Float(14.0)));
+        assert_eq!(interpreter.call_function("wrong_arguments", vec![]), runtime_error!("Square.scaled expected 1 arguments but got 0"));
+    }
+
+    #[test]
+    fn test_interface_values() {
+        let mut interpreter = load!(r#"
+            interface Printable {
+                func toString() -> string;
+            }
+            struct Point : Printable {
+                x: i32;
+                y: i32;
+                func toString() -> string { "(" + to_string(self.x) + ", " + to_string(self.y) + ")" }
+            }
+            struct Name : Printable {
+                value: string;
+                func toString() -> string { "Name " + self.value }
+            }
+            struct Other {
+                value: i32;
+            }
+            func show(item: Printable) -> string { item.toString() }
+            func point() -> string { show(new Point { x: 1, y: 2 }) }
+            func name() -> string { show(new Name { value: "Ada" }) }
+            func printable() -> Printable { new Point { x: 3, y: 4 } }
+            func not_printable() -> string { show(new Other { value: 1 }) }
+            func field(item: Printable) -> i32 { item.x }
+            func through_interface() -> i32 { field(new Point { x: 1, y: 2 }) }
+        "#);
+        assert_eq!(interpreter.call_function("point", vec![]), Ok(Value::String("(1, 2)".to_string())));
+        assert_eq!(interpreter.call_function("name", vec![]), Ok(Value::String("Name Ada".to_string())));
+
+        let Ok(Value::Interface(printable)) = interpreter.call_function("printable", vec![]) else {
+            panic!("Expected an interface value");
+        };
+        assert_eq!(printable.interface, "Printable");
+        assert_eq!(printable.instance.name, "Point");
+        assert_eq!(printable.vtable.keys().collect::<Vec<_>>(), ["toString"]);
+
+        assert_eq!(interpreter.call_function("not_printable", vec![]), runtime_error!("Struct Other doesn't implement interface Printable"));
+        // Only the interface's methods are visible through it
+        assert_eq!(interpreter.call_function("through_interface", vec![]), runtime_error!("Point { x: 1, y: 2 } has no member x"));
+    }
+
+    #[test]
+    fn test_operator_overloading() {
+        let mut interpreter = load!(r#"
+            struct Vec2 {
+                x: f64;
+                y: f64;
+                func add(other: Vec2) -> Vec2 { new Vec2 { x: self.x + other.x, y: self.y + other.y } }
+                func multiply(by: f64) -> Vec2 { new Vec2 { x: self.x * by, y: self.y * by } }
+            }
+            func vec2(x: f64, y: f64) -> Vec2 { new Vec2 { x: x, y: y } }
+            func sum() -> Vec2 { vec2(1.0, 2.0) + vec2(3.0, 4.0) }
+            func compound() -> Vec2 {
+                let position: Vec2 = vec2(1.0, 1.0);
+                position += vec2(0.5, 0.0);
+                position * 2.0
+            }
+            func unsupported() -> Vec2 { vec2(1.0, 2.0) - vec2(3.0, 4.0) }
+        "#);
+        let vec2 = |x: f64, y: f64| Value::Struct(Rc::new(StructValue {
+            name: "Vec2".to_string(),
+            fields: vec![("x".to_string(), Value::Float(x)), ("y".to_string(), Value::Float(y))]
+        }));
+        assert_eq!(interpreter.call_function("sum", vec![]), Ok(vec2(4.0, 6.0)));
+        assert_eq!(interpreter.call_function("compound", vec![]), Ok(vec2(3.0, 2.0)));
+        assert!(interpreter.call_function("unsupported", vec![]).is_err());
+    }
+
+    #[test]
+    fn test_enums() {
+        let mut interpreter = load!(r#"
+            enum Shape {
+                Circle(f64),
+                Rect(f64, f64),
+                Empty
+            }
+            func area(shape: Shape) -> f64 {
+                match (shape) {
+                    Shape.Circle(r) => 3.0 * r * r,
+                    Shape.Rect(w, h) => w * h,
+                    _ => 0.0
+                }
+            }
+            func circle() -> f64 { area(Shape.Circle(2.0)) }
+            func rect() -> f64 { area(Shape.Rect(2.0, 3.5)) }
+            func empty() -> f64 { area(Shape.Empty) }
+            func describe() -> Shape { Shape.Rect(1.0, 2.0) }
+            func same() -> bool { Shape.Circle(1.0) == Shape.Circle(1.0) && Shape.Circle(1.0) != Shape.Circle(2.0) }
+            func unmatched() -> i32 { match (Shape.Empty) { Shape.Circle(r) => 1 } }
+            func missing_values() -> Shape { Shape.Circle }
+            func unknown_variant() -> Shape { Shape.Square(1.0) }
+            func wrong_bindings() -> f64 { match (Shape.Empty) { Shape.Rect(w) => w, _ => 0.0 } }
+        "#);
+        assert_eq!(interpreter.call_function("circle", vec![]), Ok(Value::Float(12.0)));
+        assert_eq!(interpreter.call_function("rect", vec![]), Ok(Value::Float(7.0)));
+        assert_eq!(interpreter.call_function("empty", vec![]), Ok(Value::Float(0.0)));
+        assert_eq!(interpreter.call_function("describe", vec![]).unwrap().to_string(), "Shape.Rect(1, 2)");
+        assert_eq!(interpreter.call_function("same", vec![]), Ok(Value::Boolean(true)));
+
+        for (function, message) in [
+            ("unmatched", "No match arm matches Shape.Empty"),
+            ("missing_values", "Shape.Circle expected 1 arguments but got 0"),
+            ("unknown_variant", "Enum Shape has no variant Square"),
+            ("wrong_bindings", "Shape.Rect has 2 values but the pattern binds 1")
+        ] {
+            assert_eq!(
+                interpreter.call_function(function, vec![]),
+                Err(InterpreterControl::RuntimeError(message.to_string())),
+                "{}", function
+            );
+        }
+    }
+
+    #[test]
+    fn test_generic_enums() {
+        let mut interpreter = load!(r#"
+            enum Option<T> { Some(T), None }
+            func unwrap_or(option: Option<i32>, default: i32) -> i32 {
+                match (option) {
+                    Option.Some(value) => value,
+                    Option.None => default
+                }
+            }
+            func some() -> i32 { let five: Option<i32> = Option.Some(5); unwrap_or(five, 0) }
+            func none() -> i32 { unwrap_or(Option.None, 7) }
+        "#);
+        assert_eq!(interpreter.call_function("some", vec![]), Ok(Value::Integer(5)));
+        assert_eq!(interpreter.call_function("none", vec![]), Ok(Value::Integer(7)));
+    }
+
+    #[test]
+    fn test_typed_literals() {
+        let mut interpreter = Interpreter::new();
+        for (source, expected) in [
+            ("42u8 + 1", Value::Integer(43)),
+            ("-128i8", Value::Integer(-128)),
+            ("0.1f32", Value::Float(0.1f32 as f64)),
+            ("0.1f64", Value::Float(0.1)),
+            ("3f32 / 2", Value::Float(1.5))
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(expected), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_casts() {
+        let mut interpreter = Interpreter::new();
+        for (source, expected) in [
+            ("3.9 as i32", Value::Integer(3)),
+            ("-3.9 as i32", Value::Integer(-3)),
+            ("300 as u8", Value::Integer(44)),
+            ("-1 as u8", Value::Integer(255)),
+            ("9223372036854775807 as u64", Value::Integer(i64::MAX)),
+            ("40000 as i16", Value::Integer(-25536)),
+            ("5000000000.0 as i32", Value::Integer(i32::MAX as i64)),
+            ("-5.0 as u32", Value::Integer(0)),
+            ("3 as f64", Value::Float(3.0)),
+            ("0.1 as f32", Value::Float(0.1f32 as f64)),
+            ("'a' as u8", Value::Integer(97)),
+            ("97 as char", Value::Char('a')),
+            ("true as i32", Value::Integer(1)),
+            ("1 + 2.5 as i32 * 2", Value::Integer(5))
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(expected), "{}", source);
+        }
+
+        for (source, message) in [
+            (r#""yes" as bool"#, "Cannot cast string to bool"),
+            ("1 as string", "Cannot cast int to string"),
+            ("1.5 as char", "Cannot cast float to char"),
+            ("-1 as char", "Invalid character code: -1"),
+            ("-1 as u64", "Cannot cast -1 to u64, which can't be negative")
+        ] {
+            assert_eq!(
+                interpreter.eval_source(source),
+                Err(EvalError::Runtime(InterpreterControl::RuntimeError(message.to_string()))),
+                "{}", source
+            );
+        }
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        let mut interpreter = Interpreter::new();
+        for (source, expected) in [
+            ("6 & 3", 2),
+            ("6 | 3", 7),
+            ("6 ^ 3", 5),
+            ("1 << 4", 16),
+            ("-16 >> 2", -4),
+            ("~0", -1),
+            ("1 + 1 << 2 | 1", 9)
+        ] {
+            assert_eq!(interpreter.eval_source(source), Ok(Value::Integer(expected)), "{}", source);
+        }
+        assert_eq!(interpreter.eval_source("6 & 3 == 2"), Ok(Value::Boolean(true)));
+
+        for (source, message) in [
+            ("1 << 64", "Shift amount out of range: 64"),
+            ("1 >> -1", "Shift amount out of range: -1"),
+            ("1.5 & 1", "Unsupported binary operation: 1.5 & 1"),
+            ("~1.5", "Unsupported unary operation: ~ 1.5")
+        ] {
+            assert_eq!(
+                interpreter.eval_source(source),
+                Err(EvalError::Runtime(InterpreterControl::RuntimeError(message.to_string()))),
+                "{}", source
+            );
+        }
+    }
+
+    #[test]
+    fn test_contextual_keywords() {
+        // `type` only starts a type alias when it's followed by a name, so it's still usable as a variable
+        let mut interpreter = load!(r#"
+            type Count = i32;
+            func next(type: Count) -> Count { type + 1 }
+            func main() -> Count {
+                let type: Count = 2;
+                type = type * 3;
+                next(type)
+            }
+        "#);
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::Integer(7)));
+
+        // So are the other keywords added since, outside the places they start something
+        let mut interpreter = load!(r#"
+            func main() -> i32 {
+                let match: i32 = 1;
+                let enum: i32 = 2;
+                let interface: i32 = 3;
+                let for: i32 = 4;
+                let in: i32 = 5;
+                let while: i32 = 6;
+                let as: i32 = 7;
+                let doubled: [i32] = [x * 2 for x in [match, enum, interface]];
+                match = match + doubled[2] as i32;
+                loop { for += 1; } while (for < 10);
+                match + enum + interface + for + in + while + as
+            }
+        "#);
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::Integer(40)));
+    }
+
+    #[test]
+    fn test_shadowing_natives() {
+        let mut interpreter = load!(r#"
+            func assert(cond: bool) -> i32 {
+                1
+            }
+        "#);
+
+        assert_eq!(interpreter.eval_source("assert(false)"), Ok(Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let mut interpreter = load!(r#"
+            func test() -> i32 {
+                let total: i32 = 0;
+                loop (const pair: enumerate([10, 20, 30])) {
+                    total = total + pair[0] * pair[1];
+                };
+                total
+            }
+        "#);
+
+        assert_eq!(interpreter.call_function("test", vec![]), Ok(Value::Integer(80)));
+        assert_eq!(
+            interpreter.eval_source("enumerate([i32, 2]{7})"),
+            Ok(Value::vector(vec![
+                Value::Tuple(vec![Value::Integer(0), Value::Integer(7)]),
+                Value::Tuple(vec![Value::Integer(1), Value::Integer(7)])
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_maps() {
+        let mut interpreter = load!(r#"
+            func scores() -> i32 {
+                let m: Map<str, i32> = { "a": 1, "b": 2 };
+                m["c"] = 3;
+                m["a"] = 10;
+                m["a"] + m["b"] + m["c"]
+            }
+
+            func missing() -> i32 {
+                let m: Map<str, i32> = { "a": 1 };
+                m["b"]
+            }
+
+            func function_key() -> i32 {
+                let m: Map<str, i32> = { "a": 1 };
+                m[missing] = 2;
+                0
+            }
+        "#);
+
+        assert_eq!(
+            interpreter.eval_source(r#"{ "a": 1, 'b': 2.5 }"#),
+            Ok(Value::map(HashMap::from([
+                (Value::String("a".to_string()), Value::Integer(1)),
+                (Value::Char('b'), Value::Float(2.5))
+            ])))
+        );
+        assert_eq!(interpreter.call_function("scores", vec![]), Ok(Value::Integer(15)));
+        assert_eq!(
+            interpreter.call_function("missing", vec![]),
+            Err(InterpreterControl::RuntimeError("Key not found in map: b".to_string()))
+        );
+        assert_eq!(
+            interpreter.call_function("function_key", vec![]),
+            Err(InterpreterControl::RuntimeError("Cannot use a function as a map key: <func missing>".to_string()))
+        );
+
+        // Changing an array would change its hash while it's in the map, so arrays can't be keys, but tuples can
+        let array_key = r#"{
+            let key: [i32] = [1, 2];
+            let m: Map<i32, i32> = { 0: 0 };
+            m[key] = 3;
+            key[0] = 5;
+            m[key]
+        }"#;
+        assert_eq!(
+            interpreter.eval_source(array_key),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Cannot use an array as a map key; use a tuple instead".to_string())))
+        );
+        assert_eq!(
+            interpreter.eval_source(r#"{ let m: Map<(i32, str), i32> = { 0: 0 }; m[(1, "a")] = 2; m[(1, "a")] }"#),
+            Ok(Value::Integer(2))
+        );
+    }
+
+    #[test]
+    fn test_array_assignment() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.eval_source("{ let a: [i32] = [1, 2]; let b: [i32] = a; b[1] = 5; a }"),
+            Ok(Value::vector(vec![Value::Integer(1), Value::Integer(5)]))
+        );
+        assert_eq!(
+            interpreter.eval_source("{ let a: [i32] = [1, 2]; a[2] = 5; }"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Index 2 out of bounds for length 2".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let mut interpreter = load!(r#"
+            func array() -> [i32] {
+                let calls: i32 = 0;
+                let next_index: func() -> i32 = { || calls += 1; 1 };
+
+                let arr: [i32] = [0, 2, 3];
+                arr[next_index()] += 1;
+                arr[2] *= 2;
+                // The index is only evaluated once
+                arr[0] = calls;
+                arr
+            }
+
+            func map() -> i32 {
+                let m: Map<str, i32> = { "k": 1 };
+                m["k"] += 1;
+                m["k"] -= 5;
+                m["k"]
+            }
+
+            func missing_key() -> i32 {
+                let m: Map<str, i32> = { "k": 1 };
+                m["j"] += 1;
+                0
+            }
+        "#);
+
+        assert_eq!(
+            interpreter.call_function("array", vec![]),
+            Ok(Value::vector(vec![Value::Integer(1), Value::Integer(3), Value::Integer(6)]))
+        );
+        assert_eq!(interpreter.call_function("map", vec![]), Ok(Value::Integer(-3)));
+        assert_eq!(
+            interpreter.call_function("missing_key", vec![]),
+            Err(InterpreterControl::RuntimeError("Key not found in map: j".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_continue_in_while() {
+        let mut interpreter = load!(r#"
+            func odd_total() -> (i32, i32) {
+                let i: i32 = 0;
+                let total: i32 = 0;
+                loop (i < 10) {
+                    i += 1;
+                    if (i % 2 == 0) {
+                        continue;
+                    };
+                    total += i;
+                };
+                (total, i)
+            }
+
+            func call(f: i32) -> i32 {
+                f()
+            }
+
+            func escape() -> i32 {
+                loop (true) {
+                    call() { break; };
+                };
+                0
+            }
+        "#);
+
+        // Only odd numbers are added, and the loop still stops once the condition is false
+        assert_eq!(interpreter.call_function("odd_total", vec![]), Ok(Value::Tuple(vec![Value::Integer(25), Value::Integer(10)])));
+        // Control flow can't escape a function into a loop around its caller
+        assert_eq!(
+            interpreter.call_function("escape", vec![]),
+            Err(InterpreterControl::RuntimeError("Cannot break outside of a loop".to_string()))
+        );
+        // A semicolon can still separate an if's body from its else branch
+        assert_eq!(interpreter.eval_source("if (false) { 1 }; else { 2 }"), Ok(Value::Integer(2)));
+    }
+
+    #[test]
+    fn test_do_while() {
+        let mut interpreter = load!(r#"
+            func runs_once() -> i32 {
+                let count: i32 = 0;
+                loop {
+                    count += 1;
+                } while (false);
+                count
+            }
+
+            func skips_odd() -> i32 {
+                let i: i32 = 0;
+                let total: i32 = 0;
+                loop {
+                    i += 1;
+                    if (i % 2 == 1) {
+                        continue;
+                    } else {
+                        total += i;
+                    };
+                    if (i >= 8) {
+                        break;
+                    } else {
+                        0
+                    };
+                } while (i < 100);
+                total
+            }
+        "#);
+
+        assert_eq!(interpreter.call_function("runs_once", vec![]), Ok(Value::Integer(1)));
+        // 2 + 4 + 6 + 8, stopping at the break once i reaches 8
+        assert_eq!(interpreter.call_function("skips_odd", vec![]), Ok(Value::Integer(20)));
+    }
+
+    #[test]
+    fn test_non_boolean_conditions() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.eval_source("if (5) { 1 } else { 2 }"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Condition must be a boolean, got 5".to_string())))
+        );
+        assert_eq!(
+            interpreter.eval_source(r#"loop ("yes") { break; }"#),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Condition must be a boolean, got yes".to_string())))
+        );
+        assert_eq!(interpreter.eval_source("if (1 < 2) { 1 } else { 2 }"), Ok(Value::Integer(1)));
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        // Like `main`, this needs a larger stack than the default test thread has
+        let thread = std::thread::Builder::new().stack_size(256 * 1024 * 1024).spawn(|| {
+            let mut interpreter = load!(r#"
+                func count(n: i32) -> i32 {
+                    if (n == 0) { 0 } else { count(n - 1) + 1 }
+                }
+            "#);
+            interpreter.set_max_stack(192 * 1024 * 1024);
+
+            assert_eq!(interpreter.call_function("count", vec![Value::Integer(100)]), Ok(Value::Integer(100)));
+            assert_eq!(
+                interpreter.call_function("count", vec![Value::Integer(100_000)]),
+                Err(InterpreterControl::RuntimeError("Maximum recursion depth exceeded".to_string()))
+            );
+
+            // The depth is reset after an error
+            assert_eq!(interpreter.call_function("count", vec![Value::Integer(10)]), Ok(Value::Integer(10)));
+        }).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        // Runaway recursion is an error, not a stack overflow, on a thread with the default stack size too
+        let mut interpreter = load!(r#"
+            func count(n: i32) -> i32 {
+                if (n == 0) { 0 } else { count(n - 1) + 1 }
+            }
+        "#);
+        assert_eq!(
+            interpreter.call_function("count", vec![Value::Integer(100_000)]),
+            Err(InterpreterControl::RuntimeError("Maximum recursion depth exceeded".to_string()))
+        );
+        assert_eq!(interpreter.call_function("count", vec![Value::Integer(10)]), Ok(Value::Integer(10)));
+    }
+
+    #[test]
+    fn test_eval_source() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval_source("1 + 2 * 3"), Ok(Value::Integer(7)));
+        assert_eq!(interpreter.eval_source("1.5 * 2"), Ok(Value::Float(3.0)));
+        assert_eq!(interpreter.eval_source("\"a\" + \"b\""), Ok(Value::String("ab".to_string())));
+        assert_eq!(interpreter.eval_source("if (1 < 2) { 3 } else { 4 }"), Ok(Value::Integer(3)));
+        assert_eq!(interpreter.eval_source("- -3 - 1"), Ok(Value::Integer(2)));
+        assert_eq!(interpreter.eval_source("5--3 == 8"), Ok(Value::Boolean(true)));
+        assert_eq!(interpreter.eval_source("!!true"), Ok(Value::Boolean(true)));
+    }
+
+    #[test]
+    fn test_eval_source_errors() {
+        let mut interpreter = Interpreter::new();
+        assert!(matches!(interpreter.eval_source("1 + 2 )"), Err(EvalError::Parse(_))));
+        assert!(matches!(interpreter.eval_source("$"), Err(EvalError::Tokenize(_))));
+        assert_eq!(
+            interpreter.eval_source("1 / 0"),
+            Err(EvalError::Runtime(InterpreterControl::RuntimeError("Division by zero".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_error_span() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.eval_source("1 +\n  (2 / 0)").is_err());
+        assert_eq!(interpreter.error_span(), Some(Span { line: 2, column: 6 }));
+
+        assert!(interpreter.eval_source("  missing(1)").is_err());
+        assert_eq!(interpreter.error_span(), Some(Span { line: 1, column: 3 }));
+
+        assert!(interpreter.eval_source("true - 1").is_err());
+        assert_eq!(interpreter.error_span(), Some(Span { line: 1, column: 6 }));
+
+        assert!(interpreter.eval_source("1 + 1").is_ok());
+        assert_eq!(interpreter.error_span(), None);
+    }
+
+    #[test]
+    fn test_evaluation_hook() {
+        struct Recorder(Rc<RefCell<Vec<String>>>);
+
+        impl EvaluationHook for Recorder {
+            fn before(&mut self, _: &Interpreter, expression: &Expression) {
+                self.0.borrow_mut().push(format!("before {:?}", expression.id()));
+            }
+
+            fn after(&mut self, _: &Interpreter, expression: &Expression, result: &InterpreterResult) {
+                self.0.borrow_mut().push(format!("after {:?} {:?}", expression.id(), result));
+            }
+        }
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_hook(Box::new(Recorder(events.clone())));
+        assert_eq!(interpreter.eval_source("-(1 + 2)"), Ok(Value::Integer(-3)));
+
+        assert_eq!(*events.borrow(), vec![
+            "before Some(ExpressionId(1))",
+            "before Some(ExpressionId(2))",
+            "before None",
+            "after None Ok(Integer(1))",
+            "before None",
+            "after None Ok(Integer(2))",
+            "after Some(ExpressionId(2)) Ok(Integer(3))",
+            "after Some(ExpressionId(1)) Ok(Integer(-3))"
+        ]);
+        // Without the hook, nothing more is recorded
+        assert!(interpreter.take_hook().is_some());
+        assert_eq!(interpreter.eval_source("1"), Ok(Value::Integer(1)));
+        assert_eq!(events.borrow().len(), 8);
+    }
+
+    #[test]
+    fn test_current_bindings() {
+        type Snapshots = Rc<RefCell<Vec<Vec<(String, Value)>>>>;
+
+        /// Records the bindings in scope whenever the variable `y` is evaluated.
+        struct Inspector(Snapshots);
+
+        impl EvaluationHook for Inspector {
+            fn before(&mut self, interpreter: &Interpreter, expression: &Expression) {
+                if let Expression::Variable { name, .. } = expression {
+                    if name.as_str() == "y" {
+                        self.0.borrow_mut().push(interpreter.current_bindings());
+                    }
+                }
+            }
+        }
+
+        let mut interpreter = load!(r#"
+            func main(a: i32) -> i32 {
+                let x: i32 = 1;
+                let y: i32 = 2;
+                {
+                    let x: str = "inner";
+                    let z: i32 = y;
+                    z
+                }
+            }
+        "#);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        interpreter.set_hook(Box::new(Inspector(seen.clone())));
+        assert_eq!(interpreter.call_function("main", vec![Value::Integer(0)]), Ok(Value::Integer(2)));
+
+        // The inner `x` shadows the outer one, `z` isn't defined yet, and globals like `main` aren't included
+        assert_eq!(*seen.borrow(), vec![vec![
+            ("x".to_string(), Value::String("inner".to_string())),
+            ("y".to_string(), Value::Integer(2)),
+            ("a".to_string(), Value::Integer(0))
+        ]]);
+    }
+}
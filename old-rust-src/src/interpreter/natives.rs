@@ -0,0 +1,322 @@
+use std::io::Write;
+
+use crate::parser::ast::VariableMutability;
+
+use super::{environment::Environment, value::{NativeFunction, Value}, Interpreter, InterpreterControl, InterpreterResult};
+
+const NATIVES: &[NativeFunction] = &[
+    NativeFunction { name: "zip", arity: Some(2), function: zip },
+    NativeFunction { name: "enumerate", arity: Some(1), function: enumerate },
+    NativeFunction { name: "assert", arity: Some(1), function: assert },
+    NativeFunction { name: "assert_eq", arity: Some(2), function: assert_eq },
+    NativeFunction { name: "panic", arity: Some(1), function: panic },
+    NativeFunction { name: "format", arity: None, function: format },
+    NativeFunction { name: "ord", arity: Some(1), function: ord },
+    NativeFunction { name: "chr", arity: Some(1), function: chr },
+    NativeFunction { name: "char_to_int", arity: Some(1), function: ord },
+    NativeFunction { name: "int_to_char", arity: Some(1), function: chr },
+    NativeFunction { name: "is_digit", arity: Some(1), function: is_digit },
+    NativeFunction { name: "is_alpha", arity: Some(1), function: is_alpha },
+    NativeFunction { name: "to_upper", arity: Some(1), function: to_upper },
+    NativeFunction { name: "to_lower", arity: Some(1), function: to_lower },
+    NativeFunction { name: "char_to_string", arity: Some(1), function: char_to_string },
+    NativeFunction { name: "string_to_chars", arity: Some(1), function: string_to_chars },
+    NativeFunction { name: "chars_to_string", arity: Some(1), function: chars_to_string },
+    NativeFunction { name: "is_type", arity: Some(2), function: is_type },
+    NativeFunction { name: "expect_type", arity: Some(2), function: expect_type },
+    NativeFunction { name: "typeof", arity: Some(1), function: type_of },
+    NativeFunction { name: "parse_int", arity: Some(1), function: parse_int },
+    NativeFunction { name: "parse_float", arity: Some(1), function: parse_float },
+    NativeFunction { name: "to_string", arity: Some(1), function: to_string },
+    NativeFunction { name: "read_line", arity: Some(0), function: read_line },
+    NativeFunction { name: "read_int", arity: Some(0), function: read_int },
+    NativeFunction { name: "eprint", arity: None, function: eprint },
+    NativeFunction { name: "eprintln", arity: None, function: eprintln }
+];
+
+/// Defines every native function in the given environment.
+/// They're defined like any other global, so programs can shadow them.
+pub fn define_natives(environment: &mut Environment) {
+    for native in NATIVES {
+        environment.define(native.name.to_string(), Value::NativeFunction(*native), VariableMutability::Immutable);
+    }
+}
+
+/// `zip(a, b)` pairs up the elements of two arrays, stopping at the end of the shorter one.
+fn zip(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    match (&args[0], &args[1]) {
+        (Value::Vector(a), Value::Vector(b)) => Ok(Value::vector(
+            a.borrow().iter().zip(b.borrow().iter()).map(|(a, b)| Value::Tuple(vec![a.clone(), b.clone()])).collect()
+        )),
+        (a, b) => runtime_error!("zip expects two arrays, got {} and {}", a, b)
+    }
+}
+
+/// `enumerate(arr)` pairs each element of an array with its index.
+fn enumerate(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    match &args[0] {
+        Value::Vector(values) => Ok(Value::vector(
+            values.borrow().iter().enumerate().map(|(i, value)| Value::Tuple(vec![Value::Integer(i as i64), value.clone()])).collect()
+        )),
+        value => runtime_error!("enumerate expects an array, got {}", value)
+    }
+}
+
+/// `assert(cond)` fails with a runtime error if the condition is false.
+fn assert(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    match &args[0] {
+        Value::Boolean(true) => Ok(Value::Nil),
+        Value::Boolean(false) => runtime_error!("Assertion failed"),
+        value => runtime_error!("assert expects a boolean, got {}", value)
+    }
+}
+
+/// `assert_eq(a, b)` fails with a runtime error showing both values if they aren't equal.
+fn assert_eq(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let (left, right) = (args[0].to_string(), args[1].to_string());
+    if args[0] == args[1] {
+        Ok(Value::Nil)
+    } else if left == right {
+        // Values like `1` and `"1"` look the same, so the types are what tell them apart
+        runtime_error!("Assertion failed: {} ({}) != {} ({})", left, args[0].type_name(), right, args[1].type_name())
+    } else {
+        runtime_error!("Assertion failed: {} != {}", left, right)
+    }
+}
+
+/// `panic(msg)` always fails with a runtime error containing the message.
+fn panic(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    runtime_error!("Panic: {}", args[0])
+}
+
+/// `format(template, ...args)` replaces each `{}` in the template with the next argument.
+/// `{{` and `}}` are literal braces, and there must be exactly as many arguments as placeholders.
+fn format(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let Some((template, values)) = args.split_first() else {
+        return runtime_error!("format expects a template");
+    };
+    let Value::String(template) = template else {
+        return runtime_error!("format expects a string template, got {}", template);
+    };
+
+    let mut result = String::new();
+    let mut remaining = values.iter();
+    let mut placeholders = 0;
+    let mut characters = template.chars().peekable();
+    while let Some(c) = characters.next() {
+        match (c, characters.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                characters.next();
+                result.push(c);
+            },
+            ('{', Some('}')) => {
+                characters.next();
+                placeholders += 1;
+                if let Some(arg) = remaining.next() {
+                    result.push_str(&arg.to_string());
+                }
+            },
+            ('{' | '}', _) => return runtime_error!("Unmatched '{}' in format template; use '{}{}' for a literal brace", c, c, c),
+            _ => result.push(c)
+        }
+    }
+
+    if placeholders != values.len() {
+        return runtime_error!("format template has {} placeholders but got {} arguments", placeholders, values.len());
+    }
+    Ok(Value::String(result))
+}
+
+/// Returns the character a native function was given, or an error if it got something else.
+fn char_argument(value: &Value) -> InterpreterResult<char> {
+    match value {
+        Value::Char(c) => Ok(*c),
+        value => runtime_error!("Expected a character, got {}", value)
+    }
+}
+
+/// `ord(c)` (or `char_to_int(c)`) returns the Unicode code point of a character.
+fn ord(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Integer(char_argument(&args[0])? as i64))
+}
+
+/// `chr(n)` (or `int_to_char(n)`) returns the character for a Unicode code point. Surrogates and values past `0x10FFFF` aren't characters.
+fn chr(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    match &args[0] {
+        Value::Integer(n) => match u32::try_from(*n).ok().and_then(char::from_u32) {
+            Some(c) => Ok(Value::Char(c)),
+            None => runtime_error!("Invalid code point: {}", n)
+        },
+        value => runtime_error!("Expected an integer code point, got {}", value)
+    }
+}
+
+/// `is_digit(c)` returns whether a character is an ASCII digit, `0` through `9`.
+fn is_digit(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Boolean(char_argument(&args[0])?.is_ascii_digit()))
+}
+
+/// `is_alpha(c)` returns whether a character is a letter, in any script.
+fn is_alpha(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Boolean(char_argument(&args[0])?.is_alphabetic()))
+}
+
+/// `to_upper(c)` returns the uppercase form of a character.
+/// Characters whose uppercase form is several characters, like `ß`, are returned unchanged.
+fn to_upper(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let c = char_argument(&args[0])?;
+    Ok(Value::Char(single_char(c.to_uppercase()).unwrap_or(c)))
+}
+
+/// `to_lower(c)` returns the lowercase form of a character.
+/// Characters whose lowercase form is several characters are returned unchanged.
+fn to_lower(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let c = char_argument(&args[0])?;
+    Ok(Value::Char(single_char(c.to_lowercase()).unwrap_or(c)))
+}
+
+/// `char_to_string(c)` returns a string holding just the character.
+fn char_to_string(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::String(char_argument(&args[0])?.to_string()))
+}
+
+/// `string_to_chars(s)` returns an array of the characters in a string.
+fn string_to_chars(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::vector(string_argument(&args[0])?.chars().map(Value::Char).collect()))
+}
+
+/// `chars_to_string(chars)` joins an array of characters into a string, undoing `string_to_chars`.
+fn chars_to_string(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let Value::Vector(chars) = &args[0] else {
+        return runtime_error!("Expected an array of characters, got {}", args[0]);
+    };
+    let mut result = String::new();
+    for c in chars.borrow().iter() {
+        result.push(char_argument(c)?);
+    }
+    Ok(Value::String(result))
+}
+
+/// Returns the only character in a case mapping, if it maps to exactly one.
+fn single_char(mut mapping: impl Iterator<Item = char>) -> Option<char> {
+    let c = mapping.next()?;
+    mapping.next().is_none().then_some(c)
+}
+
+/// Checks a value against a type name given as a string, returning whether it matches.
+fn check_type(value: &Value, name: &Value) -> InterpreterResult<bool> {
+    let Value::String(name) = name else {
+        return runtime_error!("Expected a type name, got {}", name);
+    };
+    match value.is_type(name) {
+        Ok(matches) => Ok(matches),
+        Err(message) => runtime_error!(message)
+    }
+}
+
+/// `is_type(value, name)` returns whether a value has the type with the given name, like `"int"` or `"string"`.
+fn is_type(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::Boolean(check_type(&args[0], &args[1])?))
+}
+
+/// `expect_type(value, name)` returns the value if it has the given type, and fails with a runtime error otherwise.
+fn expect_type(_: &mut Interpreter, mut args: Vec<Value>) -> InterpreterResult {
+    if !check_type(&args[0], &args[1])? {
+        return runtime_error!("Expected a value of type {}, got {} {}", args[1], args[0].type_name(), args[0]);
+    }
+    Ok(args.swap_remove(0))
+}
+
+/// `typeof(value)` returns the name of a value's type, like `"i32"` or `"string"`, with the name of the struct or enum
+/// for those, like `"struct Point"`. The interpreter names a variable, typed literal, or cast by its declared numeric type
+/// before this is called; other numbers are named by how they're stored, `"i64"` or `"f64"`.
+fn type_of(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let name = match &args[0] {
+        Value::Integer(_) => "i64".to_string(),
+        Value::Float(_) => "f64".to_string(),
+        Value::Struct(instance) => format!("struct {}", instance.name),
+        Value::Interface(value) => format!("struct {}", value.instance.name),
+        Value::Enum(instance) => format!("enum {}", instance.enum_name),
+        value => value.type_name().to_string()
+    };
+    Ok(Value::String(name))
+}
+
+/// Returns the string a native function was given, or an error if it got something else.
+fn string_argument(value: &Value) -> InterpreterResult<&str> {
+    match value {
+        Value::String(s) => Ok(s),
+        value => runtime_error!("Expected a string, got {}", value)
+    }
+}
+
+/// `parse_int(s)` parses a string like `"-42"` as an integer.
+fn parse_int(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let s = string_argument(&args[0])?;
+    match s.parse() {
+        Ok(n) => Ok(Value::Integer(n)),
+        Err(_) => runtime_error!("Invalid integer: \"{}\"", s)
+    }
+}
+
+/// `parse_float(s)` parses a string like `"1.5"` or `"2"` as a float.
+fn parse_float(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    let s = string_argument(&args[0])?;
+    match s.parse() {
+        Ok(n) => Ok(Value::Float(n)),
+        Err(_) => runtime_error!("Invalid float: \"{}\"", s)
+    }
+}
+
+/// `to_string(value)` formats any value the same way `print` does.
+fn to_string(_: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    Ok(Value::String(args[0].to_string()))
+}
+
+/// `read_line()` reads a line from the interpreter's input without its line ending, or returns nil at the end of the input.
+fn read_line(interpreter: &mut Interpreter, _: Vec<Value>) -> InterpreterResult {
+    interpreter.require_io("read_line")?;
+    let mut line = String::new();
+    match interpreter.input.read_line(&mut line) {
+        Ok(0) => Ok(Value::Nil),
+        Ok(_) => {
+            let length = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(length);
+            Ok(Value::String(line))
+        },
+        Err(e) => runtime_error!("Failed to read input: {}", e)
+    }
+}
+
+/// `read_int()` reads a line from the interpreter's input and parses it as an integer, or returns nil at the end of the input.
+/// Whitespace around the number is ignored.
+fn read_int(interpreter: &mut Interpreter, _: Vec<Value>) -> InterpreterResult {
+    interpreter.require_io("read_int")?;
+    match read_line(interpreter, Vec::new())? {
+        Value::String(line) => match line.trim().parse() {
+            Ok(n) => Ok(Value::Integer(n)),
+            Err(_) => runtime_error!("Invalid integer: \"{}\"", line)
+        },
+        value => Ok(value)
+    }
+}
+
+/// `eprint(values...)` writes the values to the interpreter's error output, separated by spaces, without a newline.
+fn eprint(interpreter: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    interpreter.require_io("eprint")?;
+    write_error_output(interpreter, &args, "")
+}
+
+/// `eprintln(values...)` is like `eprint`, but ends with a newline.
+fn eprintln(interpreter: &mut Interpreter, args: Vec<Value>) -> InterpreterResult {
+    interpreter.require_io("eprintln")?;
+    write_error_output(interpreter, &args, "\n")
+}
+
+fn write_error_output(interpreter: &mut Interpreter, args: &[Value], end: &str) -> InterpreterResult {
+    let text: Vec<String> = args.iter().map(|value| value.to_string()).collect();
+    match write!(interpreter.error_output, "{}{}", text.join(" "), end) {
+        Ok(()) => Ok(Value::Nil),
+        Err(e) => runtime_error!("Failed to write output: {}", e)
+    }
+}
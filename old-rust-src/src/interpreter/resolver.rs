@@ -1,32 +1,175 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
 
-use crate::parser::ast::{Declaration, Expression, ExpressionId, LoopType, Program, Statement, Type};
+use crate::{lint::LintWarning, optimizer::to_value, parser::ast::{BinaryOperator, Declaration, EnumVariant, Expression, ExpressionId, FunctionParameter, InterfaceMethod, LoopType, Pattern, Program, Span, Statement, StructElement, Type, UnaryOperator, VariableMutability}, stack::StackLimit};
 
-use super::Interpreter;
+use super::{value::Value, Interpreter};
+
+/// How the resolver resolved one variable read or assignment.
+#[derive(Debug, PartialEq)]
+pub struct Resolution {
+    pub expression_id: ExpressionId,
+    pub name: String,
+    /// How many scopes out the variable was found, or None if it's a global
+    pub depth: Option<usize>,
+    /// Where the variable is in the source, if the interpreter was given the parser's spans before resolving
+    pub span: Option<Span>
+}
+
+/// An error found while resolving, with where it is if that's known.
+#[derive(Debug, PartialEq)]
+pub struct ResolveError {
+    pub message: String,
+    /// The innermost expression with a known position that the error is in, which also says which file it's from
+    pub expression_id: Option<ExpressionId>,
+    pub span: Option<Span>
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.depth {
+            Some(depth) => write!(f, "#{} {}: depth {}", self.expression_id.0, self.name, depth)?,
+            None => write!(f, "#{} {}: global", self.expression_id.0, self.name)?
+        }
+        match self.span {
+            Some(span) => write!(f, " at {}", span),
+            None => Ok(())
+        }
+    }
+}
+
+/// How deeply const functions can call each other while a constant is being worked out.
+const MAX_CONST_CALL_DEPTH: usize = 64;
+
+/// A top-level function with type parameters, like `func identity<T>(x: T) -> T`.
+struct GenericFunction {
+    generic_args: Vec<String>,
+    param_types: Vec<Type>,
+    return_type: Type
+}
 
 pub struct Resolver<'a> {
     interpreter: &'a mut Interpreter,
-    scopes: Vec<HashMap<String, bool>>
+    scopes: Vec<HashMap<String, bool>>,
+    /// Type aliases by name, with their generic parameters and the type they stand for
+    type_aliases: HashMap<String, (Vec<String>, Type)>,
+    /// Enums by name, with their generic parameters and variants
+    enums: HashMap<String, (Vec<String>, Vec<EnumVariant>)>,
+    /// Interfaces by name, with the methods they require
+    interfaces: HashMap<String, Vec<InterfaceMethod>>,
+    /// Top-level functions with type parameters, by name
+    generic_functions: HashMap<String, GenericFunction>,
+    /// Every variable resolved so far, in the order they were resolved
+    resolutions: Vec<Resolution>,
+    /// The values of the constants declared in each scope, for checking array sizes
+    constants: Vec<HashMap<String, i64>>,
+    /// The values of the top-level constants declared so far
+    global_constants: HashMap<String, i64>,
+    /// Top-level `const func`s by name, with their parameters and bodies
+    const_functions: HashMap<String, (Vec<FunctionParameter>, Rc<Expression>)>,
+    /// The types of the variables declared in each scope with an optional type
+    optionals: Vec<HashMap<String, Type>>,
+    /// The reads of variables with optional types, for checking that they're unwrapped before they're used
+    optional_reads: HashMap<ExpressionId, Type>,
+    /// The `let`s and `const`s declared in each scope that haven't been read yet, with the IDs of their declarations
+    unread: Vec<HashMap<String, ExpressionId>>,
+    /// The imports that nothing has referenced yet by the last part of their path, with their paths and the IDs of their declarations
+    unreferenced_imports: HashMap<String, (String, ExpressionId)>,
+    /// Warnings about code that resolves but is probably a mistake
+    warnings: Vec<LintWarning>,
+    /// The name and declared return type of each function being resolved, innermost last. Lambdas don't declare one.
+    return_types: Vec<Option<(String, Type)>>,
+    /// The errors found so far. Resolving carries on after an error in a declaration or statement to find the rest.
+    errors: Vec<ResolveError>,
+    /// The innermost expression with a known position where the error being reported happened
+    error_expression: Option<ExpressionId>,
+    /// How many expressions are currently being resolved inside each other
+    depth: usize,
+    /// The same limit the interpreter has, since resolving recurses over expressions the same way evaluating them does
+    stack: StackLimit
 }
 
 impl<'a> Resolver<'a> {
     pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        let stack = interpreter.stack;
         Resolver {
             interpreter,
-            scopes: Vec::new()
+            scopes: Vec::new(),
+            type_aliases: HashMap::new(),
+            enums: HashMap::new(),
+            interfaces: HashMap::new(),
+            generic_functions: HashMap::new(),
+            resolutions: Vec::new(),
+            constants: Vec::new(),
+            global_constants: HashMap::new(),
+            const_functions: HashMap::new(),
+            optionals: Vec::new(),
+            optional_reads: HashMap::new(),
+            unread: Vec::new(),
+            unreferenced_imports: HashMap::new(),
+            warnings: Vec::new(),
+            return_types: Vec::new(),
+            errors: Vec::new(),
+            error_expression: None,
+            depth: 0,
+            stack
         }
     }
 
+    /// Returns how each variable read and assignment was resolved, in the order they were resolved.
+    pub fn resolutions(&self) -> &[Resolution] {
+        &self.resolutions
+    }
+
+    /// Returns every error found while resolving, in the order they were found.
+    pub fn errors(&self) -> &[ResolveError] {
+        &self.errors
+    }
+
+    /// Takes the warnings found while resolving, like local variables that are never read and imports that are never referenced.
+    pub fn take_warnings(&mut self) -> Vec<LintWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
+        self.constants.push(HashMap::new());
+        self.optionals.push(HashMap::new());
+        self.unread.push(HashMap::new());
     }
 
     fn end_scope(&mut self) {
         self.scopes.pop();
+        self.constants.pop();
+        self.optionals.pop();
+        if let Some(unread) = self.unread.pop() {
+            let mut unread: Vec<(String, ExpressionId)> = unread.into_iter().collect();
+            unread.sort_by_key(|(_, expression_id)| expression_id.0);
+            for (name, expression_id) in unread {
+                self.warn_unread(&name, expression_id);
+            }
+        }
+    }
+
+    /// Warns about a variable that went out of scope or was shadowed without being read.
+    fn warn_unread(&mut self, name: &str, expression_id: ExpressionId) {
+        let message = match self.interpreter.span(expression_id) {
+            Some(span) => format!("Variable {} at {} is never read; start its name with an underscore if that's intended", name, span),
+            None => format!("Variable {} is never read; start its name with an underscore if that's intended", name)
+        };
+        self.warnings.push(LintWarning { message });
     }
     
     /// Declares a variable in the topmost scope as "being defined".
     fn declare(&mut self, name: String) {
+        if let Some(constants) = self.constants.last_mut() {
+            constants.remove(&name); // The new variable shadows any constant with the same name
+        }
+        if let Some(optionals) = self.optionals.last_mut() {
+            optionals.remove(&name);
+        }
+        if let Some(expression_id) = self.unread.last_mut().and_then(|unread| unread.remove(&name)) {
+            self.warn_unread(&name, expression_id); // Shadowing it means it can't be read anymore
+        }
         if let Some(scope) = self.scopes.last_mut() {
             scope.insert(name, false);
         }
@@ -40,99 +183,421 @@ impl<'a> Resolver<'a> {
     }
 
     pub fn resolve_program(&mut self, program: &Program) -> Result<(), String> {
+        // Aliases, enums, interfaces, and generic functions are collected first so that code can refer to ones declared later in the file
         for declaration in &program.declarations {
-            self.resolve_declaration(declaration)?;
+            match declaration {
+                Declaration::TypeDeclaration { name, generic_args, alias } => {
+                    self.type_aliases.insert(name.clone(), (generic_args.clone(), alias.clone()));
+                },
+                Declaration::Enum { name, variants, generic_args } => {
+                    self.enums.insert(name.clone(), (generic_args.clone(), variants.clone()));
+                },
+                Declaration::Interface { name, methods } => {
+                    self.interfaces.insert(name.clone(), methods.clone());
+                },
+                Declaration::Function { name, params, generic_args, return_type, .. } if !generic_args.is_empty() => {
+                    self.generic_functions.insert(name.clone(), GenericFunction {
+                        generic_args: generic_args.clone(),
+                        param_types: params.iter().map(|param| param.param_type.clone()).collect(),
+                        return_type: return_type.clone()
+                    });
+                },
+                Declaration::Function { name, params, body, is_const: true, .. } => {
+                    self.const_functions.insert(name.clone(), (params.clone(), body.clone()));
+                },
+                _ => {}
+            }
+        }
+
+        for declaration in &program.declarations {
+            self.recover(|resolver| resolver.resolve_declaration(declaration));
+        }
+
+        let mut unreferenced: Vec<(String, ExpressionId)> = self.unreferenced_imports.drain().map(|(_, import)| import).collect();
+        unreferenced.sort_by_key(|(_, expression_id)| expression_id.0);
+        for (path, expression_id) in unreferenced {
+            let message = match self.interpreter.span(expression_id) {
+                Some(span) => format!("Import {} at {} is never referenced", path, span),
+                None => format!("Import {} is never referenced", path)
+            };
+            self.warnings.push(LintWarning { message });
+        }
+        self.first_error()
+    }
+
+    /// Resolves an expression on its own, like one passed to `Interpreter::eval_source`.
+    pub fn resolve_snippet(&mut self, expression: &Expression) -> Result<(), String> {
+        self.recover(|resolver| resolver.resolve_expression(expression));
+        self.first_error()
+    }
+
+    /// Returns the message of the first error found, if there was one. All of them are in `errors`.
+    fn first_error(&self) -> Result<(), String> {
+        match self.errors.first() {
+            Some(error) => Err(error.message.clone()),
+            None => Ok(())
+        }
+    }
+
+    /// Runs `resolve`, recording the error it fails with, if any, so that resolving can continue after it.
+    fn recover(&mut self, resolve: impl FnOnce(&mut Self) -> Result<(), String>) {
+        let lengths = (self.scopes.len(), self.constants.len(), self.optionals.len(), self.unread.len(), self.return_types.len());
+        self.error_expression = None;
+        let Err(message) = resolve(self) else {
+            return;
+        };
+
+        // Close the scopes the error left open, without warning about their variables
+        self.scopes.truncate(lengths.0);
+        self.constants.truncate(lengths.1);
+        self.optionals.truncate(lengths.2);
+        self.unread.truncate(lengths.3);
+        self.return_types.truncate(lengths.4);
+
+        let expression_id = self.error_expression.take();
+        let span = expression_id.and_then(|expression_id| self.interpreter.span(expression_id));
+        self.errors.push(ResolveError { message, expression_id, span });
+    }
+
+    /// Locates the error being reported at an expression, unless it's already been located at one inside it.
+    fn locate_error(&mut self, expression_id: Option<ExpressionId>) {
+        if self.error_expression.is_none() {
+            self.error_expression = expression_id.filter(|&expression_id| self.interpreter.span(expression_id).is_some());
         }
-        Ok(())
     }
 
     fn resolve_declaration(&mut self, declaration: &Declaration) -> Result<(), String> {
         match declaration {
-            Declaration::Function { name, params, return_type, body, generic_args } => {
-                todo!()
+            Declaration::Function { name, params, return_type, body, .. } => {
+                self.declare(name.to_string());
+                self.define(name.to_string());
+                self.resolve_function(name, params, return_type, body)?;
+            },
+            Declaration::Import { path, expression_id } => {
+                // TODO: Imports
+                // An import is referenced by the last part of its path, like `math.square()` after `import util.math;`
+                if let Some(name) = path.last() {
+                    self.unreferenced_imports.insert(name.clone(), (path.join("."), *expression_id));
+                }
+            },
+            Declaration::Struct { name: struct_name, elements, interfaces, .. } => {
+                // A field sharing its name with a method would make member access ambiguous
+                let methods: Vec<&String> = elements.iter().filter_map(|element| match element {
+                    StructElement::Declaration(Declaration::Function { name, .. }) => Some(name),
+                    _ => None
+                }).collect();
+                for element in elements {
+                    if let StructElement::Field { name, .. } = element {
+                        if methods.contains(&name) {
+                            return Err(format!("Error: Field {} conflicts with method {} in struct {}.", name, name, struct_name));
+                        }
+                    }
+                }
+
+                for element in elements {
+                    match element {
+                        // Methods see the instance they're called on as `self`, in a scope around their parameters
+                        StructElement::Declaration(Declaration::Function { name, params, return_type, body, .. }) => {
+                            self.begin_scope();
+                            self.declare("self".to_string());
+                            self.define("self".to_string());
+                            self.resolve_function(name, params, return_type, body)?;
+                            self.end_scope();
+                        },
+                        StructElement::Declaration(declaration) => self.resolve_declaration(declaration)?,
+                        StructElement::Field { .. } => {}
+                    }
+                }
+
+                for interface in interfaces {
+                    let Some(required) = self.interfaces.get(interface) else {
+                        return Err(format!("Error: Struct {} implements unknown interface {}.", struct_name, interface));
+                    };
+                    for method in required {
+                        let declared = elements.iter().find_map(|element| match element {
+                            StructElement::Declaration(Declaration::Function { name, params, return_type, .. }) if name == &method.name => Some((params, return_type)),
+                            _ => None
+                        });
+                        let Some((params, return_type)) = declared else {
+                            return Err(format!("Error: Struct {} is missing method {} required by interface {}.", struct_name, method.name, interface));
+                        };
+                        let expected = self.method_type(&method.params, &method.return_type)?;
+                        let actual = self.method_type(params, return_type)?;
+                        if actual != expected {
+                            return Err(format!("Error: Method {}.{} has type {}, but interface {} requires {}.", struct_name, method.name, actual, interface, expected));
+                        }
+                    }
+                }
+            },
+            Declaration::Interface { name, methods } => {
+                for method in methods {
+                    self.method_type(&method.params, &method.return_type)?;
+                }
+                self.interfaces.insert(name.clone(), methods.clone());
             },
-            Declaration::Import { path } => {
-                todo!()
+            Declaration::TypeDeclaration { name, generic_args, alias } => {
+                self.type_aliases.insert(name.clone(), (generic_args.clone(), alias.clone()));
+                // Expanding the alias catches aliases that refer back to themselves
+                self.expand_type(alias, &mut vec![name.clone()])?;
             },
-            Declaration::Struct { name, elements: declarations, generic_args } => {
-                todo!()
+            Declaration::Enum { name, variants, generic_args } => {
+                self.enums.insert(name.clone(), (generic_args.clone(), variants.clone()));
+                for variant in variants {
+                    for field in &variant.fields {
+                        self.resolve_type(field)?;
+                    }
+                }
             },
-            Declaration::TypeDeclaration { name, alias, generic_args } => {
-                todo!()
+            Declaration::GlobalVariable { mutability, name, variable_type, value } => {
+                self.resolve_variable_declaration(*mutability, name, variable_type, value)?;
             }
         }
         Ok(())
     }
 
+    /// Resolves a function's parameters and body, and checks that its body returns what it declares.
+    /// The caller declares the function's name, since methods aren't visible by name.
+    fn resolve_function(&mut self, name: &str, params: &[FunctionParameter], return_type: &Type, body: &Expression) -> Result<(), String> {
+        for param in params {
+            self.resolve_type(&param.param_type)?;
+        }
+        let return_type = self.resolve_type(return_type)?;
+
+        self.begin_scope();
+        for param in params {
+            self.declare(param.name.to_string());
+            self.define(param.name.to_string());
+            self.record_optional(&param.name, &param.param_type);
+        }
+        self.return_types.push(Some((name.to_string(), return_type.clone())));
+        let resolved = self.resolve_expression(body);
+        self.return_types.pop();
+        resolved?;
+        self.end_scope();
+
+        if return_type != Type::Nil {
+            // An optional function that falls off the end returns nil
+            if !matches!(return_type, Type::Optional(_)) && !always_returns(body) {
+                return Err(format!("Error: Function {} returns {}, but can reach the end of its body without a value.", name, return_type));
+            }
+            // The block's result is what the function returns
+            if let Expression::Block(statements) = body {
+                if let Some(Statement::Expression { expression, result: true }) = statements.last() {
+                    check_return_value(name, &return_type, expression)?;
+                    self.check_unwrapped(expression, &return_type)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the type of a method with these parameters and return type, with aliases expanded so they compare equal.
+    fn method_type(&self, params: &[FunctionParameter], return_type: &Type) -> Result<Type, String> {
+        Ok(Type::Function {
+            params: params.iter().map(|param| self.resolve_type(&param.param_type)).collect::<Result<_, _>>()?,
+            return_type: Box::new(self.resolve_type(return_type)?)
+        })
+    }
+
     fn resolve_expression(&mut self, expression: &Expression) -> Result<(), String> {
+        if self.depth == 0 {
+            self.stack.restart();
+        }
+        if self.stack.exceeded() {
+            return Err("Error: Expression nesting too deep to resolve.".to_string());
+        }
+
+        self.depth += 1;
+        let result = self.resolve_expression_inner(expression);
+        self.depth -= 1;
+        if result.is_err() {
+            self.locate_error(expression.id());
+        }
+        result
+    }
+
+    fn resolve_expression_inner(&mut self, expression: &Expression) -> Result<(), String> {
         match expression {
             Expression::Assignment { name: variable, value, expression_id } => {
                 self.resolve_expression(value)?;
-                self.record_local_depth(*expression_id, variable.to_string())?;
+                self.record_local_depth(*expression_id, variable.as_str())?;
             },
             Expression::BinaryOperation { left, right, .. } => {
                 self.resolve_expression(left)?;
                 self.resolve_expression(right)?;
             },
+            Expression::UnaryOperation { operator: UnaryOperator::Negate, operand, .. } if matches!(operand.as_ref(), Expression::TypedIntegerLiteral(..)) => {
+                // `-128i8` fits even though `128i8` on its own wouldn't
+                if let Expression::TypedIntegerLiteral(value, literal_type) = operand.as_ref() {
+                    check_typed_literal(-(*value as i128), literal_type)?;
+                }
+            },
             Expression::UnaryOperation { operand, .. } => {
-                self.resolve_expression(&operand)?;
+                self.resolve_expression(operand)?;
             },
             Expression::Block(statements) => {
                 self.begin_scope();
 
+                // Functions are hoisted, so they can be called from anywhere in their block
+                for statement in statements {
+                    if let Statement::Declaration(Declaration::Function { name, .. }) = statement {
+                        self.declare(name.to_string());
+                        self.define(name.to_string());
+                    }
+                }
                 for statement in statements {
-                    self.resolve_statement(statement)?;
+                    self.recover(|resolver| resolver.resolve_statement(statement));
                 }
 
                 self.end_scope();
             },
-            Expression::BooleanLiteral(_) | Expression::CharLiteral(_) | Expression::NumberLiteral(_) | Expression::StringLiteral(_) => {
+            Expression::BooleanLiteral(_) | Expression::CharLiteral(_) | Expression::IntegerLiteral(_) | Expression::NumberLiteral(_) |
+            Expression::TypedNumberLiteral(..) | Expression::StringLiteral(_) => {
                 // Nothing
             },
-            Expression::FunctionCall { callee, args } => {
-                self.resolve_expression(&callee)?;
+            Expression::TypedIntegerLiteral(value, literal_type) => {
+                check_typed_literal(*value as i128, literal_type)?;
+            },
+            Expression::ArrayLiteral(elements) | Expression::Tuple(elements) => {
+                for element in elements {
+                    self.resolve_expression(element)?;
+                }
+            },
+            Expression::ArrayComprehension { element, binding, iterable, condition } => {
+                self.resolve_expression(iterable)?;
+
+                // Like a loop variable, the binding lives in its own scope around the condition and element
+                self.begin_scope();
+                self.declare(binding.to_string());
+                self.define(binding.to_string());
+                if let Some(condition) = condition {
+                    self.resolve_expression(condition)?;
+                }
+                self.resolve_expression(element)?;
+                self.end_scope();
+            },
+            Expression::Range { start, end, .. } => {
+                self.resolve_expression(start)?;
+                self.resolve_expression(end)?;
+            },
+            Expression::MapLiteral(entries) => {
+                for (key, value) in entries {
+                    self.resolve_expression(key)?;
+                    self.resolve_expression(value)?;
+                }
+            },
+            Expression::FunctionCall { callee, args, .. } => {
+                self.resolve_expression(callee)?;
                 for arg in args {
                     self.resolve_expression(arg)?;
                 }
+                self.check_variant_arguments(callee, args, &HashMap::new())?;
+                self.infer_generics(callee, args)?;
+            },
+            Expression::Spread(value) => {
+                self.resolve_expression(value)?;
+            },
+            Expression::Lambda { params, body } => {
+                self.begin_scope();
+                for param in params {
+                    self.declare(param.to_string());
+                    self.define(param.to_string());
+                }
+                self.return_types.push(None);
+                let resolved = self.resolve_expression(body);
+                self.return_types.pop();
+                resolved?;
+                self.end_scope();
             },
             Expression::Variable { name, expression_id } => {
                 if let Some(scope) = self.scopes.last() {
-                    if scope.get(name) == Some(&false) {
+                    if scope.get(name.as_str()) == Some(&false) {
                         return Err(format!("Error: Tried to read {} in its own declaration.", name));
                     }
                 }
 
-                self.record_local_depth(*expression_id, name.to_string())?
+                self.record_local_depth(*expression_id, name.as_str())?;
+                // The innermost variable with the name is the one being read
+                if let Some(index) = self.scopes.iter().rposition(|scope| scope.contains_key(name.as_str())) {
+                    if let Some(optional_type) = self.optionals[index].get(name.as_str()) {
+                        self.optional_reads.insert(*expression_id, optional_type.clone());
+                    }
+                    self.unread[index].remove(name.as_str());
+                } else {
+                    self.unreferenced_imports.remove(name.as_str());
+                }
             },
             Expression::If { condition, then_branch, else_branch } => {
-                self.resolve_expression(&condition)?;
-                self.resolve_expression(&then_branch)?;
+                self.resolve_expression(condition)?;
+                self.resolve_expression(then_branch)?;
                 if let Some(else_branch) = else_branch {
-                    self.resolve_expression(&else_branch)?;
+                    self.resolve_expression(else_branch)?;
+                }
+            },
+            Expression::Match { value, arms } => {
+                self.resolve_expression(value)?;
+                for arm in arms {
+                    // Each arm's bindings live in their own scope around its body
+                    self.begin_scope();
+                    if let Pattern::Variant { bindings, .. } = &arm.pattern {
+                        for binding in bindings {
+                            self.declare(binding.to_string());
+                            self.define(binding.to_string());
+                        }
+                    }
+                    self.resolve_expression(&arm.body)?;
+                    self.end_scope();
                 }
             },
             Expression::Loop(LoopType::Infinite { body }) => {
-                self.resolve_expression(&body)?;
+                self.resolve_expression(body)?;
+            },
+            Expression::Loop(LoopType::DoWhile { body, condition }) => {
+                self.resolve_expression(body)?;
+                self.resolve_expression(condition)?;
             },
             Expression::Loop(LoopType::While { condition, body }) => {
-                self.resolve_expression(&condition)?;
-                self.resolve_expression(&body)?;
+                self.resolve_expression(condition)?;
+                self.resolve_expression(body)?;
             },
-            Expression::Loop(LoopType::Iterator { iterator, iterable, body, .. }) => {
-                self.declare(iterator.to_string());
-                self.resolve_expression(&iterable)?;
-                self.define(iterator.to_string());
+            Expression::Loop(LoopType::Iterator { iterator: binding, iterable: value, body, .. } | LoopType::WhileLet { binding, expr: value, body, .. }) => {
+                self.resolve_expression(value)?;
 
-                self.resolve_expression(&body)?;
+                // The loop variable lives in its own scope around the body
+                self.begin_scope();
+                self.declare(binding.to_string());
+                self.define(binding.to_string());
+                self.resolve_expression(body)?;
+                self.end_scope();
             },
             Expression::MemberAccess { object, .. } => {
-                self.resolve_expression(&object)?;
+                self.resolve_expression(object)?;
             },
-            Expression::Array { array_type, size, initial_value } => {
-                todo!()
+            Expression::Index { object, index, .. } => {
+                self.resolve_expression(object)?;
+                self.resolve_expression(index)?;
             },
-            Expression::StructCreation { struct_type, fields } => {
-                todo!()
+            Expression::IndexAssignment { object, index, value, .. } => {
+                self.resolve_expression(value)?;
+                self.resolve_expression(object)?;
+                self.resolve_expression(index)?;
+            },
+            Expression::Array { size, initial_value, .. } => {
+                self.resolve_expression(size)?;
+                let size = self.evaluate_constant(size)?;
+                if size < 0 {
+                    return Err(format!("Error: Array size must be a non-negative integer, got {}.", size));
+                }
+                self.resolve_expression(initial_value)?;
+            },
+            Expression::StructCreation { fields, .. } => {
+                for (_, value) in fields {
+                    self.resolve_expression(value)?;
+                }
+            },
+            Expression::Cast { value, target } => {
+                self.resolve_expression(value)?;
+                self.resolve_type(target)?;
             },
         }
         Ok(())
@@ -153,31 +618,816 @@ impl<'a> Resolver<'a> {
                 if let Some(value) = value {
                     self.resolve_expression(value)?;
                 }
+                if let Some(Some((name, return_type))) = self.return_types.last() {
+                    match value {
+                        Some(value) => {
+                            check_return_value(name, return_type, value)?;
+                            self.check_unwrapped(value, return_type)?;
+                        },
+                        None if !matches!(return_type, Type::Nil | Type::Optional(_)) => {
+                            return Err(format!("Error: Function {} returns {}, but this return has no value.", name, return_type));
+                        },
+                        None => {}
+                    }
+                }
             },
-            Statement::VariableDeclaration { name, variable_type, value, .. } => {
-                self.declare(name.to_string());
-                self.resolve_expression(value)?;
-                self.define(name.to_string());
+            Statement::VariableDeclaration { name, variable_type, value, mutability, expression_id } => {
+                if let Err(message) = self.resolve_variable_declaration(*mutability, name, variable_type, value) {
+                    // Reading the variable later shouldn't be reported as well
+                    self.define(name.clone());
+                    self.locate_error(Some(*expression_id));
+                    return Err(message);
+                }
+                // A leading underscore marks a variable that's meant to go unread
+                if !name.starts_with('_') {
+                    if let Some(unread) = self.unread.last_mut() {
+                        unread.insert(name.clone(), *expression_id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves a `let` or `const`, whether it's in a block or at the top level of the program.
+    fn resolve_variable_declaration(&mut self, mutability: VariableMutability, name: &str, variable_type: &Type, value: &Expression) -> Result<(), String> {
+        self.declare(name.to_string());
+        self.resolve_expression(value)?;
+        let resolved_type = self.resolve_type(variable_type)?;
+        self.check_unwrapped(value, &resolved_type)?;
+        self.define(name.to_string());
+        self.record_optional(name, &resolved_type);
+
+        if mutability == VariableMutability::Immutable {
+            if let Ok(constant) = self.evaluate_constant(value) {
+                match self.constants.last_mut() {
+                    Some(constants) => { constants.insert(name.to_string(), constant); },
+                    None => { self.global_constants.insert(name.to_string(), constant); }
+                }
+            }
+        } else if self.scopes.is_empty() {
+            self.global_constants.remove(name); // A global variable shadows any constant with the same name
+        }
 
-                self.resolve_type(variable_type);
+        // A generic function returning its type parameter, like `identity(300)`, returns what the parameter was inferred from
+        let mut checked_value = value;
+        if let Expression::FunctionCall { callee, args, .. } = value {
+            if let Some(Type::Identifier { name: generic, .. }) = self.generic_function(callee).map(|function| &function.return_type) {
+                checked_value = self.infer_generics(callee, args)?.get(generic).copied().unwrap_or(value);
+            }
+        }
+
+        if let (Some(literal), Some((min, max))) = (integer_literal(checked_value), resolved_type.integer_range()) {
+            if !(min..=max).contains(&literal) {
+                return Err(format!("Error: {} doesn't fit in {} {}, which holds {} to {}.", literal, variable_type, name, min, max));
+            }
+        }
+
+        // A generic enum's type parameters are only known from the type it's declared as, like `Option<i32>`
+        if let Type::Identifier { name: enum_name, generics } = &resolved_type {
+            if let Some((params, _)) = self.enums.get(enum_name) {
+                if generics.len() != params.len() {
+                    return Err(format!("Error: Enum {} expects {} generic arguments but got {}.", enum_name, params.len(), generics.len()));
+                }
+                if let Expression::FunctionCall { callee, args, .. } = value {
+                    let arguments = params.iter().cloned().zip(generics.iter().cloned()).collect();
+                    self.check_variant_arguments(callee, args, &arguments)?;
+                }
             }
         }
         Ok(())
     }
 
-    fn record_local_depth(&mut self, expression_id: ExpressionId, name: String) -> Result<(), String> {
-        for (i, scope) in self.scopes.iter().rev().enumerate() {
-            if scope.contains_key(&name) {
-                self.interpreter.resolve(expression_id, i);
-                return Ok(());
+    /// Remembers that a variable in the innermost scope has an optional type, if it does.
+    fn record_optional(&mut self, name: &str, variable_type: &Type) {
+        if let (Type::Optional(_), Some(optionals)) = (variable_type, self.optionals.last_mut()) {
+            optionals.insert(name.to_string(), variable_type.clone());
+        }
+    }
+
+    /// Checks that a variable with an optional type isn't used directly where a type that isn't optional is expected,
+    /// since it could be nil. It has to be unwrapped with `as` first.
+    fn check_unwrapped(&self, value: &Expression, expected: &Type) -> Result<(), String> {
+        let Expression::Variable { name, expression_id } = value else {
+            return Ok(());
+        };
+        if matches!(expected, Type::Optional(_)) {
+            return Ok(());
+        }
+        match self.optional_reads.get(expression_id) {
+            Some(optional_type) => Err(format!(
+                "Error: {} has type {}, so it could be nil and has to be unwrapped with `as {}` before it's used as {}.",
+                name, optional_type, expected, expected
+            )),
+            None => Ok(())
+        }
+    }
+
+    /// Returns the generic function a call is to, if it's to one that isn't shadowed by a local variable.
+    fn generic_function(&self, callee: &Expression) -> Option<&GenericFunction> {
+        let Expression::Variable { name, .. } = callee else {
+            return None;
+        };
+        if self.scopes.iter().any(|scope| scope.contains_key(name.as_str())) {
+            return None;
+        }
+        self.generic_functions.get(name.as_str())
+    }
+
+    /// Works out the type arguments of a call to a generic function from its literal arguments, returning the argument
+    /// each one was inferred from, like `T` from `5` in `identity(5)`. Arguments that aren't literals aren't known until
+    /// the program runs, so they're skipped. It's an error for two literals of different types to be given for one parameter.
+    fn infer_generics<'e>(&self, callee: &Expression, args: &'e [Expression]) -> Result<HashMap<String, &'e Expression>, String> {
+        let mut inferred: HashMap<String, &Expression> = HashMap::new();
+        let (Expression::Variable { name, .. }, Some(function)) = (callee, self.generic_function(callee)) else {
+            return Ok(inferred);
+        };
+
+        // Arguments after a spread could line up with any parameter
+        for (param_type, arg) in function.param_types.iter().zip(args.iter().take_while(|arg| !matches!(arg, Expression::Spread(_)))) {
+            let Type::Identifier { name: generic, generics } = param_type else {
+                continue;
+            };
+            if !generics.is_empty() || !function.generic_args.contains(generic) {
+                continue;
+            }
+            let Some(value) = to_value(arg) else {
+                continue;
+            };
+            match inferred.get(generic).and_then(|previous| to_value(previous)) {
+                Some(previous) if previous.type_name() != value.type_name() => {
+                    return Err(format!("Error: Function {} can't infer {} as both {} and {}.", name, generic, previous.type_name(), value.type_name()));
+                },
+                Some(_) => {},
+                None => {
+                    inferred.insert(generic.clone(), arg);
+                }
+            }
+        }
+        Ok(inferred)
+    }
+
+    /// If a call constructs an enum variant, like `Option.Some(5)`, checks that the literal values it's given
+    /// have the types the variant expects. `generics` gives the enum's type parameters, if they're known.
+    fn check_variant_arguments(&self, callee: &Expression, args: &[Expression], generics: &HashMap<String, Type>) -> Result<(), String> {
+        let Expression::MemberAccess { object, member, .. } = callee else {
+            return Ok(());
+        };
+        let Expression::Variable { name, .. } = object.as_ref() else {
+            return Ok(());
+        };
+        // Local variables shadow enums
+        if self.scopes.iter().any(|scope| scope.contains_key(name.as_str())) {
+            return Ok(());
+        }
+        let Some(variant) = self.enums.get(name.as_str()).and_then(|(_, variants)| variants.iter().find(|variant| &variant.name == member)) else {
+            return Ok(());
+        };
+
+        for (index, (arg, field)) in args.iter().take_while(|arg| !matches!(arg, Expression::Spread(_))).zip(&variant.fields).enumerate() {
+            let expected = self.resolve_type(&substitute_generics(field, generics))?;
+            if let Some(value) = to_value(arg) {
+                if literal_fits(&value, &expected) == Some(false) {
+                    return Err(format!("Error: {}.{} expects {} for argument {}, but got a value of type {}.", name, member, expected, index + 1, value.type_name()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn record_local_depth(&mut self, expression_id: ExpressionId, name: &str) -> Result<(), String> {
+        let depth = self.scopes.iter().rev().position(|scope| scope.contains_key(name));
+        if let Some(depth) = depth {
+            self.interpreter.resolve(expression_id, depth);
+        }
+        let span = self.interpreter.span(expression_id);
+        self.resolutions.push(Resolution { expression_id, name: name.to_string(), depth, span });
+        Ok(())
+    }
+
+    /// Evaluates an integer expression that has to be known before the program runs, like an array size.
+    /// Constant expressions are integer literals, `const` variables initialized with constant expressions,
+    /// calls to `const func`s with constant arguments, and arithmetic on them.
+    fn evaluate_constant(&self, expression: &Expression) -> Result<i64, String> {
+        self.evaluate_constant_in(expression, None, 0)
+    }
+
+    /// Evaluates a constant expression. Inside a const function, `locals` holds its parameters and variables,
+    /// and `depth` is how many const function calls deep it is.
+    fn evaluate_constant_in(&self, expression: &Expression, locals: Option<&HashMap<String, i64>>, depth: usize) -> Result<i64, String> {
+        match expression {
+            Expression::IntegerLiteral(value) | Expression::TypedIntegerLiteral(value, _) => Ok(*value),
+            Expression::NumberLiteral(value) => Err(format!("Error: Array size must be a non-negative integer, got {}.", value)),
+            Expression::Variable { name, .. } => {
+                let value = match locals {
+                    // A const function's body can only see its own variables and top-level constants
+                    Some(locals) => locals.get(name.as_str()).or_else(|| self.global_constants.get(name.as_str())),
+                    // The innermost scope declaring the name decides whether it's a constant
+                    None => match self.scopes.iter().rposition(|scope| scope.contains_key(name.as_str())) {
+                        Some(scope) => self.constants[scope].get(name.as_str()),
+                        None => self.global_constants.get(name.as_str())
+                    }
+                };
+                match value {
+                    Some(value) => Ok(*value),
+                    None => Err(format!("Error: Array size must be a constant, but {} isn't a const with a constant value.", name))
+                }
+            },
+            Expression::UnaryOperation { operator: UnaryOperator::Negate, operand, .. } => {
+                self.evaluate_constant_in(operand, locals, depth)?.checked_neg().ok_or_else(|| "Error: Integer overflow in constant expression.".to_string())
+            },
+            Expression::BinaryOperation { left, operator, right, .. } => {
+                let (left, right) = (self.evaluate_constant_in(left, locals, depth)?, self.evaluate_constant_in(right, locals, depth)?);
+                let value = match operator {
+                    BinaryOperator::Add => left.checked_add(right),
+                    BinaryOperator::Subtract => left.checked_sub(right),
+                    BinaryOperator::Multiply => left.checked_mul(right),
+                    _ => return Err(format!("Error: The {} operator can't be used in a constant expression.", operator))
+                };
+                value.ok_or_else(|| "Error: Integer overflow in constant expression.".to_string())
+            },
+            Expression::FunctionCall { callee, args, .. } => {
+                let Expression::Variable { name, .. } = callee.as_ref() else {
+                    return Err("Error: Side effects not allowed in constant context.".to_string());
+                };
+                // A local variable shadows a const function with the same name
+                let shadowed = locals.is_some_and(|locals| locals.contains_key(name.as_str())) ||
+                    (locals.is_none() && self.scopes.iter().any(|scope| scope.contains_key(name.as_str())));
+                match self.const_functions.get(name.as_str()) {
+                    Some((params, body)) if !shadowed => {
+                        let args = args.iter().map(|arg| self.evaluate_constant_in(arg, locals, depth)).collect::<Result<Vec<_>, _>>()?;
+                        self.evaluate_const_call(name.as_str(), params, body, args, depth + 1)
+                    },
+                    // Other functions are only run when the program is, since they could do IO
+                    _ => Err("Error: Side effects not allowed in constant context.".to_string())
+                }
+            },
+            // Constants are worked out without running anything, so assignments can't be allowed
+            Expression::Assignment { .. } | Expression::IndexAssignment { .. } => {
+                Err("Error: Side effects not allowed in constant context.".to_string())
+            },
+            _ => Err("Error: Array size must be a constant expression, like an integer literal or a const.".to_string())
+        }
+    }
+
+    /// Runs a const function with constant arguments. Its body can declare variables and return a value, with every
+    /// expression in it constant.
+    fn evaluate_const_call(&self, name: &str, params: &[FunctionParameter], body: &Expression, args: Vec<i64>, depth: usize) -> Result<i64, String> {
+        if depth > MAX_CONST_CALL_DEPTH {
+            return Err(format!("Error: Const function calls nested more than {} deep while calling {}.", MAX_CONST_CALL_DEPTH, name));
+        }
+        if args.len() != params.len() {
+            return Err(format!("Error: Function {} expects {} arguments but got {}.", name, params.len(), args.len()));
+        }
+
+        let mut locals: HashMap<String, i64> = params.iter().map(|param| param.name.clone()).zip(args).collect();
+        let Expression::Block(statements) = body else {
+            return self.evaluate_constant_in(body, Some(&locals), depth);
+        };
+        for statement in statements {
+            match statement {
+                Statement::VariableDeclaration { name, value, .. } => {
+                    let value = self.evaluate_constant_in(value, Some(&locals), depth)?;
+                    locals.insert(name.clone(), value);
+                },
+                Statement::Return(Some(value)) | Statement::Expression { expression: value, result: true } => {
+                    return self.evaluate_constant_in(value, Some(&locals), depth);
+                },
+                _ => return Err(format!("Error: Const function {} can only declare variables and return a value when it's called in a constant context.", name))
             }
         }
+        Err(format!("Error: Const function {} doesn't return a value.", name))
+    }
+
+    /// Returns a type with every alias in it replaced by the type it stands for.
+    pub fn resolve_type(&self, ty: &Type) -> Result<Type, String> {
+        self.expand_type(ty, &mut Vec::new())
+    }
+
+    /// Expands the aliases in a type. `expanding` holds the aliases currently being expanded, to detect cycles.
+    fn expand_type(&self, ty: &Type, expanding: &mut Vec<String>) -> Result<Type, String> {
+        let expanded = match ty {
+            Type::Identifier { name, generics } => {
+                let generics = generics.iter().map(|generic| self.expand_type(generic, expanding)).collect::<Result<Vec<_>, _>>()?;
+                let Some((params, alias)) = self.type_aliases.get(name) else {
+                    return Ok(Type::Identifier { name: name.clone(), generics });
+                };
+
+                if let Some(start) = expanding.iter().position(|expanding_name| expanding_name == name) {
+                    let cycle: Vec<&str> = expanding[start..].iter().chain([name]).map(|name| name.as_str()).collect();
+                    return Err(format!("Error: Type alias {} refers to itself: {}.", name, cycle.join(" -> ")));
+                }
+                if generics.len() != params.len() {
+                    return Err(format!("Error: Type alias {} expects {} generic arguments but got {}.", name, params.len(), generics.len()));
+                }
+
+                let substituted = substitute_generics(alias, &params.iter().cloned().zip(generics).collect());
+                expanding.push(name.clone());
+                let expanded = self.expand_type(&substituted, expanding);
+                expanding.pop();
+                return expanded;
+            },
+            Type::Function { params, return_type } => Type::Function {
+                params: params.iter().map(|param| self.expand_type(param, expanding)).collect::<Result<_, _>>()?,
+                return_type: Box::new(self.expand_type(return_type, expanding)?)
+            },
+            Type::Array(element) => Type::Array(Box::new(self.expand_type(element, expanding)?)),
+            Type::Optional(inner) => Type::Optional(Box::new(self.expand_type(inner, expanding)?)),
+            Type::Tuple(elements) => Type::Tuple(elements.iter().map(|element| self.expand_type(element, expanding)).collect::<Result<_, _>>()?),
+            ty => ty.clone()
+        };
+        Ok(expanded)
+    }
+}
+
+/// Replaces generic parameters in a type with the types they were given.
+pub(super) fn substitute_generics(ty: &Type, arguments: &HashMap<String, Type>) -> Type {
+    match ty {
+        Type::Identifier { name, generics } if generics.is_empty() && arguments.contains_key(name) => arguments[name].clone(),
+        Type::Identifier { name, generics } => Type::Identifier {
+            name: name.clone(),
+            generics: generics.iter().map(|generic| substitute_generics(generic, arguments)).collect()
+        },
+        Type::Function { params, return_type } => Type::Function {
+            params: params.iter().map(|param| substitute_generics(param, arguments)).collect(),
+            return_type: Box::new(substitute_generics(return_type, arguments))
+        },
+        Type::Array(element) => Type::Array(Box::new(substitute_generics(element, arguments))),
+        Type::Optional(inner) => Type::Optional(Box::new(substitute_generics(inner, arguments))),
+        Type::Tuple(elements) => Type::Tuple(elements.iter().map(|element| substitute_generics(element, arguments)).collect()),
+        ty => ty.clone()
+    }
+}
+
+/// Checks that a value a function returns has the function's return type, if it's a literal.
+fn check_return_value(name: &str, return_type: &Type, value: &Expression) -> Result<(), String> {
+    if let Some(value) = to_value(value) {
+        if literal_fits(&value, return_type) == Some(false) {
+            return Err(format!("Error: Function {} returns {}, but returns a value of type {}.", name, return_type, value.type_name()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether running an expression always ends in a result expression or a `return`, rather than falling off the end.
+fn always_returns(expression: &Expression) -> bool {
+    match expression {
+        Expression::Block(statements) => statements.iter().any(|statement| match statement {
+            Statement::Return(_) | Statement::Expression { result: true, .. } => true,
+            Statement::Expression { expression, result: false } => always_returns(expression),
+            _ => false
+        }),
+        Expression::If { then_branch, else_branch: Some(else_branch), .. } => always_returns(then_branch) && always_returns(else_branch),
+        Expression::Match { arms, .. } => arms.iter().all(|arm| always_returns(&arm.body)),
+        // A `break` out of an infinite loop isn't noticed, so these are assumed to run until they return
+        Expression::Loop(LoopType::Infinite { .. }) => true,
+        _ => false
+    }
+}
+
+/// Returns whether a literal's value can be stored as a type, or None if the type isn't one a literal can be checked against,
+/// like a struct or an unsubstituted generic parameter.
+fn literal_fits(value: &Value, expected: &Type) -> Option<bool> {
+    let is_float = matches!(expected, Type::F32 | Type::F64);
+    let is_string = matches!(expected, Type::Identifier { name, generics } if name == "string" && generics.is_empty());
+    if expected.integer_range().is_none() && !is_float && !is_string && !matches!(expected, Type::Boolean | Type::Character) {
+        return None;
+    }
+    Some(match value {
+        Value::Integer(_) => expected.integer_range().is_some() || is_float,
+        Value::Float(_) => is_float,
+        Value::String(_) => is_string,
+        Value::Boolean(_) => matches!(expected, Type::Boolean),
+        Value::Char(_) => matches!(expected, Type::Character),
+        _ => true
+    })
+}
+
+/// Returns the value of an integer literal, including a negated one like `-128`.
+fn integer_literal(expression: &Expression) -> Option<i64> {
+    match expression {
+        Expression::IntegerLiteral(value) | Expression::TypedIntegerLiteral(value, _) => Some(*value),
+        Expression::UnaryOperation { operator: UnaryOperator::Negate, operand, .. } => match operand.as_ref() {
+            Expression::IntegerLiteral(value) | Expression::TypedIntegerLiteral(value, _) => value.checked_neg(),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// Checks that an integer literal with a type suffix fits in that type.
+/// The value is widened so that negating `i64::MIN`'s magnitude can't overflow.
+fn check_typed_literal(value: i128, literal_type: &Type) -> Result<(), String> {
+    let (min, max) = literal_type.integer_range().unwrap_or((i64::MIN, i64::MAX));
+    if (min as i128..=max as i128).contains(&value) {
         Ok(())
+    } else {
+        Err(format!("Error: {}{} doesn't fit in {}, which holds {} to {}.", value, literal_type, literal_type, min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::value::Value, parser::Parser, tokenizer::Tokenizer};
+
+    macro_rules! resolve {
+        ($input:expr) => {
+            {
+                let mut tokenizer = Tokenizer::new($input.to_string());
+                let tokens = tokenizer.tokenize().unwrap();
+                let mut parser = Parser::new(&tokens);
+                let program = parser.parse_program().unwrap();
+                let mut interpreter = Interpreter::new();
+                let result = Resolver::new(&mut interpreter).resolve_program(&program);
+                result
+            }
+        };
+    }
+
+    #[test]
+    fn test_field_method_collision() {
+        let result = resolve!(r#"
+            struct Point {
+                x: f64;
+                length: f64;
+
+                func length() -> f64 {
+                    0.0
+                }
+            }
+        "#);
+
+        assert_eq!(result, Err("Error: Field length conflicts with method length in struct Point.".to_string()));
+    }
+
+    #[test]
+    fn test_every_error() {
+        let source = "func main() -> i32 {\n    let b: u8 = 256;\n    let c: i8 = -129;\n    b + c\n}\nfunc other() -> u8 {\n    let d: u8 = 300;\n    d\n}";
+        let tokens = Tokenizer::new(source.to_string()).tokenize().unwrap().clone();
+        let mut parser = Parser::new(&tokens);
+        let program = parser.parse_program().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.add_spans(parser.take_spans());
+
+        // Resolving carries on after each error, and reading a variable whose declaration failed isn't another one
+        let mut resolver = Resolver::new(&mut interpreter);
+        assert_eq!(resolver.resolve_program(&program), Err("Error: 256 doesn't fit in u8 b, which holds 0 to 255.".to_string()));
+        let errors: Vec<(&str, Option<Span>)> = resolver.errors().iter().map(|error| (error.message.as_str(), error.span)).collect();
+        assert_eq!(errors, vec![
+            ("Error: 256 doesn't fit in u8 b, which holds 0 to 255.", Some(Span { line: 2, column: 9 })),
+            ("Error: -129 doesn't fit in i8 c, which holds -128 to 127.", Some(Span { line: 3, column: 9 })),
+            ("Error: 300 doesn't fit in u8 d, which holds 0 to 255.", Some(Span { line: 7, column: 9 }))
+        ]);
+    }
+
+    #[test]
+    fn test_type_aliases() {
+        let mut tokenizer = Tokenizer::new(r#"
+            func main() -> Id {
+                let pair: Pair<Id> = (1, 2);
+                let ids: [Id] = [1];
+                5
+            }
+            type Id = u64;
+            type Pair<T> = (T, T);
+        "#.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        assert_eq!(resolver.resolve_program(&program), Ok(()));
+
+        let id = Type::Identifier { name: "Id".to_string(), generics: vec![] };
+        assert_eq!(resolver.resolve_type(&id), Ok(Type::U64));
+        assert_eq!(
+            resolver.resolve_type(&Type::Array(Box::new(Type::Identifier { name: "Pair".to_string(), generics: vec![id] }))),
+            Ok(Type::Array(Box::new(Type::Tuple(vec![Type::U64, Type::U64]))))
+        );
+    }
+
+    #[test]
+    fn test_type_alias_errors() {
+        assert_eq!(
+            resolve!("type A = B; type B = [A];"),
+            Err("Error: Type alias A refers to itself: A -> B -> A.".to_string())
+        );
+        assert_eq!(
+            resolve!("type List<T> = (T, List<T>);"),
+            Err("Error: Type alias List refers to itself: List -> List.".to_string())
+        );
+        assert_eq!(
+            resolve!("type Pair<T> = (T, T); func main() -> i32 { let p: Pair<i32, i32> = (1, 2); 0 }"),
+            Err("Error: Type alias Pair expects 1 generic arguments but got 2.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolutions() {
+        let mut tokenizer = Tokenizer::new("func main() -> i32 { let x: i32 = 1; { x = x + 1; }; print(x); x }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve_program(&program).unwrap();
+
+        let resolutions: Vec<String> = resolver.resolutions().iter().map(|resolution| resolution.to_string()).collect();
+        assert_eq!(resolutions, ["#4 x: depth 1", "#2 x: depth 1", "#6 print: global", "#8 x: depth 0", "#9 x: depth 0"]);
+    }
+
+    #[test]
+    fn test_resolution_positions() {
+        let source = "func main() -> i32 {\n    let x: i32 = 1;\n    {\n        let x: i32 = 2;\n        { print(x); };\n    };\n    x\n}";
+        let mut tokenizer = Tokenizer::new(source.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.add_spans(parser.take_spans());
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve_program(&program).unwrap();
+
+        // The printed `x` is the shadowing one a scope out, and the one after the block is the function's
+        let resolutions: Vec<String> = resolver.resolutions().iter().map(|resolution| resolution.to_string()).collect();
+        assert_eq!(resolutions, ["#3 print: global at 5:11", "#5 x: depth 1 at 5:17", "#6 x: depth 0 at 7:5"]);
     }
 
-    fn resolve_type(&self, ty: &Type) {
-        match ty {
-            _ => todo!()
+    #[test]
+    fn test_unread_variables() {
+        let warnings = |source: &str| {
+            let mut tokenizer = Tokenizer::new(source.to_string());
+            let tokens = tokenizer.tokenize().unwrap();
+            let mut parser = Parser::new(tokens);
+            let program = parser.parse_program().unwrap();
+            let mut interpreter = Interpreter::new();
+            interpreter.add_spans(parser.take_spans());
+            let mut resolver = Resolver::new(&mut interpreter);
+            resolver.resolve_program(&program).unwrap();
+            resolver.take_warnings().into_iter().map(|warning| warning.to_string()).collect::<Vec<_>>()
         };
+
+        assert_eq!(warnings("func main() -> i32 {\n    let unused: i32 = 1;\n    0\n}"), [
+            "Warning: Variable unused at 2:9 is never read; start its name with an underscore if that's intended"
+        ]);
+        assert_eq!(warnings("func main() -> i32 {\n    let _unused: i32 = 1;\n    0\n}"), Vec::<String>::new());
+        // Assigning isn't reading, but compound assignment and reads from closures are
+        assert_eq!(warnings("func main() -> i32 {\n    let x: i32 = 1;\n    x = 2;\n    0\n}"), [
+            "Warning: Variable x at 2:9 is never read; start its name with an underscore if that's intended"
+        ]);
+        assert_eq!(warnings("func main() -> i32 { let x: i32 = 1; x += 2; let y: i32 = 3; let f: func() -> i32 = { || y }; f() }"), Vec::<String>::new());
+        // A variable shadowed before it's read can never be read
+        assert_eq!(warnings("func main() -> i32 {\n    let x: i32 = 1;\n    let x: i32 = 2;\n    x\n}"), [
+            "Warning: Variable x at 2:9 is never read; start its name with an underscore if that's intended"
+        ]);
+
+        // An import is referenced by the last part of its path, unless a local variable has the same name
+        assert_eq!(warnings("import util.math;\nfunc main() -> i32 { 0 }"), ["Warning: Import util.math at 1:8 is never referenced"]);
+        assert_eq!(warnings("import util.math;\nfunc main() -> i32 { math.square(2) }"), Vec::<String>::new());
+        assert_eq!(warnings("import math;\nfunc main() -> i32 { let math: i32 = 1; math }"), ["Warning: Import math at 1:8 is never referenced"]);
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let mut tokenizer = Tokenizer::new("func main() -> i32 { let x: i32 = 1; let y: i32 = { let x: i32 = 10; x += 5; x }; x * 100 + y }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve_program(&program).unwrap();
+
+        // The read and the write of the shadowing `x` have their own IDs, and both resolve to the inner block
+        let resolutions: Vec<String> = resolver.resolutions().iter().map(|resolution| resolution.to_string()).collect();
+        assert_eq!(resolutions, ["#6 x: depth 0", "#4 x: depth 0", "#7 x: depth 0", "#8 x: depth 0", "#11 y: depth 0"]);
+
+        interpreter.load(&program).unwrap();
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::Integer(115)));
+    }
+
+    #[test]
+    fn test_constant_array_sizes() {
+        assert_eq!(resolve!("func main() -> i32 { const N: i32 = 3; let a: [i32] = [i32, N] { 0 }; 0 }"), Ok(()));
+        assert_eq!(resolve!("func main() -> i32 { const N: i32 = 3; const M: i32 = N * 2 - 1; let a: [i32] = [i32, M + 1] { 0 }; 0 }"), Ok(()));
+        assert_eq!(
+            resolve!("func main() -> i32 { let n: i32 = 3; let a: [i32] = [i32, n] { 0 }; 0 }"),
+            Err("Error: Array size must be a constant, but n isn't a const with a constant value.".to_string())
+        );
+        assert_eq!(
+            resolve!("func main() -> i32 { let a: [i32] = [i32, \"3\"] { 0 }; 0 }"),
+            Err("Error: Array size must be a constant expression, like an integer literal or a const.".to_string())
+        );
+        // A variable shadowing a constant isn't constant
+        assert_eq!(
+            resolve!("func main() -> i32 { const N: i32 = 3; { let N: i32 = 4; let a: [i32] = [i32, N] { 0 }; }; 0 }"),
+            Err("Error: Array size must be a constant, but N isn't a const with a constant value.".to_string())
+        );
+        assert_eq!(
+            resolve!("func main() -> i32 { const N: i32 = 0 - 1; let a: [i32] = [i32, N] { 0 }; 0 }"),
+            Err("Error: Array size must be a non-negative integer, got -1.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_constant_side_effects() {
+        assert_eq!(resolve!("func main() -> i32 { const N: i32 = 2 * 3; let a: [i32] = [i32, N - 1] { 0 }; 0 }"), Ok(()));
+        for source in [
+            "func main() -> i32 { let a: [i32] = [i32, print(3)] { 0 }; 0 }",
+            "func size() -> i32 { 3 } func main() -> i32 { let a: [i32] = [i32, 1 + size()] { 0 }; 0 }",
+            "func main() -> i32 { let n: i32 = 0; let a: [i32] = [i32, n = 3] { 0 }; 0 }"
+        ] {
+            assert_eq!(resolve!(source), Err("Error: Side effects not allowed in constant context.".to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_const_functions() {
+        assert_eq!(resolve!("const func square(n: i32) -> i32 { n * n } func main() -> i32 { let a: [i32] = [i32, square(4)] { 0 }; 0 }"), Ok(()));
+        // Const functions can call each other and declare their own variables, and global constants can use them
+        assert_eq!(resolve!("
+            const func square(n: i32) -> i32 { n * n }
+            const func area(w: i32, h: i32) -> i32 { let total: i32 = w * h; return total + square(1); }
+            const SIZE: i32 = area(2, 3) - 1;
+            func main() -> i32 { let a: [i32] = [i32, SIZE] { 0 }; 0 }
+        "), Ok(()));
+        assert_eq!(
+            resolve!("const func square(n: i32) -> i32 { n * n } func main() -> i32 { let a: [i32] = [i32, square(0 - 2) - 5] { 0 }; 0 }"),
+            Err("Error: Array size must be a non-negative integer, got -1.".to_string())
+        );
+        // Everything a const function calls has to be const too
+        assert_eq!(
+            resolve!("func size() -> i32 { 3 } const func twice() -> i32 { size() * 2 } func main() -> i32 { let a: [i32] = [i32, twice()] { 0 }; 0 }"),
+            Err("Error: Side effects not allowed in constant context.".to_string())
+        );
+        assert_eq!(
+            resolve!("const func forever(n: i32) -> i32 { forever(n) } func main() -> i32 { let a: [i32] = [i32, forever(1)] { 0 }; 0 }"),
+            Err("Error: Const function calls nested more than 64 deep while calling forever.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_literal_ranges() {
+        for source in [
+            "func main() -> i32 { let b: u8 = 255; 0 }",
+            "func main() -> i32 { let b: u8 = 0; 0 }",
+            "func main() -> i32 { let b: i8 = -128; let c: i8 = 127; 0 }",
+            "func main() -> i32 { let b: u64 = 9223372036854775807; 0 }",
+            "func main() -> i32 { let b: i8 = -128i8; 0 }",
+            // Only literals are checked before running
+            "func main() -> i32 { let b: u8 = 255 + 1; 0 }"
+        ] {
+            assert_eq!(resolve!(source), Ok(()), "{}", source);
+        }
+        for (source, message) in [
+            ("func main() -> i32 { let b: u8 = 256; 0 }", "Error: 256 doesn't fit in u8 b, which holds 0 to 255."),
+            ("func main() -> i32 { let b: u16 = -1; 0 }", "Error: -1 doesn't fit in u16 b, which holds 0 to 65535."),
+            ("func main() -> i32 { let b: i8 = -129; 0 }", "Error: -129 doesn't fit in i8 b, which holds -128 to 127."),
+            ("type Byte = u8; func main() -> i32 { const B: Byte = 300; 0 }", "Error: 300 doesn't fit in Byte B, which holds 0 to 255."),
+            // A suffix is checked against its own type, and against the variable's
+            ("func main() -> i32 { let b: i32 = 256u8; 0 }", "Error: 256u8 doesn't fit in u8, which holds 0 to 255."),
+            ("func main() -> i32 { let b: i32 = -129i8; 0 }", "Error: -129i8 doesn't fit in i8, which holds -128 to 127."),
+            ("func main() -> i32 { let b: u8 = 300u16; 0 }", "Error: 300 doesn't fit in u8 b, which holds 0 to 255.")
+        ] {
+            assert_eq!(resolve!(source), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_return_types() {
+        for source in [
+            "func f(x: i32) -> i32 { if (x < 0) { return 0; }; x }",
+            "func f(x: i32) -> i32 { if (x < 0) { return 0; } else { return 1; }; }",
+            "func f() -> i32 { loop { return 1; }; }",
+            "func f() -> nil { return; }",
+            "func f() -> nil { print(1); }",
+            // Lambdas don't declare a return type
+            "func apply(f: func() -> nil) -> nil { f(); } func g() -> i32 { apply() { return; }; 1 }"
+        ] {
+            assert_eq!(resolve!(source), Ok(()), "{}", source);
+        }
+        for (source, message) in [
+            ("func f() -> i32 { return; }", "Error: Function f returns i32, but this return has no value."),
+            ("func f() -> i32 { return \"one\"; }", "Error: Function f returns i32, but returns a value of type string."),
+            ("func f() -> bool { 1.5 }", "Error: Function f returns bool, but returns a value of type float."),
+            ("func f(x: i32) -> i32 { if (x < 0) { return 0; }; }", "Error: Function f returns i32, but can reach the end of its body without a value."),
+            ("func f() -> i32 { print(1); }", "Error: Function f returns i32, but can reach the end of its body without a value.")
+        ] {
+            assert_eq!(resolve!(source), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_optional_unwrapping() {
+        for source in [
+            "func f(x: i32?) -> i32 { let y: i32 = x as i32; y }",
+            "func f(x: i32?) -> i32? { let y: i32? = x; y }",
+            "func f(x: i32?) -> i32 { x as i32 }",
+            // A variable that shadows an optional one isn't optional itself
+            "func f(x: i32?) -> i32 { let x: i32 = 1; x }"
+        ] {
+            assert_eq!(resolve!(source), Ok(()), "{}", source);
+        }
+        for (source, message) in [
+            ("func f(x: i32?) -> i32 { let y: i32 = x; y }", "Error: x has type i32?, so it could be nil and has to be unwrapped with `as i32` before it's used as i32."),
+            ("func f(x: i32?) -> i32 { x }", "Error: x has type i32?, so it could be nil and has to be unwrapped with `as i32` before it's used as i32."),
+            ("func f(x: bool?) -> bool { if (true) { return x; }; false }", "Error: x has type bool?, so it could be nil and has to be unwrapped with `as bool` before it's used as bool.")
+        ] {
+            assert_eq!(resolve!(source), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_generic_enums() {
+        let enums = "enum Option<T> { Some(T), None } enum Shape { Circle(f64) }";
+        for source in [
+            "func main() -> i32 { let x: Option<i32> = Option.Some(5); 0 }",
+            "func main() -> i32 { let x: Option<string> = Option.Some(\"five\"); 0 }",
+            // Without a declared type, the payload could be anything
+            "func main() -> i32 { let x: i32 = size([Option.Some(\"five\")]); 0 }",
+            "func main() -> i32 { let x: Option<f64> = Option.None; 0 }"
+        ] {
+            assert_eq!(resolve!(format!("{} {}", enums, source)), Ok(()), "{}", source);
+        }
+        for (source, message) in [
+            ("func main() -> i32 { let x: Option<i32> = Option.Some(\"five\"); 0 }", "Error: Option.Some expects i32 for argument 1, but got a value of type string."),
+            ("func main() -> i32 { let x: Option<bool> = Option.Some(1.5); 0 }", "Error: Option.Some expects bool for argument 1, but got a value of type float."),
+            ("func main() -> i32 { let x: Option<i32, i32> = Option.None; 0 }", "Error: Enum Option expects 1 generic arguments but got 2."),
+            ("func main() -> i32 { Shape.Circle('c'); 0 }", "Error: Shape.Circle expects f64 for argument 1, but got a value of type char.")
+        ] {
+            assert_eq!(resolve!(format!("{} {}", enums, source)), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_fields_and_methods() {
+        let result = resolve!(r#"
+            struct Point {
+                x: f64;
+                y: f64;
+
+                func length() -> f64 {
+                    0.0
+                }
+            }
+        "#);
+
+        assert_eq!(result, Ok(()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_interfaces() {
+        let interface = "interface Shape { func area() -> f64; func name() -> string; }";
+        for source in [
+            "struct Square : Shape { side: f64; func area() -> f64 { self.side * self.side } func name() -> string { \"square\" } }",
+            // Aliases are expanded before comparing signatures
+            "type Length = f64; struct Square : Shape { func area() -> Length { 1.0 } func name() -> string { \"square\" } }",
+            "func describe(shape: Shape) -> f64 { shape.area() }"
+        ] {
+            assert_eq!(resolve!(format!("{} {}", interface, source)), Ok(()), "{}", source);
+        }
+        for (source, message) in [
+            ("struct Square : Shape { func area() -> f64 { 1.0 } }", "Error: Struct Square is missing method name required by interface Shape."),
+            ("struct Square : Shape { func area(scale: f64) -> f64 { scale } func name() -> string { \"square\" } }",
+                "Error: Method Square.area has type func(f64) -> f64, but interface Shape requires func() -> f64."),
+            ("struct Square : Drawable { }", "Error: Struct Square implements unknown interface Drawable.")
+        ] {
+            assert_eq!(resolve!(format!("{} {}", interface, source)), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        let functions = "func identity<T>(x: T) -> T { x } func same<T>(a: T, b: T) -> bool { a == b }";
+        for source in [
+            "func main() -> i32 { let x: i32 = identity(300); 0 }",
+            "func main() -> i32 { let x: bool = same(1, 2); let y: bool = same(\"a\", \"b\"); 0 }",
+            // Only literals are checked, since variables could hold anything
+            "func main() -> i32 { let x: f64 = 1.5; let y: bool = same(1, x); 0 }",
+            // A local variable shadows the generic function
+            "func main() -> i32 { let identity: func(i32) -> i32 = { |x| x }; let x: u8 = identity(300); 0 }"
+        ] {
+            assert_eq!(resolve!(format!("{} {}", functions, source)), Ok(()), "{}", source);
+        }
+        for (source, message) in [
+            ("func main() -> i32 { let x: u8 = identity(300); 0 }", "Error: 300 doesn't fit in u8 x, which holds 0 to 255."),
+            ("func main() -> i32 { let x: bool = same(1, \"one\"); 0 }", "Error: Function same can't infer T as both int and string."),
+            ("func main() -> i32 { same('a', true); 0 }", "Error: Function same can't infer T as both char and bool.")
+        ] {
+            assert_eq!(resolve!(format!("{} {}", functions, source)), Err(message.to_string()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        // The parser limits how deeply expressions nest, but one built some other way fails to resolve
+        // instead of overflowing the stack. The thread's stack only has to be large enough to drop it.
+        let thread = std::thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(|| {
+            let mut expression = Expression::IntegerLiteral(1);
+            for _ in 0..20_000 {
+                expression = Expression::UnaryOperation { operator: UnaryOperator::Negate, operand: Box::new(expression), expression_id: ExpressionId(0) };
+            }
+            let mut interpreter = Interpreter::new();
+            let mut resolver = Resolver::new(&mut interpreter);
+            assert_eq!(resolver.resolve_snippet(&expression), Err("Error: Expression nesting too deep to resolve.".to_string()));
+        }).unwrap();
+        thread.join().unwrap();
+    }
+}
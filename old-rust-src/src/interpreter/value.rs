@@ -1,16 +1,118 @@
-#[derive(Debug, Clone)]
+use std::{cell::RefCell, collections::{HashMap, HashSet}, hash::{BuildHasher, DefaultHasher, Hash, Hasher}, rc::Rc};
+
+use crate::parser::ast::{Expression, Type};
+
+use super::{environment::Environment, Interpreter, InterpreterResult};
+
+#[derive(Debug, Clone, Default)]
 pub enum Value {
-    Number(f64),
+    Integer(i64),
+    Float(f64),
     String(String),
     Boolean(bool),
     Char(char),
-    Vector(Vec<Value>),
+    /// Arrays and maps are shared by reference, so assigning to an element is visible through every copy.
+    Vector(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<MapEntries>>),
+    Tuple(Vec<Value>),
+    Struct(Rc<StructValue>),
+    /// A struct used as an interface it implements, like a `Point` passed to a `Printable` parameter
+    Interface(Rc<InterfaceValue>),
+    Enum(Rc<EnumValue>),
+    Function(Rc<Function>),
+    NativeFunction(NativeFunction),
+    #[default]
     Nil
 }
 
-impl Default for Value {
-    fn default() -> Self {
-        Value::Nil
+/// The entries of a map value.
+pub type MapEntries = HashMap<Value, Value, MapHasher>;
+
+/// Hashes the keys of maps. Unlike the standard library's default, the seed is fixed rather than random,
+/// so a map with the same entries iterates in the same order in every run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MapHasher {
+    seed: u64
+}
+
+impl MapHasher {
+    pub fn new(seed: u64) -> Self {
+        MapHasher { seed }
+    }
+}
+
+impl BuildHasher for MapHasher {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.seed);
+        hasher
+    }
+}
+
+/// An instance of a struct, with its fields in the order the struct declares them.
+#[derive(Debug, PartialEq)]
+pub struct StructValue {
+    pub name: String,
+    pub fields: Vec<(String, Value)>
+}
+
+impl StructValue {
+    /// Returns the value of a field, or None if the struct doesn't have it.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, value)| value)
+    }
+}
+
+/// A struct instance seen through an interface, with the struct's implementation of each of the interface's methods.
+/// Calling a method looks it up here, so the same call runs whichever struct's method the value was created with.
+#[derive(Debug)]
+pub struct InterfaceValue {
+    pub interface: String,
+    pub instance: Rc<StructValue>,
+    pub vtable: HashMap<String, Rc<Function>>
+}
+
+/// A variant of an enum, with the values it carries.
+#[derive(Debug, PartialEq, Hash)]
+pub struct EnumValue {
+    pub enum_name: String,
+    pub variant: String,
+    pub values: Vec<Value>
+}
+
+/// A user-defined function or lambda, along with the environment it closes over.
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    /// The declared type of each parameter, which lambdas don't have
+    pub param_types: Option<Vec<Type>>,
+    /// The declared return type, which lambdas don't have
+    pub return_type: Option<Type>,
+    pub body: Rc<Expression>,
+    pub closure: Rc<RefCell<Environment>>
+}
+
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The closure isn't printed since it may contain this function
+        write!(f, "<func {}>", self.name)
+    }
+}
+
+/// A function implemented in Rust and made available to programs as a global.
+#[derive(Clone, Copy)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    /// How many arguments it takes, or None if it takes any number and checks them itself
+    pub arity: Option<usize>,
+    pub function: fn(&mut Interpreter, Vec<Value>) -> InterpreterResult
+}
+
+impl std::fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native func {}>", self.name)
     }
 }
 
@@ -22,16 +124,39 @@ impl std::fmt::Display for Value {
             } else {
                 write!(f, "false")
             },
-            Value::Number(n) => write!(f, "{}", n),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Float(n) => write!(f, "{}", n),
             Value::String(s) => write!(f, "{}", s),
             Value::Char(c) => write!(f, "{}", c),
             Value::Vector(vec) => {
-                write!(f, "[")?;
-                for value in vec {
-                    write!(f, "{}, ", value)?;
+                let values: Vec<String> = vec.borrow().iter().map(|value| value.to_string()).collect();
+                write!(f, "[{}]", values.join(", "))
+            },
+            Value::Map(map) => {
+                // Sorted so the output doesn't depend on hashing order
+                let mut entries: Vec<String> = map.borrow().iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                entries.sort();
+                write!(f, "{{{}}}", entries.join(", "))
+            },
+            Value::Tuple(values) => {
+                let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                write!(f, "({})", values.join(", "))
+            },
+            Value::Struct(instance) => {
+                let fields: Vec<String> = instance.fields.iter().map(|(name, value)| format!("{}: {}", name, value)).collect();
+                write!(f, "{} {{ {} }}", instance.name, fields.join(", "))
+            },
+            Value::Interface(value) => Value::Struct(value.instance.clone()).fmt(f),
+            Value::Enum(instance) => {
+                write!(f, "{}.{}", instance.enum_name, instance.variant)?;
+                if !instance.values.is_empty() {
+                    let values: Vec<String> = instance.values.iter().map(|value| value.to_string()).collect();
+                    write!(f, "({})", values.join(", "))?;
                 }
-                write!(f, "]")
+                Ok(())
             },
+            Value::Function(function) => write!(f, "<func {}>", function.name),
+            Value::NativeFunction(function) => write!(f, "<native func {}>", function.name),
             Value::Nil => write!(f, "nil"),
         }
     }
@@ -40,12 +165,250 @@ impl std::fmt::Display for Value {
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::Integer(l), Value::Integer(r)) => l == r,
+            (Value::Float(l), Value::Float(r)) => l == r,
             (Value::String(l), Value::String(r)) => l == r,
             (Value::Boolean(l), Value::Boolean(r)) => l == r,
             (Value::Char(l), Value::Char(r)) => l == r,
-            (Value::Vector(l), Value::Vector(r)) => l == r,
-            _ => false,
+            // Nested values are compared recursively; the same shared value is always equal to itself
+            (Value::Vector(l), Value::Vector(r)) => Rc::ptr_eq(l, r) || l == r,
+            (Value::Map(l), Value::Map(r)) => Rc::ptr_eq(l, r) || l == r,
+            (Value::Tuple(l), Value::Tuple(r)) => l == r,
+            // Structs are equal if they're the same struct and every field is equal
+            (Value::Struct(l), Value::Struct(r)) => Rc::ptr_eq(l, r) || l == r,
+            (Value::Interface(l), Value::Interface(r)) => l.interface == r.interface && (Rc::ptr_eq(&l.instance, &r.instance) || l.instance == r.instance),
+            (Value::Enum(l), Value::Enum(r)) => Rc::ptr_eq(l, r) || l == r,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::NativeFunction(l), Value::NativeFunction(r)) => l.name == r.name,
+            (Value::Nil, Value::Nil) => true,
+            // Values of different variants are never equal. Every variant is listed so adding one
+            // fails to compile until its equality is defined above.
+            (Value::Integer(_) | Value::Float(_) | Value::String(_) | Value::Boolean(_) | Value::Char(_) |
+                Value::Vector(_) | Value::Map(_) | Value::Tuple(_) | Value::Struct(_) | Value::Interface(_) | Value::Enum(_) |
+                Value::Function(_) | Value::NativeFunction(_) | Value::Nil, _) => false
+        }
+    }
+}
+
+/// Values compare equal only to values of the same variant, so this holds for every value that passes
+/// `check_hashable`. NaN is the only value that isn't equal to itself, and it's rejected as a key.
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Integer(n) => n.hash(state),
+            // 0.0 and -0.0 are equal, so they need to hash the same
+            Value::Float(n) => if *n == 0.0 { 0u64.hash(state) } else { n.to_bits().hash(state) },
+            Value::String(s) => s.hash(state),
+            Value::Boolean(b) => b.hash(state),
+            Value::Char(c) => c.hash(state),
+            Value::Vector(vec) => vec.borrow().hash(state),
+            Value::Tuple(vec) => vec.hash(state),
+            Value::Struct(instance) => {
+                instance.name.hash(state);
+                for (name, value) in &instance.fields {
+                    name.hash(state);
+                    value.hash(state);
+                }
+            },
+            Value::Interface(value) => {
+                value.interface.hash(state);
+                Value::Struct(value.instance.clone()).hash(state);
+            },
+            Value::Enum(instance) => instance.hash(state),
+            // Maps are never hashable, but they still need a hash consistent with equality
+            Value::Map(map) => map.borrow().len().hash(state),
+            Value::Function(function) => Rc::as_ptr(function).hash(state),
+            Value::NativeFunction(function) => function.name.hash(state),
+            Value::Nil => {}
+        }
+    }
+}
+
+/// The runtime type names returned by `Value::type_name`.
+const TYPE_NAMES: &[&str] = &["int", "float", "string", "bool", "char", "vector", "map", "tuple", "struct", "enum", "function", "nil"];
+
+impl Value {
+    pub fn vector(values: Vec<Value>) -> Value {
+        Value::Vector(Rc::new(RefCell::new(values)))
+    }
+
+    /// Creates a map hashed with the default seed.
+    pub fn map(entries: impl IntoIterator<Item = (Value, Value)>) -> Value {
+        Value::Map(Rc::new(RefCell::new(entries.into_iter().collect())))
+    }
+
+    /// Returns the name of this value's type at runtime.
+    /// Native functions are functions like any other, so they share a name with user-defined ones.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "int",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "bool",
+            Value::Char(_) => "char",
+            Value::Vector(_) => "vector",
+            Value::Map(_) => "map",
+            Value::Tuple(_) => "tuple",
+            Value::Struct(_) | Value::Interface(_) => "struct",
+            Value::Enum(_) => "enum",
+            Value::Function(_) | Value::NativeFunction(_) => "function",
+            Value::Nil => "nil"
+        }
+    }
+
+    /// Returns whether this value's type has the given name, or an error if no type has that name.
+    pub fn is_type(&self, name: &str) -> Result<bool, String> {
+        if !TYPE_NAMES.contains(&name) {
+            return Err(format!("Unknown type name: {}", name));
+        }
+        Ok(self.type_name() == name)
+    }
+
+    /// Returns roughly how many bytes the value allocated when it was created. Only its own allocation is counted,
+    /// not the values it holds, since those were counted when they were created.
+    pub fn allocated_size(&self) -> usize {
+        let value_size = std::mem::size_of::<Value>();
+        match self {
+            Value::String(s) => s.len(),
+            Value::Vector(values) => values.borrow().len() * value_size,
+            Value::Map(map) => map.borrow().len() * value_size * 2,
+            Value::Tuple(values) => values.len() * value_size,
+            Value::Struct(instance) => instance.fields.iter().map(|(name, _)| name.len() + value_size).sum(),
+            Value::Enum(instance) => instance.values.len() * value_size,
+            _ => 0
+        }
+    }
+
+    /// Returns roughly how many bytes this value and every value it refers to have allocated, including the variables
+    /// of the environments its closures keep alive. Arrays, maps, and other shared values are only counted the first
+    /// time they're found, using `seen` to remember them.
+    pub fn reachable_size(&self, seen: &mut HashSet<*const ()>) -> usize {
+        match self {
+            Value::Vector(values) => {
+                if !seen.insert(Rc::as_ptr(values) as *const ()) {
+                    return 0;
+                }
+                self.allocated_size() + values.borrow().iter().map(|value| value.reachable_size(seen)).sum::<usize>()
+            },
+            Value::Map(map) => {
+                if !seen.insert(Rc::as_ptr(map) as *const ()) {
+                    return 0;
+                }
+                self.allocated_size() + map.borrow().iter().map(|(key, value)| key.reachable_size(seen) + value.reachable_size(seen)).sum::<usize>()
+            },
+            Value::Tuple(values) => self.allocated_size() + values.iter().map(|value| value.reachable_size(seen)).sum::<usize>(),
+            Value::Struct(instance) => {
+                if !seen.insert(Rc::as_ptr(instance) as *const ()) {
+                    return 0;
+                }
+                self.allocated_size() + instance.fields.iter().map(|(_, value)| value.reachable_size(seen)).sum::<usize>()
+            },
+            Value::Interface(value) => Value::Struct(value.instance.clone()).reachable_size(seen),
+            Value::Enum(instance) => {
+                if !seen.insert(Rc::as_ptr(instance) as *const ()) {
+                    return 0;
+                }
+                self.allocated_size() + instance.values.iter().map(|value| value.reachable_size(seen)).sum::<usize>()
+            },
+            Value::Function(function) => {
+                if !seen.insert(Rc::as_ptr(function) as *const ()) {
+                    return 0;
+                }
+                Environment::reachable_size(&function.closure, seen)
+            },
+            _ => self.allocated_size()
+        }
+    }
+
+    /// Returns an error if this value can't be used as a map key.
+    /// Functions have no meaningful equality, and NaN isn't equal to itself. Arrays and maps can be changed
+    /// after they're used as a key, which would change their hash while they're in the map.
+    pub fn check_hashable(&self) -> Result<(), String> {
+        match self {
+            Value::Function(_) | Value::NativeFunction(_) => Err(format!("Cannot use a function as a map key: {}", self)),
+            Value::Float(n) if n.is_nan() => Err("Cannot use NaN as a map key".to_string()),
+            Value::Map(_) => Err("Cannot use a map as a map key".to_string()),
+            Value::Vector(_) => Err("Cannot use an array as a map key; use a tuple instead".to_string()),
+            Value::Tuple(vec) => vec.iter().try_for_each(Value::check_hashable),
+            Value::Struct(instance) => instance.fields.iter().try_for_each(|(_, value)| value.check_hashable()),
+            Value::Interface(value) => Value::Struct(value.instance.clone()).check_hashable(),
+            Value::Enum(instance) => instance.values.iter().try_for_each(Value::check_hashable),
+            _ => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::ast::Expression;
+
+    use super::*;
+
+    #[test]
+    fn test_hashable_keys() {
+        let mut map = HashMap::new();
+        for key in [Value::Integer(1), Value::String("one".to_string()), Value::Char('1'), Value::Float(0.0)] {
+            assert_eq!(key.check_hashable(), Ok(()));
+            map.insert(key, Value::Boolean(true));
         }
+
+        assert_eq!(map.get(&Value::Integer(1)), Some(&Value::Boolean(true)));
+        assert_eq!(map.get(&Value::String("one".to_string())), Some(&Value::Boolean(true)));
+        assert_eq!(map.get(&Value::Char('1')), Some(&Value::Boolean(true)));
+        assert_eq!(map.get(&Value::Float(-0.0)), Some(&Value::Boolean(true)));
+        // Integers and floats are different keys
+        assert_eq!(map.get(&Value::Float(1.0)), None);
+    }
+
+    #[test]
+    fn test_nested_equality() {
+        let nested = |inner: i64| Value::vector(vec![Value::Integer(1), Value::vector(vec![Value::Integer(inner)])]);
+        assert_eq!(nested(2), nested(2));
+        assert_ne!(nested(2), nested(3));
+
+        let point = |x: i64| Value::Struct(Rc::new(StructValue {
+            name: "Point".to_string(),
+            fields: vec![("x".to_string(), Value::Integer(x)), ("y".to_string(), Value::Tuple(vec![Value::Nil]))]
+        }));
+        assert_eq!(point(1), point(1));
+        assert_ne!(point(1), point(2));
+        let other = Value::Struct(Rc::new(StructValue { name: "Other".to_string(), fields: vec![("x".to_string(), Value::Integer(1))] }));
+        assert_ne!(point(1), other);
+
+        // Different variants are unequal, even when they look alike
+        assert_ne!(Value::Integer(1), Value::Float(1.0));
+        assert_ne!(Value::Char('1'), Value::String("1".to_string()));
+        assert_ne!(Value::vector(vec![Value::Integer(1)]), Value::Tuple(vec![Value::Integer(1)]));
+        assert_ne!(Value::Nil, Value::Boolean(false));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_type_names() {
+        assert_eq!(Value::Integer(1).type_name(), "int");
+        assert_eq!(Value::Nil.type_name(), "nil");
+        assert_eq!(Value::String("1".to_string()).is_type("string"), Ok(true));
+        assert_eq!(Value::String("1".to_string()).is_type("int"), Ok(false));
+        assert_eq!(Value::Integer(1).is_type("i32"), Err("Unknown type name: i32".to_string()));
+    }
+
+    #[test]
+    fn test_unhashable_keys() {
+        let function = Value::Function(Rc::new(Function {
+            name: "f".to_string(),
+            params: vec![],
+            param_types: None,
+            return_type: None,
+            body: Rc::new(Expression::Block(vec![])),
+            closure: Rc::new(RefCell::new(Environment::default()))
+        }));
+
+        assert_eq!(function.check_hashable(), Err("Cannot use a function as a map key: <func f>".to_string()));
+        assert!(Value::Float(f64::NAN).check_hashable().is_err());
+        assert!(Value::Tuple(vec![Value::Integer(1), function]).check_hashable().is_err());
+        assert!(Value::map(HashMap::new()).check_hashable().is_err());
+        assert!(Value::Tuple(vec![Value::vector(vec![])]).check_hashable().is_err());
+    }
+}
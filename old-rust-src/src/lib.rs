@@ -0,0 +1,17 @@
+pub mod debugger;
+pub mod diagnostic;
+pub mod stack;
+pub mod symbol;
+pub mod tokenizer;
+pub mod parser;
+pub mod interpreter;
+pub mod bytecode;
+pub mod cache;
+pub mod lint;
+pub mod optimizer;
+pub mod prelude;
+pub mod test_runner;
+pub mod watch;
+
+/// The file extension of Saffron source files.
+pub const SOURCE_EXTENSION: &str = "saffron";
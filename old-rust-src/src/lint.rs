@@ -0,0 +1,91 @@
+use crate::parser::{ast::{Expression, Program, Statement}, visitor::{walk_expression, Visitor}};
+
+/// A warning about code that's valid but probably doesn't do what was intended.
+#[derive(Debug, PartialEq)]
+pub struct LintWarning {
+    pub message: String
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Warning: {}", self.message)
+    }
+}
+
+/// Checks a program for suspicious code, returning a warning for each problem found.
+pub fn lint_program(program: &Program) -> Vec<LintWarning> {
+    let mut linter = Linter { warnings: Vec::new() };
+    linter.visit_program(program);
+    linter.warnings
+}
+
+struct Linter {
+    warnings: Vec<LintWarning>
+}
+
+impl Linter {
+    fn warn(&mut self, message: &str) {
+        self.warnings.push(LintWarning { message: message.to_string() });
+    }
+}
+
+impl Visitor for Linter {
+    fn visit_expression(&mut self, expression: &Expression) {
+        // A loop without a trailing semicolon becomes the block's value, but loops don't produce values,
+        // so this is almost always a statement-style loop that's missing its semicolon
+        if let Expression::Block(statements) = expression {
+            if let Some(Statement::Expression { expression, result: true }) = statements.last() {
+                if let Expression::Loop(_) = expression.as_ref() {
+                    self.warn("Loop used as a value; did you mean to end the block here?");
+                }
+            }
+        }
+        walk_expression(self, expression);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, tokenizer::Tokenizer};
+
+    macro_rules! lint {
+        ($input:expr) => {
+            {
+                let mut tokenizer = Tokenizer::new($input.to_string());
+                let tokens = tokenizer.tokenize().unwrap();
+                let mut parser = Parser::new(&tokens);
+                lint_program(&parser.parse_program().unwrap())
+            }
+        };
+    }
+
+    #[test]
+    fn test_loop_as_block_value() {
+        let warnings = lint!(r#"
+            func main() -> nil {
+                let i: i32 = 0;
+                loop (i < 10) {
+                    i += 1;
+                }
+            }
+        "#);
+
+        assert_eq!(warnings, vec![LintWarning { message: "Loop used as a value; did you mean to end the block here?".to_string() }]);
+    }
+
+    #[test]
+    fn test_loop_as_statement() {
+        let warnings = lint!(r#"
+            func main() -> i32 {
+                let i: i32 = 0;
+                loop (i < 10) {
+                    i += 1;
+                };
+                if (i > 5) { i } else { 0 }
+            }
+        "#);
+
+        assert_eq!(warnings, vec![]);
+    }
+}
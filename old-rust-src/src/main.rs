@@ -1,19 +1,17 @@
-#![feature(let_chains)]
+use std::{collections::HashMap, fs, io::{self, BufReader}, path::{Path, PathBuf}, time::{Duration, Instant}};
 
-use std::fs;
-
-use clap::{command, Parser};
-use parser::ast_printer::ASTPrinter;
-
-mod tokenizer;
-mod parser;
-mod interpreter;
+use clap::{Parser, Subcommand};
+use saffron::{bytecode::{self, vm::VM}, cache::ResolutionCache, debugger::Debugger, diagnostic::Diagnostic, interpreter::{self, resolver::Resolver}, lint, optimizer, parser::{self, ast::{Declaration, ExpressionId, Program, Span}, ast_printer::ASTPrinter, docs::Doc}, prelude, test_runner, tokenizer, watch::{self, FileWatcher}};
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
-    /// The input file
-    input: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The input files, whose declarations are combined into one program
+    #[arg(required = true)]
+    input: Vec<String>,
 
     /// If we should print the AST and exit
     #[arg(long)]
@@ -22,49 +20,360 @@ struct Args {
     /// If we should print the tokens and exit
     #[arg(long)]
     only_print_tokens: bool,
+
+    /// If we should print the documented declarations and exit
+    #[arg(long)]
+    emit_docs: bool,
+
+    /// If we should print how long each phase took to stderr
+    #[arg(long)]
+    time: bool,
+
+    /// If we should fold constant expressions before running the program
+    #[arg(long)]
+    optimize: bool,
+
+    /// If we should re-run the program whenever it or its imports change
+    #[arg(long)]
+    watch: bool,
+
+    /// If we should print the program compiled to bytecode and exit
+    #[arg(long)]
+    emit_bytecode: bool,
+
+    /// If we should run the program with the bytecode VM instead of the tree-walking interpreter
+    #[arg(long)]
+    vm: bool,
+
+    /// If integers that don't fit in a variable's type should be clamped to it instead of being an error
+    #[arg(long)]
+    saturate: bool,
+
+    /// How many bytes the program's values can have allocated at once before it fails with a runtime error
+    #[arg(long, value_name = "BYTES")]
+    max_memory: Option<usize>,
+
+    /// The seed maps hash their keys with, to check that the program doesn't depend on the order maps iterate in
+    #[arg(long, value_name = "SEED")]
+    map_seed: Option<u64>,
+
+    /// If we should run the program without the prelude, which declares things like `Option` and `Result` in Saffron
+    #[arg(long)]
+    no_prelude: bool,
+
+    /// If we should only check the program for errors, printing them and exiting with a nonzero status if there are any
+    #[arg(long)]
+    check: bool,
+
+    /// If we should print how the resolver resolved each variable and exit
+    #[arg(long)]
+    dump_resolved: bool,
+
+    /// If we should resolve the program from scratch instead of reusing the results from an earlier run
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Lines to pause at before running, to step through the program and inspect its variables
+    #[arg(long, value_name = "LINES", value_delimiter = ',')]
+    debug: Option<Vec<usize>>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Runs every .saffron file in a directory and reports how many passed.
+    /// Files with functions named `test...` run each of them; other files run `main`.
+    Test {
+        /// The directory containing the test files
+        dir: String
+    }
+}
+
+/// Prints the duration of a phase to stderr if timing is enabled.
+fn report_time(args: &Args, phase: &str, duration: Duration, detail: Option<String>) {
+    if !args.time {
+        return;
+    }
+
+    match detail {
+        Some(detail) => eprintln!("{}: {:?} ({})", phase, duration, detail),
+        None => eprintln!("{}: {:?}", phase, duration)
+    }
 }
 
+/// Parsing and interpreting both recurse once per level of nesting, which quickly outgrows the main thread's stack
+/// (especially in debug builds), so everything runs on a thread with a much larger one.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+/// How much of that stack parsing, resolving, and interpreting can each use, leaving room for everything else
+const MAX_STACK: usize = STACK_SIZE - 16 * 1024 * 1024;
+
 fn main() {
     let args: Args = Args::parse();
 
-    // Read the input file
-    let input: String = fs::read_to_string(args.input).expect("Failed to read input file.");
+    let thread = std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || run(args))
+        .expect("Failed to start the interpreter thread.");
+    if let Err(e) = thread.join() {
+        std::panic::resume_unwind(e);
+    }
+}
 
-    let mut lex: tokenizer::Tokenizer = tokenizer::Tokenizer::new(input);
+fn run(args: Args) {
+    if let Some(Command::Test { dir }) = &args.command {
+        run_tests(dir);
+        return;
+    }
+
+    let input_paths = &args.input;
+    if args.check {
+        if !check_files(&args, input_paths) {
+            std::process::exit(1);
+        }
+    } else if args.watch {
+        run_watching(&args, input_paths);
+    } else {
+        run_files(&args, input_paths);
+    }
+}
 
-    // Split the input into tokens
-    let tokens = match lex.tokenize() {
-        Ok(tokens) => tokens,
+/// Re-runs the program whenever one of its files or imports changes, until the process is stopped.
+fn run_watching(args: &Args, input_paths: &[String]) {
+    let mut watcher = match FileWatcher::new(watch::DEBOUNCE) {
+        Ok(watcher) => watcher,
         Err(e) => {
-            eprintln!("Error: {}", e);
+            eprintln!("Error: Failed to watch files: {}", e);
             return;
         }
     };
-    
-    if args.only_print_tokens {
-        for token in tokens {
-            println!("{:?}", token);
+
+    loop {
+        // Clear the terminal so only the latest run's output is visible
+        print!("\x1B[2J\x1B[H");
+        let sources = run_files(args, input_paths);
+
+        if let Err(e) = watcher.watch(&sources) {
+            eprintln!("Error: Failed to watch files: {}", e);
+            return;
         }
-        return;
+        if !watcher.wait_for_change(None) {
+            return;
+        }
+    }
+}
+
+/// One of the input files of the program being run.
+struct SourceFile {
+    path: String,
+    /// The file's contents, for showing the code errors point at
+    source: String,
+    /// The last expression ID used by this file. Each file's IDs start after the previous file's,
+    /// so an expression's ID says which file it's from.
+    last_id: u32
+}
+
+/// The input files, parsed and combined into one program.
+struct ParsedFiles {
+    files: Vec<SourceFile>,
+    program: Program,
+    spans: HashMap<ExpressionId, Span>,
+    docs: Vec<Doc>,
+    /// The files imported by any of the input files
+    imports: Vec<PathBuf>
+}
+
+/// Reads and tokenizes a file, printing the error if either fails.
+fn tokenize_file(path: &str) -> Option<(String, tokenizer::Tokenizer)> {
+    let input = match fs::read_to_string(path) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Error: Failed to read input file {}: {}", path, e);
+            return None;
+        }
+    };
+
+    let mut lex = tokenizer::Tokenizer::new(input.clone());
+    if let Err(e) = lex.tokenize() {
+        eprintln!("{}", Diagnostic::new(e.to_string(), Some(e.span()), 1).render(&input, path));
+        return None;
     }
+    Some((input, lex))
+}
+
+/// Tokenizes and parses each input file on its own, then combines their declarations into one program,
+/// printing any errors. Expression IDs start after `first_id` so they don't collide with the prelude's.
+fn parse_files(args: &Args, input_paths: &[String], first_id: u32) -> Option<ParsedFiles> {
+    let mut parsed = ParsedFiles {
+        files: Vec::new(),
+        program: Program { declarations: Vec::new() },
+        spans: HashMap::new(),
+        docs: Vec::new(),
+        imports: Vec::new()
+    };
+    let mut last_id = first_id;
+    let mut tokenizing = Duration::ZERO;
+    let mut parsing = Duration::ZERO;
+    let mut token_count = 0;
+    let mut main_files = Vec::new();
+
+    for path in input_paths {
+        let start = Instant::now();
+        let (input, lex) = tokenize_file(path)?;
+        tokenizing += start.elapsed();
+        token_count += lex.tokens().len();
 
-    let mut parser: parser::Parser = parser::Parser::new(&tokens);
-    let program = match parser.parse_program() {
-        Some(program) => program,
-        None => {
+        let start = Instant::now();
+        let mut parser = parser::Parser::new(lex.tokens());
+        parser.set_max_stack(MAX_STACK);
+        parser.set_comments(lex.comments());
+        parser.start_ids_after(last_id);
+        let Some(program) = parser.parse_program() else {
+            for error in parser.errors() {
+                eprintln!("{}", Diagnostic::from_parse_error(error).render(&input, path));
+            }
             eprintln!("Error: Failed to parse the program.");
-            return;
+            return None;
+        };
+        parsing += start.elapsed();
+
+        if program.declarations.iter().any(|declaration| matches!(declaration, Declaration::Function { name, .. } if name == "main")) {
+            main_files.push(path.as_str());
+        }
+        last_id = parser.last_id();
+        parsed.files.push(SourceFile { path: path.clone(), source: input, last_id });
+        parsed.spans.extend(parser.take_spans());
+        parsed.docs.extend(parser.docs().iter().cloned());
+
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+        parsed.imports.extend(watch::import_paths(&program, base));
+        parsed.program.declarations.extend(program.declarations);
+    }
+    report_time(args, "Tokenizing", tokenizing, Some(format!("{} tokens", token_count)));
+    report_time(args, "Parsing", parsing, Some(format!("{} declarations", parsed.program.declarations.len())));
+
+    if main_files.len() > 1 {
+        eprintln!("Error: More than one file declares main: {}.", main_files.join(", "));
+        return None;
+    }
+    if main_files.is_empty() && input_paths.len() > 1 {
+        eprintln!("Error: None of the input files declares main.");
+        return None;
+    }
+    Some(parsed)
+}
+
+/// Runs a program made of one or more files, returning the source files it depends on.
+fn run_files(args: &Args, input_paths: &[String]) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = input_paths.iter().map(PathBuf::from).collect();
+
+    if args.only_print_tokens {
+        for path in input_paths {
+            let Some((_, lex)) = tokenize_file(path) else {
+                return sources;
+            };
+            for token in lex.tokens() {
+                println!("{:?}", token);
+            }
         }
+        return sources;
+    }
+
+    let prelude = (!args.no_prelude).then(prelude::parse);
+    let first_id = prelude.as_ref().map_or(0, |(_, last_id)| *last_id);
+    let Some(ParsedFiles { files, program, spans, docs, imports }) = parse_files(args, input_paths, first_id) else {
+        return sources;
+    };
+
+    if args.emit_docs {
+        for doc in docs {
+            println!("{}", doc);
+        }
+        return sources;
+    }
+
+    sources.extend(imports);
+
+    for warning in lint::lint_program(&program) {
+        eprintln!("{}", warning);
+    }
+
+    let program = if args.optimize {
+        let start = Instant::now();
+        let program = optimizer::optimize_program(program);
+        report_time(args, "Optimizing", start.elapsed(), None);
+        program
+    } else {
+        program
     };
 
     if args.only_print_ast {
         let mut printer = ASTPrinter::new();
         println!("Parsed program:\n{}", printer.print_program(&program));
-        return;
+        return sources;
     }
 
+    if args.emit_bytecode || args.vm {
+        run_bytecode(args, &program);
+        return sources;
+    }
+
+    // The prelude is only added now, so the printed AST and bytecode only show the program itself
+    let program = match prelude {
+        Some((prelude, _)) => prelude::with_prelude(prelude, program),
+        None => program
+    };
+
     let mut interpreter: interpreter::Interpreter = interpreter::Interpreter::new();
-    match interpreter.run(&program) {
+    interpreter.set_max_stack(MAX_STACK);
+    interpreter.set_saturating(args.saturate);
+    interpreter.set_max_memory(args.max_memory);
+    if let Some(seed) = args.map_seed {
+        interpreter.set_map_seed(seed);
+    }
+    // The spans are added first so the resolutions can be dumped with their positions
+    interpreter.add_spans(spans);
+
+    if args.dump_resolved {
+        let mut resolver = Resolver::new(&mut interpreter);
+        if let Err(e) = resolver.resolve_program(&program) {
+            eprintln!("{}", e);
+        }
+        for resolution in resolver.resolutions() {
+            println!("{}", resolution);
+        }
+        return sources;
+    }
+
+    let start = Instant::now();
+    let resolved = match ResolutionCache::default_dir() {
+        Some(dir) if !args.no_cache => ResolutionCache::new(dir).resolve(&mut interpreter, &program, &sources[input_paths.len()..]),
+        _ => {
+            let mut resolver = Resolver::new(&mut interpreter);
+            resolver.resolve_program(&program).map(|_| resolver.take_warnings())
+        }
+    };
+    match resolved {
+        Ok(warnings) => {
+            for warning in warnings {
+                eprintln!("{}", warning);
+            }
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            return sources;
+        }
+    }
+    report_time(args, "Resolving", start.elapsed(), None);
+
+    if let Some(breakpoints) = &args.debug {
+        let input = Box::new(BufReader::new(io::stdin()));
+        interpreter.set_hook(Box::new(Debugger::new(breakpoints.iter().copied(), input, Box::new(io::stdout()))));
+    }
+
+    let start = Instant::now();
+    let result = interpreter.run(&program);
+    report_time(args, "Interpreting", start.elapsed(), None);
+
+    match result {
         Ok(_) => {
             println!("Program executed successfully.");
         },
@@ -80,9 +389,96 @@ fn main() {
                     eprintln!("Error: Program returned ouside of a function: {}", value);
                 },
                 interpreter::InterpreterControl::RuntimeError(msg) => {
-                    eprintln!("Runtime error: {}", msg);
+                    // IDs are handed out in order, so the error is in the first file whose last ID isn't below it
+                    let file = interpreter.error_expression().and_then(|id| files.iter().find(|file| id.0 <= file.last_id));
+                    match (interpreter.error_span(), file) {
+                        (Some(span), Some(file)) => eprintln!("Runtime error: {} | {}:{}", msg, file.path, span),
+                        _ => eprintln!("Runtime error: {}", msg)
+                    }
                 }
             }
         }
     }
+
+    sources
+}
+
+/// Tokenizes, parses, and resolves a program without running it, printing the errors it finds.
+/// Returns whether the program is free of errors.
+fn check_files(args: &Args, input_paths: &[String]) -> bool {
+    let prelude = (!args.no_prelude).then(prelude::parse);
+    let first_id = prelude.as_ref().map_or(0, |(_, last_id)| *last_id);
+    let Some(parsed) = parse_files(args, input_paths, first_id) else {
+        return false;
+    };
+
+    for warning in lint::lint_program(&parsed.program) {
+        eprintln!("{}", warning);
+    }
+    let program = match prelude {
+        Some((prelude, _)) => prelude::with_prelude(prelude, parsed.program),
+        None => parsed.program
+    };
+
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.set_max_stack(MAX_STACK);
+    interpreter.add_spans(parsed.spans);
+    let mut resolver = Resolver::new(&mut interpreter);
+    if resolver.resolve_program(&program).is_err() {
+        for error in resolver.errors() {
+            // IDs are handed out in order, so the error is in the first file whose last ID isn't below it
+            let file = error.expression_id.and_then(|id| parsed.files.iter().find(|file| id.0 <= file.last_id));
+            let message = error.message.strip_prefix("Error: ").unwrap_or(&error.message);
+            let diagnostic = Diagnostic::new(message, file.and(error.span), 1);
+            eprintln!("{}", diagnostic.render(file.map_or("", |file| &file.source), file.map_or("", |file| &file.path)));
+        }
+        return false;
+    }
+    for warning in resolver.take_warnings() {
+        eprintln!("{}", warning);
+    }
+    true
+}
+
+/// Compiles the program to bytecode, then prints it or runs it with the VM.
+fn run_bytecode(args: &Args, program: &Program) {
+    let start = Instant::now();
+    let bytecode = match bytecode::compile_program(program) {
+        Ok(bytecode) => bytecode,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+    report_time(args, "Compiling", start.elapsed(), None);
+
+    if args.emit_bytecode {
+        print!("{}", bytecode);
+        return;
+    }
+
+    let start = Instant::now();
+    let result = VM::new(&bytecode).run();
+    report_time(args, "Running", start.elapsed(), None);
+
+    match result {
+        Ok(_) => println!("Program executed successfully."),
+        Err(interpreter::InterpreterControl::RuntimeError(msg)) => eprintln!("Runtime error: {}", msg),
+        Err(control) => eprintln!("Error: Unexpected control flow: {:?}", control)
+    }
+}
+
+fn run_tests(dir: &str) {
+    match test_runner::run_tests(Path::new(dir), &mut io::stdout()) {
+        Ok(summary) => {
+            println!("\n{}", summary);
+            if summary.failed > 0 {
+                std::process::exit(1);
+            }
+        },
+        Err(e) => {
+            eprintln!("Error: Failed to run the tests in {}: {}", dir, e);
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,240 @@
+use std::rc::Rc;
+
+use crate::{interpreter::{binary_operation, typed_float, unary_operation, value::Value}, parser::ast::{Declaration, Expression, LoopType, MatchArm, Program, Statement, StructElement}};
+
+/// Folds constant subexpressions throughout a program.
+pub fn optimize_program(program: Program) -> Program {
+    Program {
+        declarations: program.declarations.into_iter().map(fold_declaration).collect()
+    }
+}
+
+fn fold_declaration(declaration: Declaration) -> Declaration {
+    match declaration {
+        Declaration::Function { name, params, generic_args, return_type, body, is_const } => Declaration::Function {
+            name,
+            params,
+            generic_args,
+            return_type,
+            body: Rc::new(fold_constants(Rc::unwrap_or_clone(body))),
+            is_const
+        },
+        Declaration::Struct { name, elements, generic_args, interfaces } => Declaration::Struct {
+            name,
+            elements: elements.into_iter().map(|element| match element {
+                StructElement::Declaration(declaration) => StructElement::Declaration(fold_declaration(declaration)),
+                field => field
+            }).collect(),
+            generic_args,
+            interfaces
+        },
+        Declaration::GlobalVariable { mutability, name, variable_type, value } => Declaration::GlobalVariable {
+            mutability,
+            name,
+            variable_type,
+            value: Box::new(fold_constants(*value))
+        },
+        declaration @ (Declaration::Enum { .. } | Declaration::Interface { .. } | Declaration::TypeDeclaration { .. } | Declaration::Import { .. }) => declaration
+    }
+}
+
+fn fold_statement(statement: Statement) -> Statement {
+    match statement {
+        Statement::Declaration(declaration) => Statement::Declaration(fold_declaration(declaration)),
+        Statement::Expression { expression, result } => Statement::Expression { expression: fold_boxed(expression), result },
+        Statement::VariableDeclaration { mutability, name, variable_type, value, expression_id } => Statement::VariableDeclaration {
+            mutability,
+            name,
+            variable_type,
+            value: fold_boxed(value),
+            expression_id
+        },
+        Statement::Return(value) => Statement::Return(value.map(fold_boxed)),
+        statement @ (Statement::Break | Statement::Continue) => statement
+    }
+}
+
+/// Folds a boxed expression in place, reusing its allocation.
+fn fold_boxed(mut expression: Box<Expression>) -> Box<Expression> {
+    let folded = fold_constants(std::mem::replace(&mut *expression, Expression::Block(Vec::new())));
+    *expression = folded;
+    expression
+}
+
+/// Converts a value back into a literal expression, if it has a literal form.
+fn to_literal(value: Value) -> Option<Expression> {
+    match value {
+        Value::Integer(n) => Some(Expression::IntegerLiteral(n)),
+        Value::Float(n) => Some(Expression::NumberLiteral(n)),
+        Value::String(s) => Some(Expression::StringLiteral(s)),
+        Value::Boolean(b) => Some(Expression::BooleanLiteral(b)),
+        Value::Char(c) => Some(Expression::CharLiteral(c)),
+        _ => None
+    }
+}
+
+pub(crate) fn to_value(expression: &Expression) -> Option<Value> {
+    match expression {
+        Expression::IntegerLiteral(n) => Some(Value::Integer(*n)),
+        Expression::NumberLiteral(n) => Some(Value::Float(*n)),
+        Expression::TypedIntegerLiteral(n, _) => Some(Value::Integer(*n)),
+        Expression::TypedNumberLiteral(n, literal_type) => Some(typed_float(*n, literal_type)),
+        Expression::StringLiteral(s) => Some(Value::String(s.clone())),
+        Expression::BooleanLiteral(b) => Some(Value::Boolean(*b)),
+        Expression::CharLiteral(c) => Some(Value::Char(*c)),
+        _ => None
+    }
+}
+
+/// Replaces operations on literals with their result, using the interpreter's own operator semantics.
+/// Anything involving a variable or call is left untouched, as is any operation that would fail at runtime
+/// (like division by zero), so the error is still reported when the program runs.
+pub fn fold_constants(expression: Expression) -> Expression {
+    match expression {
+        Expression::BinaryOperation { left, operator, right, expression_id } => {
+            let left = fold_boxed(left);
+            let right = fold_boxed(right);
+            if let (Some(l), Some(r)) = (to_value(&left), to_value(&right)) {
+                if let Some(literal) = binary_operation(&operator, l, r).ok().and_then(to_literal) {
+                    return literal;
+                }
+            }
+            Expression::BinaryOperation { left, operator, right, expression_id }
+        },
+        Expression::UnaryOperation { operator, operand, expression_id } => {
+            let operand = fold_boxed(operand);
+            if let Some(value) = to_value(&operand) {
+                if let Some(literal) = unary_operation(&operator, value).ok().and_then(to_literal) {
+                    return literal;
+                }
+            }
+            Expression::UnaryOperation { operator, operand, expression_id }
+        },
+
+        Expression::Block(statements) => Expression::Block(statements.into_iter().map(fold_statement).collect()),
+        Expression::ArrayLiteral(elements) => Expression::ArrayLiteral(elements.into_iter().map(fold_constants).collect()),
+        Expression::ArrayComprehension { element, binding, iterable, condition } => Expression::ArrayComprehension {
+            element: fold_boxed(element),
+            binding,
+            iterable: fold_boxed(iterable),
+            condition: condition.map(fold_boxed)
+        },
+        Expression::Range { start, end, inclusive } => Expression::Range { start: fold_boxed(start), end: fold_boxed(end), inclusive },
+        Expression::Tuple(elements) => Expression::Tuple(elements.into_iter().map(fold_constants).collect()),
+        Expression::MapLiteral(entries) => Expression::MapLiteral(
+            entries.into_iter().map(|(key, value)| (fold_constants(key), fold_constants(value))).collect()
+        ),
+        Expression::FunctionCall { callee, args, expression_id } => Expression::FunctionCall {
+            callee: fold_boxed(callee),
+            args: args.into_iter().map(fold_constants).collect(),
+            expression_id
+        },
+        Expression::Spread(value) => Expression::Spread(fold_boxed(value)),
+        Expression::Lambda { params, body } => Expression::Lambda {
+            params,
+            body: Rc::new(fold_constants(Rc::unwrap_or_clone(body)))
+        },
+        Expression::Assignment { name, value, expression_id } => Expression::Assignment { name, value: fold_boxed(value), expression_id },
+        Expression::MemberAccess { object, member, optional } => Expression::MemberAccess { object: fold_boxed(object), member, optional },
+        Expression::Index { object, index, expression_id } => Expression::Index {
+            object: fold_boxed(object),
+            index: fold_boxed(index),
+            expression_id
+        },
+        Expression::IndexAssignment { object, index, operator, value, expression_id } => Expression::IndexAssignment {
+            object: fold_boxed(object),
+            index: fold_boxed(index),
+            operator,
+            value: fold_boxed(value),
+            expression_id
+        },
+        Expression::Array { array_type, size, initial_value } => Expression::Array {
+            array_type,
+            size: fold_boxed(size),
+            initial_value: fold_boxed(initial_value)
+        },
+        Expression::StructCreation { struct_type, fields } => Expression::StructCreation {
+            struct_type,
+            fields: fields.into_iter().map(|(name, value)| (name, fold_boxed(value))).collect()
+        },
+        Expression::Cast { value, target } => Expression::Cast { value: fold_boxed(value), target },
+        Expression::If { condition, then_branch, else_branch } => Expression::If {
+            condition: fold_boxed(condition),
+            then_branch: fold_boxed(then_branch),
+            else_branch: else_branch.map(fold_boxed)
+        },
+        Expression::Match { value, arms } => Expression::Match {
+            value: fold_boxed(value),
+            arms: arms.into_iter().map(|arm| MatchArm { pattern: arm.pattern, body: fold_boxed(arm.body) }).collect()
+        },
+        Expression::Loop(LoopType::While { condition, body }) => Expression::Loop(LoopType::While {
+            condition: fold_boxed(condition),
+            body: fold_boxed(body)
+        }),
+        Expression::Loop(LoopType::Infinite { body }) => Expression::Loop(LoopType::Infinite { body: fold_boxed(body) }),
+        Expression::Loop(LoopType::DoWhile { body, condition }) => Expression::Loop(LoopType::DoWhile {
+            body: fold_boxed(body),
+            condition: fold_boxed(condition)
+        }),
+        Expression::Loop(LoopType::Iterator { mutability, iterator, iterable, body }) => Expression::Loop(LoopType::Iterator {
+            mutability,
+            iterator,
+            iterable: fold_boxed(iterable),
+            body: fold_boxed(body)
+        }),
+        Expression::Loop(LoopType::WhileLet { mutability, binding, expr, body }) => Expression::Loop(LoopType::WhileLet {
+            mutability,
+            binding,
+            expr: fold_boxed(expr),
+            body: fold_boxed(body)
+        }),
+
+        expression @ (Expression::IntegerLiteral(_) | Expression::NumberLiteral(_) | Expression::TypedIntegerLiteral(..) |
+            Expression::TypedNumberLiteral(..) | Expression::StringLiteral(_) |
+            Expression::CharLiteral(_) | Expression::BooleanLiteral(_) | Expression::Variable { .. }) => expression
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::{ast::{BinaryOperator, ExpressionId}, Parser}, symbol::Symbol, tokenizer::Tokenizer};
+
+    macro_rules! fold {
+        ($input:expr) => {
+            {
+                let mut tokenizer = Tokenizer::new($input.to_string());
+                let tokens = tokenizer.tokenize().unwrap();
+                let mut parser = Parser::new(&tokens);
+                fold_constants(parser.parse_snippet().unwrap())
+            }
+        };
+    }
+
+    #[test]
+    fn test_fold_literals() {
+        assert_eq!(fold!("1 + 2 * 3"), Expression::IntegerLiteral(7));
+        assert_eq!(fold!("1.5 * 2"), Expression::NumberLiteral(3.0));
+        assert_eq!(fold!("true && false"), Expression::BooleanLiteral(false));
+        assert_eq!(fold!("!(1 < 2)"), Expression::BooleanLiteral(false));
+        assert_eq!(fold!(r#""a" + "b""#), Expression::StringLiteral("ab".to_string()));
+    }
+
+    #[test]
+    fn test_fold_leaves_variables_and_errors() {
+        assert_eq!(fold!("x + 2 * 3"), Expression::BinaryOperation {
+            left: Box::new(Expression::Variable { name: Symbol::intern("x"), expression_id: ExpressionId(1) }),
+            operator: BinaryOperator::Add,
+            right: Box::new(Expression::IntegerLiteral(6)),
+            expression_id: ExpressionId(2)
+        });
+
+        // Division by zero is left for the interpreter to report
+        assert_eq!(fold!("1 / (1 - 1)"), Expression::BinaryOperation {
+            left: Box::new(Expression::IntegerLiteral(1)),
+            operator: BinaryOperator::Divide,
+            right: Box::new(Expression::IntegerLiteral(0)),
+            expression_id: ExpressionId(1)
+        });
+    }
+}
@@ -1,42 +1,112 @@
+use std::{hash::{Hash, Hasher}, rc::Rc};
+
+use crate::symbol::Symbol;
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct ExpressionId(pub u32);
 
-#[derive(Debug, PartialEq)]
+/// A position in the source code, used to point errors at the expression that caused them.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Block(Vec<Statement>),
 
+    IntegerLiteral(i64),
     NumberLiteral(f64),
+    /// An integer with a type suffix, like `42u8`
+    TypedIntegerLiteral(i64, Type),
+    /// A float with a type suffix, like `3.14f32` or `2f64`
+    TypedNumberLiteral(f64, Type),
     StringLiteral(String),
     CharLiteral(char),
     Variable {
-        name: String,
+        name: Symbol,
         expression_id: ExpressionId
     },
     BooleanLiteral(bool),
 
+    /// An array of values, written as `[a, b, c]`.
+    ArrayLiteral(Vec<Expression>),
+    /// An array built from the elements of another, written as `[x * 2 for x in values]`.
+    /// With a condition, like `[x for x in values if x > 0]`, only the elements it's true for are kept.
+    ArrayComprehension {
+        element: Box<Expression>,
+        binding: String,
+        iterable: Box<Expression>,
+        condition: Option<Box<Expression>>
+    },
+    /// The integers or characters from `start` up to `end`, written as `1..5`, or `'a'..='z'` to include the end.
+    /// It's an array of every value in the range, so it can be iterated over like any other.
+    Range {
+        start: Box<Expression>,
+        end: Box<Expression>,
+        inclusive: bool
+    },
+    /// A fixed-size group of values, written as `(a, b)`.
+    Tuple(Vec<Expression>),
+    /// A map from keys to values, written as `{ "a": 1, "b": 2 }`.
+    MapLiteral(Vec<(Expression, Expression)>),
+
     FunctionCall {
         callee: Box<Expression>,
-        args: Vec<Expression>
+        args: Vec<Expression>,
+        expression_id: ExpressionId
+    },
+    /// An array passed to a function as separate arguments, written as `f(...values)`. Only allowed in argument lists.
+    Spread(Box<Expression>),
+    /// An anonymous function, written as `{ |a, b| body }`.
+    Lambda {
+        params: Vec<String>,
+        body: Rc<Expression>
     },
     
     BinaryOperation {
         left: Box<Expression>,
         operator: BinaryOperator,
-        right: Box<Expression>
+        right: Box<Expression>,
+        expression_id: ExpressionId
     },
     UnaryOperation {
         operator: UnaryOperator,
-        operand: Box<Expression>
+        operand: Box<Expression>,
+        expression_id: ExpressionId
     },
     
     Assignment {
-        name: String,
+        name: Symbol,
         value: Box<Expression>,
         expression_id: ExpressionId
     },
+    /// `object.member`, or `object?.member` when `optional` is set, which is nil instead of an error if the object is nil.
     MemberAccess {
         object: Box<Expression>,
-        member: String
+        member: String,
+        optional: bool
+    },
+    Index {
+        object: Box<Expression>,
+        index: Box<Expression>,
+        expression_id: ExpressionId
+    },
+    /// `object[index] = value`, or `object[index] += value` and friends when there's an operator.
+    /// The object and index are only evaluated once. Compound assignment to a missing map key is an error, just like reading it.
+    IndexAssignment {
+        object: Box<Expression>,
+        index: Box<Expression>,
+        operator: Option<BinaryOperator>,
+        value: Box<Expression>,
+        expression_id: ExpressionId
     },
 
     Array {
@@ -48,22 +118,155 @@ pub enum Expression {
         struct_type: Type,
         fields: Vec<(String, Box<Expression>)>
     },
+    /// Converts a value to another type, like `3.9 as i32`
+    Cast {
+        value: Box<Expression>,
+        target: Type
+    },
 
     If {
         condition: Box<Expression>,
         then_branch: Box<Expression>,
         else_branch: Option<Box<Expression>>
     },
+    /// Runs the first arm whose pattern matches the value
+    Match {
+        value: Box<Expression>,
+        arms: Vec<MatchArm>
+    },
     Loop(LoopType)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Hash)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Box<Expression>
+}
+
+#[derive(Debug, PartialEq, Clone, Hash)]
+pub enum Pattern {
+    /// An enum variant like `Shape.Rect(w, h)`, binding each value the variant carries to a name
+    Variant {
+        enum_name: String,
+        variant: String,
+        bindings: Vec<String>
+    },
+    /// `_`, which matches anything
+    Wildcard
+}
+
+/// Expressions hash only their structure: IDs are left out, and names are hashed by their text rather than by
+/// their interned symbol, so structurally identical programs hash the same however and whenever they were parsed.
+impl Hash for Expression {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Expression::Block(statements) => statements.hash(state),
+            Expression::IntegerLiteral(n) => n.hash(state),
+            Expression::NumberLiteral(n) => n.to_bits().hash(state),
+            Expression::TypedIntegerLiteral(n, literal_type) => (n, literal_type).hash(state),
+            Expression::TypedNumberLiteral(n, literal_type) => (n.to_bits(), literal_type).hash(state),
+            Expression::StringLiteral(s) => s.hash(state),
+            Expression::CharLiteral(c) => c.hash(state),
+            Expression::Variable { name, .. } => name.as_str().hash(state),
+            Expression::BooleanLiteral(b) => b.hash(state),
+            Expression::ArrayLiteral(elements) | Expression::Tuple(elements) => elements.hash(state),
+            Expression::ArrayComprehension { element, binding, iterable, condition } => {
+                element.hash(state);
+                binding.hash(state);
+                iterable.hash(state);
+                condition.hash(state);
+            },
+            Expression::Range { start, end, inclusive } => (start, end, inclusive).hash(state),
+            Expression::MapLiteral(entries) => entries.hash(state),
+            Expression::FunctionCall { callee, args, .. } => {
+                callee.hash(state);
+                args.hash(state);
+            },
+            Expression::Spread(value) => value.hash(state),
+            Expression::Lambda { params, body } => {
+                params.hash(state);
+                body.hash(state);
+            },
+            Expression::BinaryOperation { left, operator, right, .. } => {
+                left.hash(state);
+                operator.hash(state);
+                right.hash(state);
+            },
+            Expression::UnaryOperation { operator, operand, .. } => {
+                operator.hash(state);
+                operand.hash(state);
+            },
+            Expression::Assignment { name, value, .. } => {
+                name.as_str().hash(state);
+                value.hash(state);
+            },
+            Expression::MemberAccess { object, member, optional } => {
+                object.hash(state);
+                member.hash(state);
+                optional.hash(state);
+            },
+            Expression::Index { object, index, .. } => {
+                object.hash(state);
+                index.hash(state);
+            },
+            Expression::IndexAssignment { object, index, operator, value, .. } => {
+                object.hash(state);
+                index.hash(state);
+                operator.hash(state);
+                value.hash(state);
+            },
+            Expression::Array { array_type, size, initial_value } => {
+                array_type.hash(state);
+                size.hash(state);
+                initial_value.hash(state);
+            },
+            Expression::StructCreation { struct_type, fields } => {
+                struct_type.hash(state);
+                fields.hash(state);
+            },
+            Expression::Cast { value, target } => {
+                value.hash(state);
+                target.hash(state);
+            },
+            Expression::If { condition, then_branch, else_branch } => {
+                condition.hash(state);
+                then_branch.hash(state);
+                else_branch.hash(state);
+            },
+            Expression::Match { value, arms } => {
+                value.hash(state);
+                arms.hash(state);
+            },
+            Expression::Loop(loop_type) => loop_type.hash(state)
+        }
+    }
+}
+
+impl Expression {
+    /// Returns the ID of this expression, if it has one. Only expressions that the resolver
+    /// or error reporting need to refer to have IDs.
+    pub fn id(&self) -> Option<ExpressionId> {
+        match self {
+            Expression::Variable { expression_id, .. } |
+            Expression::Assignment { expression_id, .. } |
+            Expression::FunctionCall { expression_id, .. } |
+            Expression::BinaryOperation { expression_id, .. } |
+            Expression::UnaryOperation { expression_id, .. } |
+            Expression::Index { expression_id, .. } |
+            Expression::IndexAssignment { expression_id, .. } => Some(*expression_id),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Hash)]
 pub enum VariableMutability {
     Mutable,
     Immutable
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub enum LoopType {
     While {
         condition: Box<Expression>,
@@ -72,25 +275,50 @@ pub enum LoopType {
     Infinite {
         body: Box<Expression>
     },
+    /// A loop whose condition is checked after the body, so the body always runs at least once.
+    /// Written as `loop { ... } while (condition)`.
+    DoWhile {
+        body: Box<Expression>,
+        condition: Box<Expression>
+    },
     Iterator {
         mutability: VariableMutability,
         iterator: String,
         iterable: Box<Expression>,
         body: Box<Expression>
+    },
+    /// A loop that runs while an expression isn't nil, binding its value for the body.
+    /// Written as `loop (let value = next()) { ... }`.
+    WhileLet {
+        mutability: VariableMutability,
+        binding: String,
+        expr: Box<Expression>,
+        body: Box<Expression>
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub enum BinaryOperator {
     Add,
     Subtract,
     Multiply,
+    /// Always produces a float, even between two integers: `5 / 2` is `2.5`.
     Divide,
+    /// Divides and rounds toward negative infinity. Between two integers the result is an integer: `5 ~/ 2` is `2`
+    /// and `-5 ~/ 2` is `-3`. It's spelled `~/` rather than `//` since `//` starts a comment.
+    FloorDivide,
     Modulus,
 
     And,
     Or,
 
+    /// The bitwise operators only apply to integers.
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
+
     Equal,
     NotEqual,
     LessThan,
@@ -99,6 +327,32 @@ pub enum BinaryOperator {
     GreaterThanOrEqual
 }
 
+impl BinaryOperator {
+    /// Returns true for the operators that apply to numeric operands.
+    pub fn is_arithmetic_or_comparison(&self) -> bool {
+        !matches!(self, BinaryOperator::And | BinaryOperator::Or | BinaryOperator::Equal | BinaryOperator::NotEqual) && !self.is_bitwise()
+    }
+
+    /// Returns true for the operators that only apply to integers.
+    pub fn is_bitwise(&self) -> bool {
+        matches!(self, BinaryOperator::BitwiseAnd | BinaryOperator::BitwiseOr | BinaryOperator::BitwiseXor | BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight)
+    }
+
+    /// Returns the name of the method a struct declares to overload the operator, like `add` for `+`.
+    /// Only the arithmetic operators can be overloaded.
+    pub fn method_name(&self) -> Option<&'static str> {
+        match self {
+            BinaryOperator::Add => Some("add"),
+            BinaryOperator::Subtract => Some("subtract"),
+            BinaryOperator::Multiply => Some("multiply"),
+            BinaryOperator::Divide => Some("divide"),
+            BinaryOperator::FloorDivide => Some("floor_divide"),
+            BinaryOperator::Modulus => Some("modulus"),
+            _ => None
+        }
+    }
+}
+
 impl std::fmt::Display for BinaryOperator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", match self {
@@ -106,9 +360,15 @@ impl std::fmt::Display for BinaryOperator {
             BinaryOperator::Subtract => "-",
             BinaryOperator::Multiply => "*",
             BinaryOperator::Divide => "/",
+            BinaryOperator::FloorDivide => "~/",
             BinaryOperator::Modulus => "%",
             BinaryOperator::And => "&&",
             BinaryOperator::Or => "||",
+            BinaryOperator::BitwiseAnd => "&",
+            BinaryOperator::BitwiseOr => "|",
+            BinaryOperator::BitwiseXor => "^",
+            BinaryOperator::ShiftLeft => "<<",
+            BinaryOperator::ShiftRight => ">>",
             BinaryOperator::Equal => "==",
             BinaryOperator::NotEqual => "!=",
             BinaryOperator::LessThan => "<",
@@ -119,34 +379,45 @@ impl std::fmt::Display for BinaryOperator {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub enum UnaryOperator {
     Negate,
-    Not
+    Not,
+    BitwiseNot
 }
 
 impl std::fmt::Display for UnaryOperator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", match self {
             UnaryOperator::Negate => "-",
-            UnaryOperator::Not => "!"
+            UnaryOperator::Not => "!",
+            UnaryOperator::BitwiseNot => "~"
         })
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub enum Declaration {
     Function {
         name: String,
         params: Vec<FunctionParameter>,
         generic_args: Vec<String>,
         return_type: Type,
-        body: Box<Expression>
+        body: Rc<Expression>,
+        /// Whether the function was declared `const func`, so it can be called where a constant is needed, like an array size
+        is_const: bool
     },
     Struct {
         name: String,
         elements: Vec<StructElement>,
-        generic_args: Vec<String>
+        generic_args: Vec<String>,
+        /// The interfaces the struct says it implements, like `Printable` in `struct Point : Printable`
+        interfaces: Vec<String>
+    },
+    /// A set of methods that structs can declare they implement, so code can accept any of those structs
+    Interface {
+        name: String,
+        methods: Vec<InterfaceMethod>
     },
     TypeDeclaration {
         name: String,
@@ -154,11 +425,40 @@ pub enum Declaration {
         alias: Type
     },
     Import {
-        path: Vec<String>
+        path: Vec<String>,
+        /// Gives the import a span, pointing at the start of its path
+        expression_id: ExpressionId
+    },
+    Enum {
+        name: String,
+        variants: Vec<EnumVariant>,
+        generic_args: Vec<String>
+    },
+    /// A `const` or `let` at the top level of a program, which is visible from every function
+    GlobalVariable {
+        mutability: VariableMutability,
+        name: String,
+        variable_type: Type,
+        value: Box<Expression>
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// A variant of an enum, like `Rect(f64, f64)`, with the types of the values it carries.
+#[derive(Debug, PartialEq, Clone, Hash)]
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<Type>
+}
+
+/// A method an interface requires, like `func toString() -> string;`.
+#[derive(Debug, PartialEq, Clone, Hash)]
+pub struct InterfaceMethod {
+    pub name: String,
+    pub params: Vec<FunctionParameter>,
+    pub return_type: Type
+}
+
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub enum StructElement {
     Declaration(Declaration),
     Field {
@@ -167,7 +467,7 @@ pub enum StructElement {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     Declaration(Declaration),
     Expression {
@@ -178,20 +478,36 @@ pub enum Statement {
         mutability: VariableMutability,
         name: String,
         variable_type: Type,
-        value: Box<Expression>
+        value: Box<Expression>,
+        /// Identifies the declaration so its span, which points at the name, can be looked up
+        expression_id: ExpressionId
     },
     Break,
     Continue,
     Return(Option<Box<Expression>>)
 }
 
-#[derive(Debug, PartialEq)]
+/// Like expressions, statements leave their IDs out of their hash.
+impl Hash for Statement {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Statement::Declaration(declaration) => declaration.hash(state),
+            Statement::Expression { expression, result } => (expression, result).hash(state),
+            Statement::VariableDeclaration { mutability, name, variable_type, value, .. } => (mutability, name, variable_type, value).hash(state),
+            Statement::Break | Statement::Continue => {},
+            Statement::Return(value) => value.hash(state)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub struct FunctionParameter {
     pub name: String,
     pub param_type: Type
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub enum Type {
     U8, U16, U32, U64,
     I8, I16, I32, I64,
@@ -207,12 +523,137 @@ pub enum Type {
         return_type: Box<Type>
     },
     Array(Box<Type>),
+    Tuple(Vec<Type>),
+    /// A value of the wrapped type or nil, written as `i32?`. It has to be unwrapped with `as`, like `x as i32`,
+    /// before it's used where the wrapped type is expected.
+    Optional(Box<Type>),
     /// Nil is the return type for functions that don't return a value.
     /// Nil can only have the value of `nil` (which, itself, is only valid for the type Nil), and is invalid in other contexts.
     Nil
 }
 
-#[derive(Debug, PartialEq)]
+impl Type {
+    /// Returns the smallest and largest values of an integer type, or None for other types.
+    /// Integers are stored as i64 at runtime, so u64 can only go up to i64's maximum.
+    pub fn integer_range(&self) -> Option<(i64, i64)> {
+        match self {
+            Type::U8 => Some((0, u8::MAX as i64)),
+            Type::U16 => Some((0, u16::MAX as i64)),
+            Type::U32 => Some((0, u32::MAX as i64)),
+            Type::U64 => Some((0, i64::MAX)),
+            Type::I8 => Some((i8::MIN as i64, i8::MAX as i64)),
+            Type::I16 => Some((i16::MIN as i64, i16::MAX as i64)),
+            Type::I32 => Some((i32::MIN as i64, i32::MAX as i64)),
+            Type::I64 => Some((i64::MIN, i64::MAX)),
+            _ => None
+        }
+    }
+
+    /// Returns the wrapped type of an optional type, or the type itself for any other type.
+    pub fn non_optional(&self) -> &Type {
+        match self {
+            Type::Optional(inner) => inner,
+            ty => ty
+        }
+    }
+}
+
+/// Formats a pattern the way it's written in source, like `Shape.Rect(w, h)`.
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Variant { enum_name, variant, bindings } if bindings.is_empty() => write!(f, "{}.{}", enum_name, variant),
+            Pattern::Variant { enum_name, variant, bindings } => write!(f, "{}.{}({})", enum_name, variant, bindings.join(", ")),
+            Pattern::Wildcard => write!(f, "_")
+        }
+    }
+}
+
+/// Formats a type the way it's written in source, like `Map<string, [i32]>`.
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        /// Writes types separated by commas.
+        fn write_list(f: &mut std::fmt::Formatter<'_>, types: &[Type]) -> std::fmt::Result {
+            for (i, ty) in types.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", ty)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::F32 => write!(f, "f32"),
+            Type::F64 => write!(f, "f64"),
+            Type::Boolean => write!(f, "bool"),
+            Type::Character => write!(f, "char"),
+            Type::Nil => write!(f, "nil"),
+            Type::Identifier { name, generics } => {
+                write!(f, "{}", name)?;
+                if !generics.is_empty() {
+                    write!(f, "<")?;
+                    write_list(f, generics)?;
+                    write!(f, ">")?;
+                }
+                Ok(())
+            },
+            Type::Function { params, return_type } => {
+                write!(f, "func(")?;
+                write_list(f, params)?;
+                write!(f, ") -> {}", return_type)
+            },
+            Type::Array(element_type) => write!(f, "[{}]", element_type),
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Tuple(element_types) => {
+                write!(f, "(")?;
+                write_list(f, element_types)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Hash)]
 pub struct Program {
     pub declarations: Vec<Declaration>
+}
+
+/// A 64-bit FNV-1a hasher. Unlike `DefaultHasher`, its algorithm is fixed, so hashes written to disk
+/// stay valid across Rust versions.
+pub struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Hashes a program's structure, ignoring where things are in the source and how expressions were numbered.
+/// Programs that differ only in formatting or comments hash the same, so this can key a cache of compiled programs.
+pub fn ast_hash(program: &Program) -> u64 {
+    let mut hasher = FnvHasher::default();
+    program.hash(&mut hasher);
+    hasher.finish()
 }
\ No newline at end of file
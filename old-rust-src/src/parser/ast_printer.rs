@@ -1,7 +1,9 @@
-use super::ast::{Declaration, Expression, LoopType, Program, Statement, StructElement, Type, VariableMutability};
+use super::{ast::{Declaration, Expression, LoopType, Program, Statement, StructElement, VariableMutability}, visitor::{walk_expression, Visitor}};
 
+/// Prints an indented tree of the AST for debugging.
 pub struct ASTPrinter {
     indent: usize,
+    output: String
 }
 
 const ANSI_GRAY: &str = "\x1b[90m";
@@ -37,311 +39,525 @@ macro_rules! fmt_indent {
     }};
 }
 
+impl Default for ASTPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ASTPrinter {
     pub fn new() -> Self {
-        ASTPrinter { indent: 0 }
+        ASTPrinter { indent: 0, output: String::new() }
     }
 
     pub fn print_program(&mut self, program: &Program) -> String {
         self.indent = 0;
-        let mut output = String::new();
-        for declaration in &program.declarations {
-            output.push_str(&self.print_declaration(declaration));
-        }
-        output
+        self.visit_program(program);
+        std::mem::take(&mut self.output)
     }
+}
 
-    fn print_declaration(&mut self, declaration: &Declaration) -> String {
+impl Visitor for ASTPrinter {
+    fn visit_declaration(&mut self, declaration: &Declaration) {
         match declaration {
-            Declaration::Function { name, params, return_type, body, generic_args } => {
-                let mut output = fmt_indent!(self, "Function: {}\n", name);
+            Declaration::Function { name, params, return_type, body, generic_args: _, is_const } => {
+                self.output.push_str(&fmt_indent!(self, "{}: {}\n", if *is_const { "Const Function" } else { "Function" }, name));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Parameters:\n"));
+                self.output.push_str(&fmt_indent!(self, "Parameters:\n"));
                 for param in params {
-                    output.push_str(&fmt_indent!(self, "- {}: {}\n", param.name, self.print_type(&param.param_type)));
+                    self.output.push_str(&fmt_indent!(self, "- {}: {}\n", param.name, param.param_type));
                 }
-                output.push_str(&fmt_indent!(self, "Return Type: {}\n", self.print_type(return_type)));
-                output.push_str(&fmt_indent!(self, "Body: "));
-                output.push_str(&self.print_expression(body));
+                self.output.push_str(&fmt_indent!(self, "Return Type: {}\n", return_type));
+                self.output.push_str(&fmt_indent!(self, "Body: "));
+                self.visit_expression(body);
                 self.indent -= 1;
-                output
             }
-            Declaration::Import { path } => {
-                fmt_indent!(self, "Import: {}\n", path.join("."))
+            Declaration::Import { path, .. } => {
+                self.output.push_str(&fmt_indent!(self, "Import: {}\n", path.join(".")));
             }
-            Declaration::Struct { name, elements, generic_args } => {
-                let mut output = fmt_indent!(self, "Struct: {}\n", name);
+            Declaration::Enum { name, variants, .. } => {
+                self.output.push_str(&fmt_indent!(self, "Enum: {}\n", name));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Elements:\n"));
+                self.output.push_str(&fmt_indent!(self, "Variants:\n"));
+                for variant in variants {
+                    if variant.fields.is_empty() {
+                        self.output.push_str(&fmt_indent!(self, "- {}\n", variant.name));
+                    } else {
+                        let fields: Vec<String> = variant.fields.iter().map(|field| field.to_string()).collect();
+                        self.output.push_str(&fmt_indent!(self, "- {}({})\n", variant.name, fields.join(", ")));
+                    }
+                }
+                self.indent -= 1;
+            }
+            Declaration::GlobalVariable { mutability, name, variable_type, value } => {
+                self.output.push_str(&fmt_indent!(self, "Global Variable: {}\n", name));
+                self.indent += 1;
+                self.output.push_str(&fmt_indent!(self, "Mutability: {}\n", match mutability {
+                    VariableMutability::Mutable => "Mutable",
+                    VariableMutability::Immutable => "Immutable",
+                }));
+                self.output.push_str(&fmt_indent!(self, "Type: {}\n", variable_type));
+                self.output.push_str(&fmt_indent!(self, "Value:\n"));
+                self.visit_expression(value);
+                self.indent -= 1;
+            }
+            Declaration::Struct { name, elements, generic_args: _, interfaces } => {
+                self.output.push_str(&fmt_indent!(self, "Struct: {}\n", name));
+                self.indent += 1;
+                if !interfaces.is_empty() {
+                    self.output.push_str(&fmt_indent!(self, "Interfaces: {}\n", interfaces.join(", ")));
+                }
+                self.output.push_str(&fmt_indent!(self, "Elements:\n"));
                 for element in elements {
                     match element {
                         StructElement::Field { name, field_type } => {
-                            output.push_str(&fmt_indent!(self, "- {}: {}\n", name, self.print_type(field_type)));
+                            self.output.push_str(&fmt_indent!(self, "- {}: {}\n", name, field_type));
                         },
                         StructElement::Declaration(declaration) => {
-                            output.push_str(&self.print_declaration(declaration));
+                            self.visit_declaration(declaration);
                         }
                     }
                 }
                 self.indent -= 1;
-                output
+            },
+            Declaration::Interface { name, methods } => {
+                self.output.push_str(&fmt_indent!(self, "Interface: {}\n", name));
+                self.indent += 1;
+                self.output.push_str(&fmt_indent!(self, "Methods:\n"));
+                for method in methods {
+                    let params: Vec<String> = method.params.iter().map(|param| format!("{}: {}", param.name, param.param_type)).collect();
+                    self.output.push_str(&fmt_indent!(self, "- {}({}) -> {}\n", method.name, params.join(", "), method.return_type));
+                }
+                self.indent -= 1;
             },
             Declaration::TypeDeclaration { name, alias, generic_args } => {
-                let mut output = fmt_indent!(self, "Type Declaration: {}\n", name);
+                self.output.push_str(&fmt_indent!(self, "Type Declaration: {}\n", name));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Alias: {}\n", self.print_type(alias)));
+                self.output.push_str(&fmt_indent!(self, "Alias: {}\n", alias));
                 if !generic_args.is_empty() {
-                    output.push_str(&fmt_indent!(self, "Generic Arguments:\n"));
+                    self.output.push_str(&fmt_indent!(self, "Generic Arguments:\n"));
                     for arg in generic_args {
-                        output.push_str(&fmt_indent!(self, "- {}\n", arg));
+                        self.output.push_str(&fmt_indent!(self, "- {}\n", arg));
                     }
                 }
                 self.indent -= 1;
-                output
             }
         }
     }
 
-    fn print_expression(&mut self, expression: &Expression) -> String {
+    fn visit_expression(&mut self, expression: &Expression) {
         match expression {
             Expression::Assignment { name: variable, value, .. } => {
-                let mut output = fmt_indent!(self, "Assignment:\n");
+                self.output.push_str(&fmt_indent!(self, "Assignment:\n"));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Variable: {}\n", variable));
-                output.push_str(&fmt_indent!(self, "Value:\n"));
-                output.push_str(&self.print_expression(value));
+                self.output.push_str(&fmt_indent!(self, "Variable: {}\n", variable));
+                self.output.push_str(&fmt_indent!(self, "Value:\n"));
+                self.visit_expression(value);
                 self.indent -= 1;
-                output
             },
-            Expression::BinaryOperation { left, operator, right } => {
-                let mut output = fmt_indent!(self, "Binary Operation: {}\n", operator);
+            Expression::BinaryOperation { left, operator, right, .. } => {
+                self.output.push_str(&fmt_indent!(self, "Binary Operation: {}\n", operator));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Left:\n"));
-                output.push_str(&self.print_expression(left));
-                output.push_str(&fmt_indent!(self, "Right:\n"));
-                output.push_str(&self.print_expression(right));
+                self.output.push_str(&fmt_indent!(self, "Left:\n"));
+                self.visit_expression(left);
+                self.output.push_str(&fmt_indent!(self, "Right:\n"));
+                self.visit_expression(right);
                 self.indent -= 1;
-                output
             },
-            Expression::UnaryOperation { operator, operand } => {
-                let mut output = fmt_indent!(self, "Unary Operation: {}\n", operator);
+            Expression::UnaryOperation { operator, operand, .. } => {
+                self.output.push_str(&fmt_indent!(self, "Unary Operation: {}\n", operator));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Operand:\n"));
-                output.push_str(&self.print_expression(operand));
+                self.output.push_str(&fmt_indent!(self, "Operand:\n"));
+                self.visit_expression(operand);
                 self.indent -= 1;
-                output
             },
-            Expression::Block(statements) => {
-                let mut output = fmt_indent!(self, "Block:\n");
+            Expression::Block(_) => {
+                self.output.push_str(&fmt_indent!(self, "Block:\n"));
                 self.indent += 1;
-                for statement in statements {
-                    output.push_str(&self.print_statement(statement));
-                }
+                walk_expression(self, expression);
                 self.indent -= 1;
-                output
             },
             Expression::BooleanLiteral(value) => {
-                fmt_indent!(self, "Boolean Literal: {}\n", value)
+                self.output.push_str(&fmt_indent!(self, "Boolean Literal: {}\n", value));
             },
             Expression::CharLiteral(value) => {
-                fmt_indent!(self, "Character Literal: {}\n", value)
+                self.output.push_str(&fmt_indent!(self, "Character Literal: {}\n", value));
+            },
+            Expression::IntegerLiteral(value) => {
+                self.output.push_str(&fmt_indent!(self, "Integer Literal: {}\n", value));
             },
             Expression::NumberLiteral(value) => {
-                fmt_indent!(self, "Number Literal: {}\n", value)
+                self.output.push_str(&fmt_indent!(self, "Number Literal: {}\n", value));
+            },
+            Expression::TypedIntegerLiteral(value, literal_type) => {
+                self.output.push_str(&fmt_indent!(self, "Integer Literal: {}{}\n", value, literal_type));
+            },
+            Expression::TypedNumberLiteral(value, literal_type) => {
+                self.output.push_str(&fmt_indent!(self, "Number Literal: {}{}\n", value, literal_type));
             },
             Expression::StringLiteral(value) => {
-                fmt_indent!(self, "String Literal: {}\n", value)
+                self.output.push_str(&fmt_indent!(self, "String Literal: {}\n", value));
             },
-            Expression::FunctionCall { callee, args } => {
-                let mut output = fmt_indent!(self, "Function Call\n");
+            Expression::ArrayLiteral(_) => {
+                self.output.push_str(&fmt_indent!(self, "Array Literal:\n"));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Callee:\n"));
-                output.push_str(&self.print_expression(callee));
+                walk_expression(self, expression);
+                self.indent -= 1;
+            },
+            Expression::ArrayComprehension { element, binding, iterable, condition } => {
+                self.output.push_str(&fmt_indent!(self, "Array Comprehension:\n"));
+                self.indent += 1;
+                self.output.push_str(&fmt_indent!(self, "Binding: {}\n", binding));
+                self.output.push_str(&fmt_indent!(self, "Iterable:\n"));
+                self.visit_expression(iterable);
+                if let Some(condition) = condition {
+                    self.output.push_str(&fmt_indent!(self, "Condition:\n"));
+                    self.visit_expression(condition);
+                }
+                self.output.push_str(&fmt_indent!(self, "Element:\n"));
+                self.visit_expression(element);
+                self.indent -= 1;
+            },
+            Expression::Range { start, end, inclusive } => {
+                self.output.push_str(&fmt_indent!(self, "Range{}:\n", if *inclusive { " (inclusive)" } else { "" }));
+                self.indent += 1;
+                self.visit_expression(start);
+                self.visit_expression(end);
+                self.indent -= 1;
+            },
+            Expression::Tuple(_) => {
+                self.output.push_str(&fmt_indent!(self, "Tuple:\n"));
+                self.indent += 1;
+                walk_expression(self, expression);
+                self.indent -= 1;
+            },
+            Expression::MapLiteral(entries) => {
+                self.output.push_str(&fmt_indent!(self, "Map Literal:\n"));
+                self.indent += 1;
+                for (key, value) in entries {
+                    self.output.push_str(&fmt_indent!(self, "Key:\n"));
+                    self.visit_expression(key);
+                    self.output.push_str(&fmt_indent!(self, "Value:\n"));
+                    self.visit_expression(value);
+                }
+                self.indent -= 1;
+            },
+            Expression::FunctionCall { callee, args, .. } => {
+                self.output.push_str(&fmt_indent!(self, "Function Call\n"));
+                self.indent += 1;
+                self.output.push_str(&fmt_indent!(self, "Callee:\n"));
+                self.visit_expression(callee);
 
-                output.push_str(&fmt_indent!(self, "Arguments:\n"));
+                self.output.push_str(&fmt_indent!(self, "Arguments:\n"));
                 for arg in args {
-                    output.push_str(&self.print_expression(arg));
+                    self.visit_expression(arg);
                 }
                 self.indent -= 1;
-                output
+            },
+            Expression::Spread(value) => {
+                self.output.push_str(&fmt_indent!(self, "Spread:\n"));
+                self.indent += 1;
+                self.visit_expression(value);
+                self.indent -= 1;
+            },
+            Expression::Lambda { params, body } => {
+                self.output.push_str(&fmt_indent!(self, "Lambda:\n"));
+                self.indent += 1;
+                self.output.push_str(&fmt_indent!(self, "Parameters: {}\n", params.join(", ")));
+                self.output.push_str(&fmt_indent!(self, "Body: "));
+                self.visit_expression(body);
+                self.indent -= 1;
             },
             Expression::Variable { name, .. } => {
-                fmt_indent!(self, "Variable: {}\n", name)
+                self.output.push_str(&fmt_indent!(self, "Variable: {}\n", name));
             },
             Expression::If { condition, then_branch, else_branch } => {
-                let mut output = fmt_indent!(self, "If Statement:\n");
+                self.output.push_str(&fmt_indent!(self, "If Statement:\n"));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Condition:\n"));
-                output.push_str(&self.print_expression(condition));
-                output.push_str(&fmt_indent!(self, "Then Branch:\n"));
-                output.push_str(&self.print_expression(then_branch));
+                self.output.push_str(&fmt_indent!(self, "Condition:\n"));
+                self.visit_expression(condition);
+                self.output.push_str(&fmt_indent!(self, "Then Branch:\n"));
+                self.visit_expression(then_branch);
+
+                // An `else if` chain is printed at the same level as the first if instead of nesting each one deeper
+                let mut else_branch = else_branch.as_deref();
+                while let Some(Expression::If { condition, then_branch, else_branch: next }) = else_branch {
+                    self.output.push_str(&fmt_indent!(self, "Else If Condition:\n"));
+                    self.visit_expression(condition);
+                    self.output.push_str(&fmt_indent!(self, "Then Branch:\n"));
+                    self.visit_expression(then_branch);
+                    else_branch = next.as_deref();
+                }
                 if let Some(else_branch) = else_branch {
-                    output.push_str(&fmt_indent!(self, "Else Branch:\n"));
-                    output.push_str(&self.print_expression(else_branch));
+                    self.output.push_str(&fmt_indent!(self, "Else Branch:\n"));
+                    self.visit_expression(else_branch);
                 }
                 self.indent -= 1;
-                output
             },
-            Expression::Loop(LoopType::Infinite { body }) => {
-                let mut output = fmt_indent!(self, "Infinite Loop:\n");
+            Expression::Match { value, arms } => {
+                self.output.push_str(&fmt_indent!(self, "Match:\n"));
                 self.indent += 1;
-                output.push_str(&self.print_expression(body));
+                self.output.push_str(&fmt_indent!(self, "Value:\n"));
+                self.visit_expression(value);
+                for arm in arms {
+                    self.output.push_str(&fmt_indent!(self, "Arm: {}\n", arm.pattern));
+                    self.indent += 1;
+                    self.visit_expression(&arm.body);
+                    self.indent -= 1;
+                }
+                self.indent -= 1;
+            },
+            Expression::Loop(LoopType::Infinite { .. }) => {
+                self.output.push_str(&fmt_indent!(self, "Infinite Loop:\n"));
+                self.indent += 1;
+                walk_expression(self, expression);
+                self.indent -= 1;
+            },
+            Expression::Loop(LoopType::DoWhile { body, condition }) => {
+                self.output.push_str(&fmt_indent!(self, "Do-While Loop:\n"));
+                self.indent += 1;
+                self.output.push_str(&fmt_indent!(self, "Body: "));
+                self.visit_expression(body);
+                self.output.push_str(&fmt_indent!(self, "Condition:\n"));
+                self.visit_expression(condition);
                 self.indent -= 1;
-                output
             },
             Expression::Loop(LoopType::While { condition, body }) => {
-                let mut output = fmt_indent!(self, "While Loop:\n");
+                self.output.push_str(&fmt_indent!(self, "While Loop:\n"));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Condition:\n"));
-                output.push_str(&self.print_expression(condition));
-                output.push_str(&fmt_indent!(self, "Body: "));
-                output.push_str(&self.print_expression(body));
+                self.output.push_str(&fmt_indent!(self, "Condition:\n"));
+                self.visit_expression(condition);
+                self.output.push_str(&fmt_indent!(self, "Body: "));
+                self.visit_expression(body);
                 self.indent -= 1;
-                output
             },
             Expression::Loop(LoopType::Iterator { mutability, iterator, iterable, body }) => {
-                let mut output = fmt_indent!(self, "Iterator Loop:\n");
+                self.output.push_str(&fmt_indent!(self, "Iterator Loop:\n"));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Mutability: {}\n", match mutability {
+                self.output.push_str(&fmt_indent!(self, "Mutability: {}\n", match mutability {
                     VariableMutability::Mutable => "Mutable",
                     VariableMutability::Immutable => "Immutable",
                 }));
-                output.push_str(&fmt_indent!(self, "Iterator: {}\n", iterator));
-                output.push_str(&fmt_indent!(self, "Iterable:\n"));
-                output.push_str(&self.print_expression(iterable));
-                output.push_str(&fmt_indent!(self, "Body: "));
-                output.push_str(&self.print_expression(body));
+                self.output.push_str(&fmt_indent!(self, "Iterator: {}\n", iterator));
+                self.output.push_str(&fmt_indent!(self, "Iterable:\n"));
+                self.visit_expression(iterable);
+                self.output.push_str(&fmt_indent!(self, "Body: "));
+                self.visit_expression(body);
                 self.indent -= 1;
-                output
             },
-            Expression::MemberAccess { object, member } => {
-                let mut output = fmt_indent!(self, "Member Access:\n");
+            Expression::Loop(LoopType::WhileLet { mutability, binding, expr, body }) => {
+                self.output.push_str(&fmt_indent!(self, "While Let Loop:\n"));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Object:\n"));
-                output.push_str(&self.print_expression(object));
-                output.push_str(&fmt_indent!(self, "Member: {}\n", member));
+                self.output.push_str(&fmt_indent!(self, "Mutability: {}\n", match mutability {
+                    VariableMutability::Mutable => "Mutable",
+                    VariableMutability::Immutable => "Immutable",
+                }));
+                self.output.push_str(&fmt_indent!(self, "Binding: {}\n", binding));
+                self.output.push_str(&fmt_indent!(self, "Value:\n"));
+                self.visit_expression(expr);
+                self.output.push_str(&fmt_indent!(self, "Body: "));
+                self.visit_expression(body);
+                self.indent -= 1;
+            },
+            Expression::MemberAccess { object, member, optional } => {
+                self.output.push_str(&fmt_indent!(self, "{}:\n", if *optional { "Optional Member Access" } else { "Member Access" }));
+                self.indent += 1;
+                self.output.push_str(&fmt_indent!(self, "Object:\n"));
+                self.visit_expression(object);
+                self.output.push_str(&fmt_indent!(self, "Member: {}\n", member));
+                self.indent -= 1;
+            },
+            Expression::Index { object, index, .. } => {
+                self.output.push_str(&fmt_indent!(self, "Index:\n"));
+                self.indent += 1;
+                self.output.push_str(&fmt_indent!(self, "Object:\n"));
+                self.visit_expression(object);
+                self.output.push_str(&fmt_indent!(self, "Index:\n"));
+                self.visit_expression(index);
+                self.indent -= 1;
+            },
+            Expression::IndexAssignment { object, index, operator, value, .. } => {
+                self.output.push_str(&fmt_indent!(self, "Index Assignment:\n"));
+                self.indent += 1;
+                if let Some(operator) = operator {
+                    self.output.push_str(&fmt_indent!(self, "Operator: {}=\n", operator));
+                }
+                self.output.push_str(&fmt_indent!(self, "Object:\n"));
+                self.visit_expression(object);
+                self.output.push_str(&fmt_indent!(self, "Index:\n"));
+                self.visit_expression(index);
+                self.output.push_str(&fmt_indent!(self, "Value:\n"));
+                self.visit_expression(value);
                 self.indent -= 1;
-                output
             }
             Expression::Array { array_type, size, initial_value } => {
-                let mut output = fmt_indent!(self, "Array:\n");
+                self.output.push_str(&fmt_indent!(self, "Array:\n"));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Type: {}\n", self.print_type(array_type)));
-                output.push_str(&fmt_indent!(self, "Size:\n"));
-                output.push_str(&self.print_expression(size));
-                output.push_str(&fmt_indent!(self, "Initial Value:\n"));
-                output.push_str(&self.print_expression(initial_value));
+                self.output.push_str(&fmt_indent!(self, "Type: {}\n", array_type));
+                self.output.push_str(&fmt_indent!(self, "Size:\n"));
+                self.visit_expression(size);
+                self.output.push_str(&fmt_indent!(self, "Initial Value:\n"));
+                self.visit_expression(initial_value);
                 self.indent -= 1;
-                output
             },
             Expression::StructCreation { struct_type, fields } => {
-                let mut output = fmt_indent!(self, "Struct Creation:\n");
+                self.output.push_str(&fmt_indent!(self, "Struct Creation:\n"));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Type: {}\n", self.print_type(struct_type)));
-                output.push_str(&fmt_indent!(self, "Fields:\n"));
+                self.output.push_str(&fmt_indent!(self, "Type: {}\n", struct_type));
+                self.output.push_str(&fmt_indent!(self, "Fields:\n"));
                 for (name, value) in fields {
-                    output.push_str(&fmt_indent!(self, "{}:\n", name));
+                    self.output.push_str(&fmt_indent!(self, "{}:\n", name));
                     self.indent += 1;
-                    output.push_str(&self.print_expression(value));
+                    self.visit_expression(value);
                     self.indent -= 1;
                 }
                 self.indent -= 1;
-                output
+            },
+            Expression::Cast { value, target } => {
+                self.output.push_str(&fmt_indent!(self, "Cast: {}\n", target));
+                self.indent += 1;
+                self.visit_expression(value);
+                self.indent -= 1;
             }
         }
     }
 
-    fn print_statement(&mut self, statement: &Statement) -> String {
+    fn visit_statement(&mut self, statement: &Statement) {
         match statement {
             Statement::Declaration(declaration) => {
-                self.print_declaration(declaration)
+                self.visit_declaration(declaration);
             },
             Statement::Break => {
-                fmt_indent!(self, "Break\n")
+                self.output.push_str(&fmt_indent!(self, "Break\n"));
             },
             Statement::Continue => {
-                fmt_indent!(self, "Continue\n")
+                self.output.push_str(&fmt_indent!(self, "Continue\n"));
             },
             Statement::Expression { expression, result } => {
-                let mut output = fmt_indent!(self, "Expression:\n");
+                self.output.push_str(&fmt_indent!(self, "Expression:\n"));
                 self.indent += 1;
-                output.push_str(&self.print_expression(expression));
+                self.visit_expression(expression);
                 if *result {
-                    output.push_str(&fmt_indent!(self, "Result: true\n"));
+                    self.output.push_str(&fmt_indent!(self, "Result: true\n"));
                 }
                 self.indent -= 1;
-                output
             },
             Statement::Return(value) => {
-                let mut output = fmt_indent!(self, "Return:\n");
+                self.output.push_str(&fmt_indent!(self, "Return:\n"));
                 self.indent += 1;
                 if let Some(value) = value {
-                    output.push_str(&self.print_expression(value));
+                    self.visit_expression(value);
                 } else {
-                    output.push_str(&fmt_indent!(self, "No value\n"));
+                    self.output.push_str(&fmt_indent!(self, "No value\n"));
                 }
                 self.indent -= 1;
-                output
             },
-            Statement::VariableDeclaration { mutability, name, variable_type, value } => {
-                let mut output = fmt_indent!(self, "Variable Declaration: {}\n", name);
+            Statement::VariableDeclaration { mutability, name, variable_type, value, .. } => {
+                self.output.push_str(&fmt_indent!(self, "Variable Declaration: {}\n", name));
                 self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Mutability: {}\n", match mutability {
+                self.output.push_str(&fmt_indent!(self, "Mutability: {}\n", match mutability {
                     VariableMutability::Mutable => "Mutable",
                     VariableMutability::Immutable => "Immutable",
                 }));
-                output.push_str(&fmt_indent!(self, "Type: {}\n", self.print_type(variable_type)));
-                output.push_str(&fmt_indent!(self, "Value:\n"));
-                output.push_str(&self.print_expression(value));
+                self.output.push_str(&fmt_indent!(self, "Type: {}\n", variable_type));
+                self.output.push_str(&fmt_indent!(self, "Value:\n"));
+                self.visit_expression(value);
                 self.indent -= 1;
-                output
             }
         }
     }
+}
 
-    fn print_type(&mut self, ty: &Type) -> String {
-        match ty {
-            Type::Boolean => "Boolean".to_string(),
-            Type::Character => "Character".to_string(),
-            Type::F32 => "F32".to_string(),
-            Type::F64 => "F64".to_string(),
-            Type::I8 => "I8".to_string(),
-            Type::I16 => "I16".to_string(),
-            Type::I32 => "I32".to_string(),
-            Type::I64 => "I64".to_string(),
-            Type::U8 => "U8".to_string(),
-            Type::U16 => "U16".to_string(),
-            Type::U32 => "U32".to_string(),
-            Type::U64 => "U64".to_string(),
-            Type::Nil => "Nil".to_string(),
-            Type::Identifier { name, generics: generic_args } => {
-                let mut output = name.clone();
-                if !generic_args.is_empty() {
-                    output.push('<');
-                    output.push_str(&generic_args.iter().map(|arg| self.print_type(arg)).collect::<Vec<_>>().join(", "));
-                    output.push('>');
-                }
-                output
-            },
-            Type::Array(of) => {
-                let mut output = "\n".to_string();
-                output.push_str(&fmt_indent!(self, "Array of "));
-                self.indent += 1;
-                output.push_str(&self.print_type(of));
-                self.indent -= 1;
-                output
-            }
-            Type::Function { params, return_type } => {
-                let mut output = fmt_indent!(self, "Function:\n");
-                self.indent += 1;
-                output.push_str(&fmt_indent!(self, "Parameters:\n"));
-                for param in params {
-                    output.push_str(&fmt_indent!(self, "- {}\n", self.print_type(&param)));
-                }
-                output.push_str(&fmt_indent!(self, "Return Type: {}\n", self.print_type(return_type)));
-                self.indent -= 1;
-                output
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use crate::{parser::Parser, tokenizer::Tokenizer};
+
+    use super::*;
+
+    #[test]
+    fn test_print_program() {
+        let mut tokenizer = Tokenizer::new("func main() -> i32 { [1, -x] }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+
+        let output = ASTPrinter::new().print_program(&program);
+        let output = output.replace(ANSI_GRAY, "").replace(ANSI_BOLD, "").replace(ANSI_RESET, "");
+        assert_eq!(output, [
+            "Function: main",
+            "|  Parameters:",
+            "|  Return Type: i32",
+            "|  Body: |  Block:",
+            "|  |  Expression:",
+            "|  |  |  Array Literal:",
+            "|  |  |  |  Integer Literal: 1",
+            "|  |  |  |  Unary Operation: -",
+            "|  |  |  |  |  Operand:",
+            "|  |  |  |  |  Variable: x",
+            "|  |  |  Result: true",
+            ""
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_else_if_chain() {
+        let mut tokenizer = Tokenizer::new("func sign(n: i32) -> i32 { if (n < 0) -1 else if (n == 0) 0 else 1 }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+
+        let output = ASTPrinter::new().print_program(&program);
+        let output = output.replace(ANSI_GRAY, "").replace(ANSI_BOLD, "").replace(ANSI_RESET, "");
+        let body: Vec<&str> = output.lines().skip_while(|line| !line.contains("If Statement")).collect();
+        assert_eq!(body, [
+            "|  |  |  If Statement:",
+            "|  |  |  |  Condition:",
+            "|  |  |  |  Binary Operation: <",
+            "|  |  |  |  |  Left:",
+            "|  |  |  |  |  Variable: n",
+            "|  |  |  |  |  Right:",
+            "|  |  |  |  |  Integer Literal: 0",
+            "|  |  |  |  Then Branch:",
+            "|  |  |  |  Unary Operation: -",
+            "|  |  |  |  |  Operand:",
+            "|  |  |  |  |  Integer Literal: 1",
+            "|  |  |  |  Else If Condition:",
+            "|  |  |  |  Binary Operation: ==",
+            "|  |  |  |  |  Left:",
+            "|  |  |  |  |  Variable: n",
+            "|  |  |  |  |  Right:",
+            "|  |  |  |  |  Integer Literal: 0",
+            "|  |  |  |  Then Branch:",
+            "|  |  |  |  Integer Literal: 0",
+            "|  |  |  |  Else Branch:",
+            "|  |  |  |  Integer Literal: 1",
+            "|  |  |  Result: true"
+        ]);
+    }
+
+    #[test]
+    fn test_while_let() {
+        let mut tokenizer = Tokenizer::new("func echo() -> nil { loop (const line = read_line()) { print(line); }; }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let program = Parser::new(tokens).parse_program().unwrap();
+
+        let output = ASTPrinter::new().print_program(&program);
+        let output = output.replace(ANSI_GRAY, "").replace(ANSI_BOLD, "").replace(ANSI_RESET, "");
+        let body: Vec<&str> = output.lines().skip_while(|line| !line.contains("While Let Loop")).collect();
+        assert_eq!(body, [
+            "|  |  |  While Let Loop:",
+            "|  |  |  |  Mutability: Immutable",
+            "|  |  |  |  Binding: line",
+            "|  |  |  |  Value:",
+            "|  |  |  |  Function Call",
+            "|  |  |  |  |  Callee:",
+            "|  |  |  |  |  Variable: read_line",
+            "|  |  |  |  |  Arguments:",
+            "|  |  |  |  Body: |  |  |  |  Block:",
+            "|  |  |  |  |  Expression:",
+            "|  |  |  |  |  |  Function Call",
+            "|  |  |  |  |  |  |  Callee:",
+            "|  |  |  |  |  |  |  Variable: print",
+            "|  |  |  |  |  |  |  Arguments:",
+            "|  |  |  |  |  |  |  Variable: line"
+        ]);
     }
-}
\ No newline at end of file
+}
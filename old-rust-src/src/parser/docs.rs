@@ -0,0 +1,128 @@
+use crate::tokenizer::Comment;
+
+use super::{ast::{Declaration, VariableMutability}, Parser};
+
+/// The comments documenting a declaration or struct field: the `///` comments before it and a `//` comment after it on its last line.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Doc {
+    /// What was documented, like `func`, `struct`, `type`, or `field`
+    pub kind: &'static str,
+    /// The name of the declaration, qualified with the structs it's in, like `Point.length`
+    pub name: String,
+    /// The line the declaration starts on
+    pub line: usize,
+    /// The text of each comment, in order
+    pub lines: Vec<String>
+}
+
+impl std::fmt::Display for Doc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} (line {})", self.kind, self.name, self.line)?;
+        for line in &self.lines {
+            write!(f, "\n    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// Gives the parser the comments from the tokenizer, so it can attach them to the declarations it parses.
+    pub fn set_comments(&mut self, comments: &[Comment]) {
+        self.comments = comments.to_vec();
+    }
+
+    /// Returns the documented declarations and fields parsed so far, in the order they appear in the source.
+    /// Nothing is documented unless the comments were passed to `set_comments` before parsing.
+    pub fn docs(&self) -> &[Doc] {
+        &self.docs
+    }
+
+    /// Records the docs for a declaration that was just parsed, starting at the token at index `start`.
+    pub(super) fn record_declaration_docs(&mut self, declaration: &Declaration, start: usize) {
+        let (kind, name) = match declaration {
+            Declaration::Function { name, is_const: true, .. } => ("const func", name),
+            Declaration::Function { name, .. } => ("func", name),
+            Declaration::Struct { name, .. } => ("struct", name),
+            Declaration::Enum { name, .. } => ("enum", name),
+            Declaration::Interface { name, .. } => ("interface", name),
+            Declaration::TypeDeclaration { name, .. } => ("type", name),
+            Declaration::GlobalVariable { mutability: VariableMutability::Immutable, name, .. } => ("const", name),
+            Declaration::GlobalVariable { mutability: VariableMutability::Mutable, name, .. } => ("let", name),
+            Declaration::Import { .. } => return
+        };
+        self.record_docs(kind, name, start);
+    }
+
+    /// Records the docs for whatever was parsed from the token at index `start` up to the current token, if it has any.
+    pub(super) fn record_docs(&mut self, kind: &'static str, name: &str, start: usize) {
+        let (first, last) = (&self.tokens[start], &self.tokens[self.current - 1]);
+        let previous = start.checked_sub(1).map(|index| &self.tokens[index]);
+        let next = self.tokens.get(self.current);
+
+        // Doc comments between the previous token and this declaration, but not on the previous token's line
+        let leading = self.comments_between(previous.map_or((0, 0), |token| (token.line + 1, 0)), (first.line, first.column))
+            .filter(|comment| comment.doc);
+        // A comment after the last token, unless something else starts on the same line before it
+        let trailing = self.comments_between((last.line, last.column), (last.line + 1, 0))
+            .filter(|comment| next.is_none_or(|next| next.line != last.line || next.column > comment.column))
+            .take(1);
+
+        let lines: Vec<String> = leading.chain(trailing).map(|comment| comment.text.clone()).collect();
+        if lines.is_empty() {
+            return;
+        }
+
+        let mut path = self.doc_path.clone();
+        path.push(name.to_string());
+        let doc = Doc { kind, name: path.join("."), line: first.line, lines };
+        // Struct members are parsed before the struct is finished, so keep the docs in source order
+        let index = self.docs.partition_point(|existing| existing.line <= doc.line);
+        self.docs.insert(index, doc);
+    }
+
+    /// Returns the comments that start after `from` and before `to`, where both are a line and column.
+    fn comments_between(&self, from: (usize, usize), to: (usize, usize)) -> impl Iterator<Item = &Comment> {
+        let start = self.comments.partition_point(|comment| (comment.line, comment.column) <= from);
+        self.comments[start..].iter().take_while(move |comment| (comment.line, comment.column) < to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tokenizer::Tokenizer;
+
+    use super::*;
+
+    #[test]
+    fn test_docs() {
+        let mut tokenizer = Tokenizer::new(r#"
+            import util; // Not documented
+            /// Adds two numbers.
+            /// Overflow is an error.
+            func add(a: i32, b: i32) -> i32 { a + b }
+
+            // A normal comment isn't documentation
+            func undocumented() -> i32 { 0 }
+
+            /// A point in 2D space.
+            struct Point {
+                x: f64; // The horizontal position
+                y: f64;
+                /// The distance from the origin.
+                func length() -> f64 { 0.0 }
+            }
+        "#.to_string());
+        tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokenizer.tokens());
+        parser.set_comments(tokenizer.comments());
+        parser.parse_program().unwrap();
+
+        let docs: Vec<String> = parser.docs().iter().map(|doc| doc.to_string()).collect();
+        assert_eq!(docs, [
+            "func add (line 5)\n    Adds two numbers.\n    Overflow is an error.",
+            "struct Point (line 11)\n    A point in 2D space.",
+            "field Point.x (line 12)\n    The horizontal position",
+            "func Point.length (line 15)\n    The distance from the origin."
+        ]);
+    }
+}
@@ -0,0 +1,230 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::tokenizer::{Token, TokenizeError, Tokenizer};
+
+use super::{ast::{Declaration, ExpressionId, Program, Span}, ParseError, Parser};
+
+/// An error from parsing the source after an edit.
+#[derive(Debug, PartialEq)]
+pub enum ReparseError {
+    Tokenize(TokenizeError),
+    Parse(Vec<ParseError>)
+}
+
+/// A top-level declaration and where it came from.
+struct ParsedDeclaration {
+    /// The bytes of the source from the start of the declaration's first token to the end of its last
+    range: Range<usize>,
+    declaration: Declaration,
+    /// Where each expression starts, relative to the start of the declaration
+    spans: HashMap<ExpressionId, Span>
+}
+
+/// Keeps a parsed program up to date as its source is edited, for editors that reparse on every keystroke.
+/// An edit inside a single top-level declaration only re-tokenizes and re-parses that declaration; anything else
+/// (an edit between declarations, one spanning several, or one that doesn't parse as exactly one declaration on its own)
+/// falls back to parsing the whole source, so the result always matches a full parse.
+///
+/// Expression IDs are unique, but after an incremental edit they aren't the IDs a full parse would assign.
+pub struct IncrementalParser {
+    source: String,
+    declarations: Vec<ParsedDeclaration>,
+    /// The last expression ID handed out, so re-parsed declarations never reuse an ID
+    last_id: u32,
+    /// How many declarations the most recent parse re-parsed
+    pub reparsed: usize
+}
+
+impl IncrementalParser {
+    pub fn new(source: String) -> Result<Self, ReparseError> {
+        let mut parser = IncrementalParser { source, declarations: Vec::new(), last_id: 0, reparsed: 0 };
+        parser.parse_all()?;
+        Ok(parser)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the parsed program.
+    pub fn program(&self) -> Program {
+        Program { declarations: self.declarations.iter().map(|parsed| parsed.declaration.clone()).collect() }
+    }
+
+    /// Returns where each expression in the program starts in the current source.
+    pub fn spans(&self) -> HashMap<ExpressionId, Span> {
+        let mut spans = HashMap::new();
+        for parsed in &self.declarations {
+            let start = position_of(&self.source, parsed.range.start);
+            spans.extend(parsed.spans.iter().map(|(id, span)| (*id, offset_span(*span, start))));
+        }
+        spans
+    }
+
+    /// Replaces the bytes in `range` with `replacement` and updates the program to match.
+    /// If the new source doesn't parse, the errors are returned and the next edit parses the whole source again.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) -> Result<(), ReparseError> {
+        self.source.replace_range(range.clone(), replacement);
+
+        // The first and last characters of the declaration have to survive the edit, or it could join up with its neighbors
+        let Some(index) = self.declarations.iter().position(|parsed| parsed.range.start < range.start && range.end < parsed.range.end) else {
+            return self.parse_all();
+        };
+
+        let old_range = self.declarations[index].range.clone();
+        let new_range = old_range.start..(old_range.end + replacement.len() - range.len());
+        let Some((declaration, spans)) = self.parse_one(new_range.clone()) else {
+            return self.parse_all();
+        };
+        self.declarations[index] = ParsedDeclaration { range: new_range.clone(), declaration, spans };
+
+        for parsed in &mut self.declarations[index + 1..] {
+            parsed.range = (parsed.range.start + new_range.end - old_range.end)..(parsed.range.end + new_range.end - old_range.end);
+        }
+        self.reparsed = 1;
+        Ok(())
+    }
+
+    /// Parses a slice of the source on its own, returning None unless it's exactly one declaration.
+    fn parse_one(&mut self, range: Range<usize>) -> Option<(Declaration, HashMap<ExpressionId, Span>)> {
+        let mut tokenizer = Tokenizer::new(self.source[range].to_string());
+        let tokens = tokenizer.tokenize().ok()?;
+        let mut parser = Parser::new(tokens);
+        parser.current_expr_id = self.last_id;
+
+        let declaration = parser.parse_declaration().ok()?;
+        if !parser.is_eof() {
+            return None;
+        }
+        self.last_id = parser.current_expr_id;
+        // The slice was tokenized from its first character, so its spans are already relative to the declaration
+        Some((declaration, parser.take_spans()))
+    }
+
+    fn parse_all(&mut self) -> Result<(), ReparseError> {
+        self.declarations.clear();
+        self.reparsed = 0;
+
+        let mut tokenizer = Tokenizer::new(self.source.clone());
+        let tokens = tokenizer.tokenize().map_err(ReparseError::Tokenize)?;
+        let mut parser = Parser::new(tokens);
+        parser.current_expr_id = self.last_id;
+
+        while !parser.is_eof() {
+            let first = parser.current;
+            let first_id = parser.current_expr_id;
+            let Ok(declaration) = parser.parse_declaration() else {
+                // Parse everything again to collect every error, like a normal parse would
+                let mut parser = Parser::new(tokens);
+                parser.parse_program();
+                return Err(ReparseError::Parse(std::mem::take(&mut parser.errors)));
+            };
+
+            let (first, last) = (&tokens[first], &tokens[parser.current - 1]);
+            let range = offset_of(&self.source, first)..(offset_of(&self.source, last) + last.token_type.reverse_format().len());
+            // IDs are handed out in order, so the declaration's spans are the ones recorded since it started
+            let start = Span { line: first.line, column: first.column };
+            let spans = (first_id + 1..=parser.current_expr_id)
+                .filter_map(|id| parser.spans.get(&ExpressionId(id)).map(|span| (ExpressionId(id), relative_span(*span, start))))
+                .collect();
+            self.declarations.push(ParsedDeclaration { range, declaration, spans });
+        }
+        self.last_id = parser.current_expr_id;
+        self.reparsed = self.declarations.len();
+        Ok(())
+    }
+}
+
+/// Makes a span relative to the start of the declaration it's in.
+fn relative_span(span: Span, start: Span) -> Span {
+    let column = if span.line == start.line { span.column - start.column + 1 } else { span.column };
+    Span { line: span.line - start.line + 1, column }
+}
+
+/// Turns a span relative to a declaration back into one relative to the whole source.
+fn offset_span(span: Span, start: Span) -> Span {
+    let column = if span.line == 1 { span.column + start.column - 1 } else { span.column };
+    Span { line: span.line + start.line - 1, column }
+}
+
+/// Finds the line and column of a byte offset into the source.
+fn position_of(source: &str, offset: usize) -> Span {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |newline| newline + 1)..].chars().count() + 1;
+    Span { line, column }
+}
+
+/// Finds the byte offset of a token in the source.
+fn offset_of(source: &str, token: &Token) -> usize {
+    let line_start: usize = source.split_inclusive('\n').take(token.line - 1).map(str::len).sum();
+    line_start + source[line_start..].chars().take(token.column - 1).map(char::len_utf8).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::ast::ast_hash;
+
+    use super::*;
+
+    /// Parses the source from scratch, returning its hash and the sorted positions of its expressions.
+    fn full_parse(source: &str) -> (u64, Vec<Span>) {
+        let mut tokenizer = Tokenizer::new(source.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program().unwrap();
+        let mut spans: Vec<Span> = parser.take_spans().into_values().collect();
+        spans.sort();
+        (ast_hash(&program), spans)
+    }
+
+    fn incremental_parse(parser: &IncrementalParser) -> (u64, Vec<Span>) {
+        let mut spans: Vec<Span> = parser.spans().into_values().collect();
+        spans.sort();
+        (ast_hash(&parser.program()), spans)
+    }
+
+    #[test]
+    fn test_matches_full_reparse() {
+        let source = "func one() -> i32 { 1 }\nfunc two() -> i32 {\n    let x: i32 = 2;\n    x\n}\nfunc three() -> i32 { two() + 1 }\n";
+        let mut parser = IncrementalParser::new(source.to_string()).unwrap();
+        assert_eq!(parser.reparsed, 3);
+        assert_eq!(incremental_parse(&parser), full_parse(source));
+
+        // Inside `two`, adding a line, so everything after it moves down
+        let offset = source.find("2;").unwrap();
+        parser.edit(offset..offset + 2, "2 * 3;\n    x += 1;").unwrap();
+        assert_eq!(parser.reparsed, 1);
+        assert_eq!(incremental_parse(&parser), full_parse(parser.source()));
+
+        // On the same line as the start of `three`
+        let offset = parser.source().find("two() + 1").unwrap();
+        parser.edit(offset + 8..offset + 9, "10").unwrap();
+        assert_eq!(parser.reparsed, 1);
+        assert_eq!(incremental_parse(&parser), full_parse(parser.source()));
+
+        // Between declarations
+        let offset = parser.source().find("func two").unwrap();
+        parser.edit(offset..offset, "func four() -> i32 { 4 }\n").unwrap();
+        assert_eq!(parser.reparsed, 4);
+        assert_eq!(incremental_parse(&parser), full_parse(parser.source()));
+    }
+
+    #[test]
+    fn test_edit_errors() {
+        let source = "func one() -> i32 { 1 }\nfunc two() -> i32 { 2 }\n";
+        let mut parser = IncrementalParser::new(source.to_string()).unwrap();
+
+        // Turning the rest of the file into a comment can't be handled by re-parsing only `one`
+        parser.edit(20..20, "/*").unwrap_err();
+        assert!(parser.program().declarations.is_empty());
+        parser.edit(20..22, "").unwrap();
+        assert_eq!(parser.reparsed, 2);
+        assert_eq!(incremental_parse(&parser), full_parse(source));
+
+        // A declaration that now ends early leaves tokens over, so the whole file is parsed instead
+        parser.edit(22..22, "} func three() -> i32 { 3").unwrap();
+        assert_eq!(parser.reparsed, 3);
+        assert_eq!(incremental_parse(&parser), full_parse(parser.source()));
+    }
+}
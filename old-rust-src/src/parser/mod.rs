@@ -1,9 +1,15 @@
-use ast::{BinaryOperator, Declaration, Expression, ExpressionId, FunctionParameter, LoopType, Program, Statement, StructElement, Type, UnaryOperator, VariableMutability};
+use std::{collections::HashMap, rc::Rc};
 
-use crate::tokenizer::{Token, TokenType};
+use docs::Doc;
+use ast::{BinaryOperator, Declaration, EnumVariant, Expression, ExpressionId, FunctionParameter, InterfaceMethod, LoopType, MatchArm, Pattern, Program, Span, Statement, StructElement, Type, UnaryOperator, VariableMutability};
+
+use crate::{stack::{StackLimit, DEFAULT_MAX_STACK}, symbol::Symbol, tokenizer::{Comment, Token, TokenType}};
 
 pub mod ast;
 pub mod ast_printer;
+pub mod docs;
+pub mod incremental;
+pub mod visitor;
 
 #[derive(Debug, PartialEq)]
 pub enum ParseError {
@@ -15,114 +21,282 @@ pub enum ParseError {
     UnexpectedEndOfInput
 }
 
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl ParseError {
+    /// Describes the error without its position.
+    pub fn message(&self) -> String {
         match self {
             ParseError::UnexpectedToken { expected, found, message } => {
                 let message = message.as_ref().map(|s| s.as_str()).unwrap_or("");
                 if let Some(expected) = expected {
-                    write!(f, "Expected {:?}, found {:?}. {}. | ", expected, found.token_type, message)?;
-                    write!(f, "file:{}:{}", found.line, found.column)
+                    format!("Expected {:?}, found {:?}. {}.", expected, found.token_type, message)
                 } else {
-                    write!(f, "Unexpected token: {:?}. {}", found.token_type, message)
+                    format!("Unexpected token: {:?}. {}", found.token_type, message)
                 }
             },
-            ParseError::UnexpectedEndOfInput => {
-                write!(f, "Unexpected end of input")
-            }
+            ParseError::UnexpectedEndOfInput => "Unexpected end of input".to_string()
+        }
+    }
+
+    /// Returns the token the error is about, if there is one.
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            ParseError::UnexpectedToken { found, .. } => Some(found),
+            ParseError::UnexpectedEndOfInput => None
         }
     }
 }
 
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())?;
+        if let ParseError::UnexpectedToken { expected: Some(_), found, .. } = self {
+            write!(f, " | file:{}:{}", found.line, found.column)?;
+        }
+        Ok(())
+    }
+}
+
+/// How many tokens past the current one are pulled from the token stream before they're consumed,
+/// which is as far as `peek_at` can look ahead.
+const LOOKAHEAD: usize = 8;
+
 pub struct Parser<'a> {
-    tokens: &'a [Token],
+    /// The tokens pulled from `stream` so far. Consumed tokens are kept, since docs look back at them by index.
+    tokens: Vec<Token>,
+    /// Where the rest of the tokens come from, pulled as the parser reaches them
+    stream: Box<dyn Iterator<Item = Token> + 'a>,
     current: usize,
     errors: Vec<ParseError>,
     /// The current expression ID. This is used to uniquely identify expressions in the AST.
     /// It is incremented each time a new expression is created.
-    current_expr_id: u32
+    current_expr_id: u32,
+    /// Where each expression with an ID starts, for error reporting.
+    spans: HashMap<ExpressionId, Span>,
+    /// How many expressions and types are currently being parsed inside each other
+    depth: usize,
+    max_depth: usize,
+    stack: StackLimit,
+    /// Returned by `peek` past the last token, so looking ahead never goes out of bounds
+    eof: Token,
+    /// The comments from the tokenizer, for attaching docs to declarations
+    comments: Vec<Comment>,
+    docs: Vec<Doc>,
+    /// The names of the structs currently being parsed, to qualify the names of their members' docs
+    doc_path: Vec<String>
 }
 
+/// The default limit on expression nesting. The stack limit (see `set_max_stack`) is what keeps deeply nested input
+/// from overflowing the stack, since how deep that is depends on the build.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
 macro_rules! parse_precedence_binary {
     ($self:ident, $next_level:ident, $( ($token_type:path, $operator:expr) ),+ $(,)?) => {
         {
             let mut expr = $self.$next_level()?;
-            while !$self.is_eof() && let Some(operator) = match $self.peek().token_type.clone() {
-                $(
-                    $token_type => Some($operator),
-                )+
-                _ => None
-            } {
+            let depth = $self.depth;
+            let result = loop {
+                if $self.is_eof() {
+                    break Ok(expr);
+                }
+                let operator = match $self.peek_type() {
+                    $(
+                        $token_type => $operator,
+                    )+
+                    _ => break Ok(expr)
+                };
                 $self.advance(); // Consume the operator
+                let expression_id = $self.get_id(); // Errors point at the operator
 
-                let right = Box::new($self.$next_level()?);
+                // Each operator nests everything before it one level deeper, so long chains count toward the limit
+                $self.depth += 1;
+                let right = match $self.nested(Self::$next_level) {
+                    Ok(right) => Box::new(right),
+                    Err(error) => break Err(error)
+                };
                 expr = Expression::BinaryOperation {
                     left: Box::new(expr),
                     operator,
-                    right
+                    right,
+                    expression_id
                 };
-            }
-            Ok(expr)
+            };
+            $self.depth = depth;
+            result
         }
     };
 }
 
 macro_rules! parse_precedence_unary {
-    ($self:ident, $next_level:ident, $( ($token_type:path, $operator:expr) ),+ $(,)?) => {
+    ($self:ident, $this_level:ident, $next_level:ident, $( ($token_type:path, $operator:expr) ),+ $(,)?) => {
         {
-            let mut expr: Option<Expression> = None;
-            while !$self.is_eof() && let Some(operator) = match $self.peek().token_type.clone() {
+            let operator = match $self.peek_type() {
                 $(
-                    $token_type => Some($operator),
+                    $token_type => $operator,
                 )+
-                _ => None
-            } {
-                $self.advance(); // Consume the operator
-
-                let right = Box::new($self.$next_level()?);
-                expr = Some(Expression::UnaryOperation {
-                    operator,
-                    operand: right
-                });
-            }
-            if let Some(expr) = expr {
-                Ok(expr)
-            } else {
-                $self.$next_level()
-            }
+                _ => return $self.$next_level()
+            };
+            $self.advance(); // Consume the operator
+            let expression_id = $self.get_id(); // Errors point at the operator
+
+            // Unary operators can be repeated, like `- -x`, so the operand can be another unary operation
+            let operand = Box::new($self.nested(Self::$this_level)?);
+            Ok(Expression::UnaryOperation {
+                operator,
+                operand,
+                expression_id
+            })
         }
     };
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a[Token]) -> Self {
-        Parser {
-            tokens,
+    pub fn new(tokens: &'a [Token]) -> Self {
+        Self::from_stream(tokens.iter().cloned())
+    }
+
+    /// Creates a parser that pulls tokens on demand instead of needing all of them up front,
+    /// like from a `Tokenizer` used as an iterator.
+    pub fn from_stream(tokens: impl Iterator<Item = Token> + 'a) -> Self {
+        let mut parser = Parser {
+            tokens: Vec::new(),
+            stream: Box::new(tokens.fuse()),
             current: 0,
             errors: Vec::new(),
-            current_expr_id: 0
+            current_expr_id: 0,
+            spans: HashMap::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            stack: StackLimit::new(DEFAULT_MAX_STACK),
+            eof: Token { token_type: TokenType::EndOfFile, line: 1, column: 1 },
+            comments: Vec::new(),
+            docs: Vec::new(),
+            doc_path: Vec::new()
+        };
+        parser.fill(LOOKAHEAD);
+        parser
+    }
+
+    /// Pulls tokens from the stream until there are `count` from the current one on, or the stream runs out.
+    fn fill(&mut self, count: usize) {
+        while self.tokens.len() < self.current + count {
+            let Some(token) = self.stream.next() else {
+                // The end of input is reported at the last token, like the tokenizer's own EndOfFile
+                if let Some(last) = self.tokens.last() {
+                    (self.eof.line, self.eof.column) = (last.line, last.column);
+                }
+                break;
+            };
+            self.tokens.push(token);
         }
     }
 
+    /// Sets how deeply expressions can be nested before parsing fails.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Sets how many bytes of stack parsing can use before it fails, which is `DEFAULT_MAX_STACK` by default.
+    /// Parsing should run on a thread with more stack than this.
+    pub fn set_max_stack(&mut self, max_stack: usize) {
+        self.stack.set_max(max_stack);
+    }
+
+    /// Makes expression IDs continue after `last_id`, so this program doesn't reuse the IDs of one parsed before it,
+    /// like the prelude it's loaded with.
+    pub fn start_ids_after(&mut self, last_id: u32) {
+        self.current_expr_id = last_id;
+    }
+
+    /// Returns the last expression ID created so far.
+    pub fn last_id(&self) -> u32 {
+        self.current_expr_id
+    }
+
+    /// Creates a new expression ID, recording the position of the most recently consumed token as its span.
     pub fn get_id(&mut self) -> ExpressionId {
         self.current_expr_id += 1;
-        return ExpressionId(self.current_expr_id);
+        let id = ExpressionId(self.current_expr_id);
+        if let Some(token) = self.current.checked_sub(1).and_then(|index| self.tokens.get(index)) {
+            self.spans.insert(id, Span { line: token.line, column: token.column });
+        }
+        id
+    }
+
+    /// Creates a new expression ID for an expression synthesized from another, giving it the same span.
+    fn get_id_at(&mut self, original: ExpressionId) -> ExpressionId {
+        self.current_expr_id += 1;
+        let id = ExpressionId(self.current_expr_id);
+        if let Some(span) = self.spans.get(&original).copied() {
+            self.spans.insert(id, span);
+        }
+        id
+    }
+
+    /// Takes the spans recorded for every expression ID created so far.
+    pub fn take_spans(&mut self) -> HashMap<ExpressionId, Span> {
+        std::mem::take(&mut self.spans)
     }
 
     fn is_eof(&self) -> bool {
         self.current >= self.tokens.len()
     }
 
+    /// Returns the current token, or an `EndOfFile` token if all of them have been consumed.
     fn peek(&self) -> &Token {
-        &self.tokens[self.current]
+        self.tokens.get(self.current).unwrap_or(&self.eof)
+    }
+
+    /// Returns the type of the current token. Match on this (or on `*self.peek_type()` to copy out fields)
+    /// instead of cloning the token, which allocates for variants that carry strings.
+    fn peek_type(&self) -> &TokenType {
+        &self.peek().token_type
+    }
+
+    /// Returns the token `offset` tokens past the current one, or None if that's past the end of the input.
+    /// Only tokens less than `LOOKAHEAD` past the current one have been pulled from the stream.
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.current + offset)
+    }
+
+    /// Returns true if the token `offset` tokens past the current one has the given type.
+    fn check_ahead(&self, offset: usize, token_type: TokenType) -> bool {
+        self.peek_at(offset).is_some_and(|token| token.token_type == token_type)
     }
 
     fn is_match(&self, token_type: TokenType) -> bool {
-        !self.is_eof() && self.peek().token_type == token_type
+        self.check_ahead(0, token_type)
+    }
+
+    /// Returns true if the token `offset` tokens past the current one is the given contextual keyword.
+    /// Contextual keywords are tokenized as identifiers and only act as keywords where the parser looks for them,
+    /// so adding one doesn't break programs that already use it as a name.
+    fn is_contextual_keyword(&self, offset: usize, keyword: &str) -> bool {
+        self.peek_at(offset).is_some_and(|token| matches!(&token.token_type, TokenType::Identifier(name) if name.as_str() == keyword))
+    }
+
+    /// Consumes the contextual keyword if it's the current token, returning whether it was.
+    fn advance_if_contextual(&mut self, keyword: &str) -> bool {
+        let found = self.is_contextual_keyword(0, keyword);
+        if found {
+            self.advance(); // Consume the keyword
+        }
+        found
+    }
+
+    /// Returns true if the current token starts a declaration with the given contextual keyword: the keyword followed
+    /// by a name, like `type Count = i32;` or `enum Color { ... }`.
+    fn is_declaration(&self, keyword: &str) -> bool {
+        self.is_contextual_keyword(0, keyword) && matches!(self.peek_at(1).map(|token| &token.token_type), Some(TokenType::Identifier(_)))
+    }
+
+    /// Returns true if the current token starts a type alias, enum, or interface declaration.
+    fn is_contextual_declaration(&self) -> bool {
+        ["type", "enum", "interface"].iter().any(|keyword| self.is_declaration(keyword))
     }
 
     fn advance(&mut self) {
         self.current += 1;
+        self.fill(LOOKAHEAD);
     }
 
     fn advance_if(&mut self, token_type: TokenType) -> bool {
@@ -134,6 +308,18 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Creates an error for the current token, or an end-of-input error if there are no tokens left.
+    fn unexpected_token(&self, expected: Option<TokenType>, message: &str) -> ParseError {
+        if self.is_eof() {
+            return ParseError::UnexpectedEndOfInput;
+        }
+        ParseError::UnexpectedToken {
+            expected,
+            found: self.peek().clone(),
+            message: Some(message.to_string())
+        }
+    }
+
     /// Synchronizes the parser by skipping tokens until it finds a semicolon or EOF.
     /// This is useful for error recovery.
     fn synchronize(&mut self) {
@@ -148,7 +334,6 @@ impl<'a> Parser<'a> {
                 TokenType::FunctionKeyword |
                 TokenType::ImportKeyword | 
                 TokenType::StructKeyword |
-                TokenType::TypeKeyword |
                 TokenType::LetKeyword |
                 TokenType::ConstKeyword |
                 TokenType::LoopKeyword |
@@ -161,6 +346,7 @@ impl<'a> Parser<'a> {
                 => {
                     break; // Stop at the next function or import keyword
                 },
+                _ if self.is_contextual_declaration() => break,
                 _ => {}
             }
 
@@ -168,7 +354,45 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Parses the entire program and returns a Program object. If parsing fails, it returns None.
+    /// Skips the rest of a statement in a block that failed to parse, stopping after its semicolon or before the next
+    /// statement-starting keyword. Braces, brackets, and parentheses inside the statement are skipped along with it,
+    /// and the block's own closing brace is left alone so the block still ends where it should.
+    fn synchronize_statement(&mut self) {
+        let start = self.current;
+        let mut depth = 0usize;
+
+        while !self.is_eof() {
+            match self.peek().token_type {
+                TokenType::OpenCurlyBracket | TokenType::OpenParenthesis | TokenType::OpenSquareBracket => depth += 1,
+                TokenType::CloseCurlyBracket if depth == 0 => break,
+                // A bracket opened before the error was part of the statement too
+                TokenType::CloseCurlyBracket | TokenType::CloseParenthesis | TokenType::CloseSquareBracket => depth = depth.saturating_sub(1),
+                TokenType::Semicolon if depth == 0 => {
+                    self.advance(); // Consume the semicolon
+                    break;
+                },
+                TokenType::LetKeyword |
+                TokenType::ConstKeyword |
+                TokenType::ReturnKeyword |
+                TokenType::BreakKeyword |
+                TokenType::ContinueKeyword |
+                TokenType::LoopKeyword |
+                TokenType::IfKeyword |
+                TokenType::FunctionKeyword |
+                TokenType::StructKeyword |
+                TokenType::ImportKeyword
+                // The statement has to be skipped past at least one token, or the same error would be found again
+                if depth == 0 && self.current > start => break,
+                _ if depth == 0 && self.current > start && self.is_contextual_declaration() => break,
+                _ => {}
+            }
+
+            self.advance(); // Consume the token
+        }
+    }
+
+    /// Parses the entire program and returns a Program object.
+    /// If parsing fails, it returns None, and the errors are available from `errors`.
     pub fn parse_program(&mut self) -> Option<Program> {
         let mut declarations = Vec::new();
         self.errors.clear(); // Clear previous errors
@@ -188,38 +412,60 @@ impl<'a> Parser<'a> {
         debug_assert!(self.is_eof(), "Unexpected end of input");
 
         if !self.errors.is_empty() {
-            for error in &self.errors {
-                eprintln!("Error: {}", error);
-            }
             return None; // Return None if there were errors
         }
 
         Some(Program { declarations })
     }
 
+    /// Returns the errors from the last call to `parse_program`.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// Parses a standalone expression that must span the entire input, e.g. a snippet evaluated by an embedder.
+    /// Unlike `parse_program`, errors are returned instead of printed.
+    pub fn parse_snippet(&mut self) -> Result<Expression, Vec<ParseError>> {
+        self.errors.clear(); // Clear previous errors
+
+        if self.is_eof() {
+            return Err(vec![ParseError::UnexpectedEndOfInput]);
+        }
+
+        let expression = match self.parse_expression() {
+            Ok(expression) => Some(expression),
+            Err(e) => {
+                self.errors.push(e);
+                None
+            }
+        };
+
+        if self.errors.is_empty() && !self.is_eof() {
+            self.errors.push(self.unexpected_token(None, "Expected the end of the input"));
+        }
+
+        match expression {
+            Some(expression) if self.errors.is_empty() => Ok(expression),
+            _ => Err(std::mem::take(&mut self.errors))
+        }
+    }
+
     fn expect_identifier(&mut self) -> Result<String, ParseError> {
-        match self.peek().token_type.clone() {
-            TokenType::Identifier(ref name) => {
+        match *self.peek_type() {
+            TokenType::Identifier(name) => {
                 self.advance(); // Consume the identifier
-                Ok(name.clone())
+                Ok(name.to_string())
             },
-            _ => Err(ParseError::UnexpectedToken {
-                expected: Some(TokenType::Identifier("".to_string())),
-                found: self.peek().clone(),
-                message: Some("Expected an identifier".to_string())
-            })
+            _ => Err(self.unexpected_token(Some(TokenType::Identifier(Symbol::intern(""))), "Expected an identifier"))
         }
     }
 
     fn expect(&mut self, token_type: TokenType, message: &str) -> Result<(), ParseError> {
-        if self.advance_if(token_type.clone()) {
+        if self.peek_type() == &token_type {
+            self.advance(); // Consume the token
             Ok(())
         } else {
-            Err(ParseError::UnexpectedToken {
-                expected: Some(token_type),
-                found: self.peek().clone(),
-                message: Some(message.to_string())
-            })
+            Err(self.unexpected_token(Some(token_type), message))
         }
     }
 
@@ -245,15 +491,21 @@ impl<'a> Parser<'a> {
         Ok(params)
     }
 
+    /// Parses a top-level declaration. Unlike in blocks, variables declared here are global.
     pub(crate) fn parse_declaration(&mut self) -> Result<Declaration, ParseError> {
+        // `const func` is a function, not a constant
+        if self.is_match(TokenType::LetKeyword) || (self.is_match(TokenType::ConstKeyword) && !self.check_ahead(1, TokenType::FunctionKeyword)) {
+            let start = self.current;
+            let (mutability, name, variable_type, value, _) = self.parse_variable_declaration()?;
+            let declaration = Declaration::GlobalVariable { mutability, name, variable_type, value };
+            self.record_declaration_docs(&declaration, start);
+            return Ok(declaration);
+        }
+
         if let Some(decl) = self.try_parse_declaration()? {
             Ok(decl)
         } else {
-            Err(ParseError::UnexpectedToken {
-                expected: None,
-                found: self.peek().clone(),
-                message: Some("Expected a function, struct, type, or import declaration".to_string())
-            })
+            Err(self.unexpected_token(None, "Expected a function, struct, enum, type, import, or variable declaration"))
         }
     }
 
@@ -296,18 +548,33 @@ impl<'a> Parser<'a> {
     }
 
     fn try_parse_declaration(&mut self) -> Result<Option<Declaration>, ParseError> {
+        let start = self.current;
+        let doc_depth = self.doc_path.len();
+        let declaration = self.try_parse_undocumented_declaration();
+        self.doc_path.truncate(doc_depth); // In case parsing a struct failed partway through
+        if let Ok(Some(declaration)) = &declaration {
+            self.record_declaration_docs(declaration, start);
+        }
+        declaration
+    }
+
+    fn try_parse_undocumented_declaration(&mut self) -> Result<Option<Declaration>, ParseError> {
+        let is_const = self.is_match(TokenType::ConstKeyword) && self.check_ahead(1, TokenType::FunctionKeyword);
+        if is_const {
+            self.advance(); // Consume 'const'
+        }
         if self.advance_if(TokenType::FunctionKeyword) {
             let name = self.expect_identifier()?;
             let generic_args = self.parse_generic_args()?;
             let params = self.parse_function_parameters()?;
-            self.expect(TokenType::Arrow, "Expected arrow after function parameters for type")?;
-            let return_type = self.parse_type()?;
+            let return_type = self.parse_return_type()?;
             let body = self.parse_block()?;
-            Ok(Some(Declaration::Function { name, params, return_type, generic_args, body: Box::new(body) }))
+            Ok(Some(Declaration::Function { name, params, return_type, generic_args, body: Rc::new(body), is_const }))
         } else if self.advance_if(TokenType::ImportKeyword) {
             let mut path = vec![
                 self.expect_identifier()? // Expect the first part of the path
             ];
+            let expression_id = self.get_id();
 
             while !self.is_eof() {
                 if self.advance_if(TokenType::Dot) {
@@ -319,19 +586,70 @@ impl<'a> Parser<'a> {
 
             self.expect(TokenType::Semicolon, "Expected semicolon after import path")?; // Expect a semicolon
 
-            Ok(Some(Declaration::Import { path }))
+            Ok(Some(Declaration::Import { path, expression_id }))
         } else if self.advance_if(TokenType::StructKeyword) {
             let name = self.expect_identifier()?;
             let generic_args = self.parse_generic_args()?;
+            let mut interfaces = Vec::new();
+            if self.advance_if(TokenType::Colon) {
+                loop {
+                    interfaces.push(self.expect_identifier()?);
+                    if !self.advance_if(TokenType::Comma) {
+                        break; // No more interfaces
+                    }
+                }
+            }
             self.expect(TokenType::OpenCurlyBracket, "Expected open brace after struct name")?;
+            self.doc_path.push(name.clone());
             let mut declarations = Vec::new();
             while !self.is_eof() && self.peek().token_type != TokenType::CloseCurlyBracket {
                 let decl = self.parse_struct_element()?;
                 declarations.push(decl);
             }
             self.expect(TokenType::CloseCurlyBracket, "Unmatched open brace")?;
-            Ok(Some(Declaration::Struct { name, elements: declarations, generic_args }))
-        } else if self.advance_if(TokenType::TypeKeyword) {
+            Ok(Some(Declaration::Struct { name, elements: declarations, generic_args, interfaces }))
+        } else if self.is_declaration("enum") {
+            self.advance(); // Consume the `enum` keyword
+            let name = self.expect_identifier()?;
+            let generic_args = self.parse_generic_args()?;
+            self.expect(TokenType::OpenCurlyBracket, "Expected open brace after enum name")?;
+            let mut variants = Vec::new();
+            while !self.is_eof() && self.peek().token_type != TokenType::CloseCurlyBracket {
+                let variant = self.expect_identifier()?;
+                let mut fields = Vec::new();
+                if self.advance_if(TokenType::OpenParenthesis) {
+                    while !self.is_eof() && self.peek().token_type != TokenType::CloseParenthesis {
+                        fields.push(self.parse_type()?);
+                        if !self.advance_if(TokenType::Comma) {
+                            break; // No more fields
+                        }
+                    }
+                    self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?;
+                }
+                variants.push(EnumVariant { name: variant, fields });
+                if !self.advance_if(TokenType::Comma) {
+                    break; // No more variants
+                }
+            }
+            self.expect(TokenType::CloseCurlyBracket, "Unmatched open brace")?;
+            Ok(Some(Declaration::Enum { name, variants, generic_args }))
+        } else if self.is_declaration("interface") {
+            self.advance(); // Consume the `interface` keyword
+            let name = self.expect_identifier()?;
+            self.expect(TokenType::OpenCurlyBracket, "Expected open brace after interface name")?;
+            let mut methods = Vec::new();
+            while !self.is_eof() && self.peek().token_type != TokenType::CloseCurlyBracket {
+                self.expect(TokenType::FunctionKeyword, "Expected a method in interface")?;
+                let method = self.expect_identifier()?;
+                let params = self.parse_function_parameters()?;
+                let return_type = self.parse_return_type()?;
+                self.expect(TokenType::Semicolon, "Expected semicolon after interface method")?;
+                methods.push(InterfaceMethod { name: method, params, return_type });
+            }
+            self.expect(TokenType::CloseCurlyBracket, "Unmatched open brace")?;
+            Ok(Some(Declaration::Interface { name, methods }))
+        } else if self.is_declaration("type") {
+            self.advance(); // Consume the `type` keyword
             let name = self.expect_identifier()?;
             let generic_args = self.parse_generic_args()?;
             self.expect(TokenType::AssignmentOperator, "Expected assignment operator after type name")?; // Expect an assignment operator
@@ -348,17 +666,36 @@ impl<'a> Parser<'a> {
             return Ok(StructElement::Declaration(decl)); // Parse a declaration
         }
 
+        let start = self.current;
         let name = self.expect_identifier()?;
         self.expect(TokenType::Colon, "Expected colon after struct field name")?; // Expect a colon after the name
         let field_type = self.parse_type()?;
         self.expect(TokenType::Semicolon, "Expected semicolon after struct field declaration")?; // Expect a semicolon
+        self.record_docs("field", &name, start);
 
         Ok(StructElement::Field { name, field_type })
     }
 
+    /// Parses the `-> Type` after a function's parameters. Functions without one return nil.
+    fn parse_return_type(&mut self) -> Result<Type, ParseError> {
+        if self.advance_if(TokenType::Arrow) {
+            self.parse_type()
+        } else {
+            Ok(Type::Nil)
+        }
+    }
+
     fn parse_type(&mut self) -> Result<Type, ParseError> {
-        match self.peek().token_type.clone() {
-            TokenType::Identifier(ref name) => {
+        let parsed_type = self.nested(Self::parse_non_optional_type)?;
+        if self.advance_if(TokenType::QuestionMark) {
+            return Ok(Type::Optional(Box::new(parsed_type)));
+        }
+        Ok(parsed_type)
+    }
+
+    fn parse_non_optional_type(&mut self) -> Result<Type, ParseError> {
+        match *self.peek_type() {
+            TokenType::Identifier(name) => {
                 self.advance(); // Consume the identifier
                 match name.as_str() {
                     "u8" => Ok(Type::U8),
@@ -373,11 +710,12 @@ impl<'a> Parser<'a> {
                     "f64" => Ok(Type::F64),
                     "bool" => Ok(Type::Boolean),
                     "char" => Ok(Type::Character),
+                    "nil" => Ok(Type::Nil),
                     _ => {
                         // Custom types (structs, enums, etc.)
                         // We can't use parse_generic_args because it expects identifiers, while we need types.
                         let generics = self.parse_generics()?;
-                        Ok(Type::Identifier { name: name.clone(), generics })
+                        Ok(Type::Identifier { name: name.to_string(), generics })
                     }
                 }
             },
@@ -388,38 +726,93 @@ impl<'a> Parser<'a> {
                 self.expect(TokenType::CloseSquareBracket, "Unmatched open square bracket")?;
                 Ok(Type::Array(Box::new(element_type)))
             },
-            _ => Err(ParseError::UnexpectedToken {
-                expected: Some(TokenType::Identifier("".to_string())),
-                found: self.peek().clone(),
-                message: Some("Expected a type identifier".to_string())
-            })
+            TokenType::OpenParenthesis => {
+                // Tuples
+                self.advance();
+                let mut element_types = Vec::new();
+                while !self.is_eof() && self.peek().token_type != TokenType::CloseParenthesis {
+                    element_types.push(self.parse_type()?);
+                    if !self.advance_if(TokenType::Comma) {
+                        break; // No more elements
+                    }
+                }
+                self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?;
+                Ok(Type::Tuple(element_types))
+            },
+            TokenType::FunctionKeyword => {
+                // Functions, either `func(A, B) -> C` or `func A -> B`
+                self.advance();
+                let mut params = Vec::new();
+                if self.advance_if(TokenType::OpenParenthesis) {
+                    while !self.is_eof() && self.peek().token_type != TokenType::CloseParenthesis {
+                        params.push(self.parse_type()?);
+                        if !self.advance_if(TokenType::Comma) {
+                            break; // No more parameters
+                        }
+                    }
+                    self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?;
+                } else {
+                    params.push(self.parse_type()?);
+                }
+                self.expect(TokenType::Arrow, "Expected arrow after function type parameters")?;
+                let return_type = Box::new(self.parse_type()?);
+                Ok(Type::Function { params, return_type })
+            },
+            _ => Err(self.unexpected_token(Some(TokenType::Identifier(Symbol::intern(""))), "Expected a type identifier"))
         }
     }
 
     pub(crate) fn parse_block(&mut self) -> Result<Expression, ParseError> {
         self.expect(TokenType::OpenCurlyBracket, "Expected open brace")?;
+        let statements = self.parse_block_body()?;
+        Ok(Expression::Block(statements))
+    }
+
+    /// Parses the statements of a block up to and including its closing brace.
+    fn parse_block_body(&mut self) -> Result<Vec<Statement>, ParseError> {
         let mut statements = Vec::new();
         while !self.is_eof() && self.peek().token_type != TokenType::CloseCurlyBracket {
             let stmt = match self.parse_statement() {
                 Ok(stmt) => stmt,
+                // There's nothing left to recover with, and the missing brace would just be reported again
+                Err(ParseError::UnexpectedEndOfInput) => return Err(ParseError::UnexpectedEndOfInput),
                 Err(e) => {
                     self.errors.push(e); // Store the error
-                    self.synchronize(); // Skip to the next statement
+                    self.synchronize_statement(); // Skip to the next statement in this block
                     continue; // Try to parse the next statement
                 }
             };
 
-            let is_result_expression = match stmt {
-                Statement::Expression { result: true, .. } => true,
-                _ => false
-            };
+            let is_result_expression = matches!(stmt, Statement::Expression { result: true, .. });
             statements.push(stmt);
             if is_result_expression {
                 break;
             }
         }
         self.expect(TokenType::CloseCurlyBracket, "Unmatched open brace")?;
-        Ok(Expression::Block(statements))
+        Ok(statements)
+    }
+
+    /// Parses a lambda, written as a block with an optional list of parameters between pipes: `{ |a, b| a + b }`.
+    fn parse_lambda(&mut self) -> Result<Expression, ParseError> {
+        self.expect(TokenType::OpenCurlyBracket, "Expected open brace")?;
+
+        let mut params = Vec::new();
+        if self.advance_if(TokenType::Pipe) {
+            while !self.is_eof() && self.peek().token_type != TokenType::Pipe {
+                params.push(self.expect_identifier()?);
+                if !self.advance_if(TokenType::Comma) {
+                    break; // No more parameters
+                }
+            }
+            self.expect(TokenType::Pipe, "Unmatched lambda parameter list")?;
+        } else {
+            // `||` is tokenized as a logical or, but here it's an empty parameter list
+            self.advance_if(TokenType::OrOperator);
+        }
+
+        let statements = self.parse_block_body()?;
+        Ok(Expression::Lambda { params, body: Rc::new(Expression::Block(statements)) })
     }
 
     pub(crate) fn parse_statement(&mut self) -> Result<Statement, ParseError> {
@@ -427,7 +820,7 @@ impl<'a> Parser<'a> {
             return Ok(Statement::Declaration(decl)); // Parse a declaration
         }
 
-        match self.peek().token_type.clone() {
+        match self.peek_type() {
             // Easy single-keyword statements
             TokenType::BreakKeyword => {
                 // TODO: Breaking with values
@@ -443,19 +836,8 @@ impl<'a> Parser<'a> {
 
             // Variable declaration
             TokenType::LetKeyword | TokenType::ConstKeyword => {
-                let mutability = if self.is_match(TokenType::LetKeyword) {
-                    VariableMutability::Mutable
-                } else {
-                    VariableMutability::Immutable
-                };
-                self.advance(); // Consume 'let' or 'const'
-                let name = self.expect_identifier()?;
-                self.expect(TokenType::Colon, "Expected colon after variable name")?; // Expect a colon after the name
-                let variable_type = self.parse_type()?;
-                self.expect(TokenType::AssignmentOperator, "Expected assignment operator after variable type")?; // Expect an assignment operator
-                let value = Box::new(self.parse_expression()?);
-                self.expect(TokenType::Semicolon, "Expected semicolon after variable declaration")?; // Expect a semicolon
-                Ok(Statement::VariableDeclaration { mutability, name, variable_type, value })
+                let (mutability, name, variable_type, value, expression_id) = self.parse_variable_declaration()?;
+                Ok(Statement::VariableDeclaration { mutability, name, variable_type, value, expression_id })
             },
 
             // Return
@@ -488,9 +870,61 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses a variable declaration like `let x: i32 = 5;`, starting at the `let` or `const`.
+    fn parse_variable_declaration(&mut self) -> Result<(VariableMutability, String, Type, Box<Expression>, ExpressionId), ParseError> {
+        let mutability = if self.is_match(TokenType::LetKeyword) {
+            VariableMutability::Mutable
+        } else {
+            VariableMutability::Immutable
+        };
+        self.advance(); // Consume 'let' or 'const'
+        let name = self.expect_identifier()?;
+        let expression_id = self.get_id(); // Points at the name
+        self.expect(TokenType::Colon, "Expected colon after variable name")?; // Expect a colon after the name
+        let variable_type = self.parse_type()?;
+        self.expect(TokenType::AssignmentOperator, "Expected assignment operator after variable type")?; // Expect an assignment operator
+        let value = Box::new(self.parse_expression()?);
+        self.expect(TokenType::Semicolon, "Expected semicolon after variable declaration")?; // Expect a semicolon
+        Ok((mutability, name, variable_type, value, expression_id))
+    }
+
     pub(crate) fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.nested(Self::parse_expression_or_lower)
+    }
+
+    /// Parses something nested inside what's currently being parsed, failing instead if the nesting is too deep
+    /// or is using too much of the stack.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        if self.depth == 0 {
+            self.stack.restart();
+        }
+        if self.depth >= self.max_depth || self.stack.exceeded() {
+            return Err(match self.peek_at(0) {
+                Some(token) => ParseError::UnexpectedToken {
+                    expected: None,
+                    found: token.clone(),
+                    message: Some("Expression nesting too deep".to_string())
+                },
+                None => ParseError::UnexpectedEndOfInput
+            });
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_expression_or_lower(&mut self) -> Result<Expression, ParseError> {
         // Blocks are expressions
         if self.is_match(TokenType::OpenCurlyBracket) {
+            // A block starting with a parameter list is a lambda
+            if self.check_ahead(1, TokenType::Pipe) || self.check_ahead(1, TokenType::OrOperator) {
+                return self.parse_lambda();
+            }
+            if self.is_map_literal() {
+                return self.parse_map_literal();
+            }
             return self.parse_block(); // Parse a block
         }
 
@@ -498,14 +932,25 @@ impl<'a> Parser<'a> {
         if self.advance_if(TokenType::LoopKeyword) {
             // If there's a set of parentheses, this is a while loop or iterator loop
             if self.advance_if(TokenType::OpenParenthesis) {
-                // If there's a let or const keyword, this is an iterator loop
-                if let Some(mutability) = match self.peek().token_type.clone() {
+                // If there's a let or const keyword, this is an iterator loop, or a while-let loop if the name is followed by `=`
+                if let Some(mutability) = match self.peek_type() {
                     TokenType::LetKeyword => Some(VariableMutability::Mutable),
                     TokenType::ConstKeyword => Some(VariableMutability::Immutable),
                     _ => None
                 } {
                     self.advance(); // Consume 'let' or 'const'
                     let iterator = self.expect_identifier()?;
+                    if self.advance_if(TokenType::AssignmentOperator) {
+                        let expr = Box::new(self.parse_expression()?);
+                        self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?; // Expect a close parenthesis
+                        let body = Box::new(self.parse_block()?);
+                        return Ok(Expression::Loop(LoopType::WhileLet {
+                            mutability,
+                            binding: iterator,
+                            expr,
+                            body
+                        }));
+                    }
                     self.expect(TokenType::Colon, "Expected colon after variable name")?; // Expect a colon after the name
                     let iterable = Box::new(self.parse_expression()?);
                     self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?; // Expect a close parenthesis
@@ -527,8 +972,18 @@ impl<'a> Parser<'a> {
                     body
                 }));
             } else {
-                // Otherwise, this is an infinite loop
+                // Otherwise, this is an infinite loop, unless there's a condition after the body
                 let body = Box::new(self.parse_block()?);
+                if self.is_contextual_keyword(0, "while") && self.check_ahead(1, TokenType::OpenParenthesis) {
+                    self.advance(); // Consume the `while` keyword
+                    self.expect(TokenType::OpenParenthesis, "Expected open parentheses after while")?; // Expect an open parenthesis
+                    let condition = Box::new(self.parse_expression()?);
+                    self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?; // Expect a close parenthesis
+                    return Ok(Expression::Loop(LoopType::DoWhile {
+                        body,
+                        condition
+                    }));
+                }
                 return Ok(Expression::Loop(LoopType::Infinite {
                     body
                 }));
@@ -542,8 +997,11 @@ impl<'a> Parser<'a> {
             self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?; // Expect a close parenthesis
             let body = Box::new(self.parse_expression()?);
 
-            // Optional semicolon after the if statement
-            self.advance_if(TokenType::Semicolon);
+            // Optional semicolon between the body and an else branch. Otherwise, a semicolon ends the if statement,
+            // and is left for the statement to consume.
+            if self.is_match(TokenType::Semicolon) && self.check_ahead(1, TokenType::ElseKeyword) {
+                self.advance(); // Consume the semicolon
+            }
 
             let else_branch = if self.advance_if(TokenType::ElseKeyword) {
                 Some(Box::new(self.parse_expression()?)) // Parse the else branch
@@ -558,8 +1016,30 @@ impl<'a> Parser<'a> {
             });
         }
 
-        if self.advance_if(TokenType::OpenSquareBracket) {
+        // `match` is only a keyword at the start of an expression and before its parentheses, so a variable named `match` still works
+        if self.is_contextual_keyword(0, "match") && self.check_ahead(1, TokenType::OpenParenthesis) {
+            self.advance(); // Consume the `match` keyword
+            self.expect(TokenType::OpenParenthesis, "Expected open parentheses after match")?; // Expect an open parenthesis
+            let value = Box::new(self.parse_expression()?);
+            self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?; // Expect a close parenthesis
+            self.expect(TokenType::OpenCurlyBracket, "Expected open brace after match value")?;
+            let mut arms = Vec::new();
+            while !self.is_eof() && self.peek().token_type != TokenType::CloseCurlyBracket {
+                let pattern = self.parse_pattern()?;
+                self.expect(TokenType::FatArrow, "Expected => after match pattern")?;
+                let body = Box::new(self.parse_expression()?);
+                arms.push(MatchArm { pattern, body });
+                if !self.advance_if(TokenType::Comma) {
+                    break; // No more arms
+                }
+            }
+            self.expect(TokenType::CloseCurlyBracket, "Unmatched open brace")?;
+            return Ok(Expression::Match { value, arms });
+        }
+
+        if self.is_typed_array_creation() {
             // Array creation
+            self.advance(); // Consume the open square bracket
             let element_type = self.parse_type()?;
             self.expect(TokenType::Comma, "Expected comma after array type")?;
             let size = Box::new(self.parse_expression()?);
@@ -599,27 +1079,152 @@ impl<'a> Parser<'a> {
         self.parse_assignment_or_lower()
     }
 
+    /// Parses a match pattern: `_`, or an enum variant like `Shape.Rect(w, h)`.
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let enum_name = self.expect_identifier()?;
+        if enum_name == "_" {
+            return Ok(Pattern::Wildcard);
+        }
+        self.expect(TokenType::Dot, "Expected dot between enum and variant names")?;
+        let variant = self.expect_identifier()?;
+
+        let mut bindings = Vec::new();
+        if self.advance_if(TokenType::OpenParenthesis) {
+            while !self.is_eof() && self.peek().token_type != TokenType::CloseParenthesis {
+                bindings.push(self.expect_identifier()?);
+                if !self.advance_if(TokenType::Comma) {
+                    break; // No more bindings
+                }
+            }
+            self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?;
+        }
+        Ok(Pattern::Variant { enum_name, variant, bindings })
+    }
+
+    /// Returns true if the current token starts a typed array creation like `[i32, 10]{0}` rather than an array literal.
+    /// The two are only distinguished by whether a brace follows the matching close square bracket.
+    fn is_typed_array_creation(&mut self) -> bool {
+        if !self.is_match(TokenType::OpenSquareBracket) {
+            return false;
+        }
+
+        let mut depth = 0;
+        let mut offset = 0;
+        // The matching bracket can be any distance away, so this pulls tokens past the usual lookahead
+        self.fill(offset + 2);
+        while let Some(token) = self.peek_at(offset) {
+            match token.token_type {
+                TokenType::OpenSquareBracket => depth += 1,
+                TokenType::CloseSquareBracket => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.check_ahead(offset + 1, TokenType::OpenCurlyBracket);
+                    }
+                },
+                _ => {}
+            }
+            offset += 1;
+            self.fill(offset + 2);
+        }
+        false
+    }
+
+    /// Returns true if the brace at the current token starts a map literal rather than a block.
+    /// No statement can start with a single token followed by a colon, so a `{ key:` shape is a map.
+    fn is_map_literal(&self) -> bool {
+        let key_like = self.peek_at(1).is_some_and(|token| matches!(token.token_type,
+            TokenType::StringLiteral(_) | TokenType::IntegerLiteral(_) | TokenType::TypedIntegerLiteral(..) | TokenType::CharLiteral(_) |
+            TokenType::TrueValue | TokenType::FalseValue | TokenType::Identifier(_)
+        ));
+        key_like && self.check_ahead(2, TokenType::Colon)
+    }
+
+    fn parse_map_literal(&mut self) -> Result<Expression, ParseError> {
+        self.expect(TokenType::OpenCurlyBracket, "Expected open brace")?;
+        let mut entries = Vec::new();
+        while !self.is_eof() && self.peek().token_type != TokenType::CloseCurlyBracket {
+            let key = self.parse_expression()?;
+            self.expect(TokenType::Colon, "Expected colon after map key")?;
+            let value = self.parse_expression()?;
+            entries.push((key, value));
+            if !self.advance_if(TokenType::Comma) {
+                break; // No more entries
+            }
+        }
+        self.expect(TokenType::CloseCurlyBracket, "Unmatched open brace")?;
+        Ok(Expression::MapLiteral(entries))
+    }
+
+    /// Parses comma-separated expressions up to and including the given closing token.
+    /// Like every other comma-separated list, a trailing comma is allowed.
+    fn parse_expression_list(&mut self, close: TokenType, message: &str) -> Result<Vec<Expression>, ParseError> {
+        let mut expressions = Vec::new();
+        while !self.is_eof() && self.peek().token_type != close {
+            expressions.push(self.parse_expression()?);
+            if !self.advance_if(TokenType::Comma) {
+                break; // No more expressions
+            }
+        }
+        self.expect(close, message)?;
+        Ok(expressions)
+    }
+
     fn parse_assignment_or_lower(&mut self) -> Result<Expression, ParseError> {
         // Assignment is right-associative, so we recursively parse instead of looping.
-        let expr = self.parse_logical_or_or_lower()?;
-        if self.advance_if(TokenType::AssignmentOperator) {
-            let value = Box::new(self.parse_logical_or_or_lower()?); // Parse the right-hand side
-            // TODO: member access assignment
-            if let Expression::Variable { name, expression_id } = expr {
-                return Ok(Expression::Assignment {
+        let expr = self.parse_range_or_lower()?;
+
+        // Compound assignment operators like `+=` carry the operator to apply
+        let operator = match self.peek_at(0).map(|token| &token.token_type) {
+            Some(TokenType::AssignmentOperator) => None,
+            Some(TokenType::AddAssignOperator) => Some(BinaryOperator::Add),
+            Some(TokenType::SubtractAssignOperator) => Some(BinaryOperator::Subtract),
+            Some(TokenType::MultiplyAssignOperator) => Some(BinaryOperator::Multiply),
+            Some(TokenType::DivideAssignOperator) => Some(BinaryOperator::Divide),
+            Some(TokenType::ModuloAssignOperator) => Some(BinaryOperator::Modulus),
+            _ => return Ok(expr)
+        };
+        self.advance(); // Consume the assignment operator
+        let operator_id = self.get_id();
+
+        let value = Box::new(self.parse_range_or_lower()?); // Parse the right-hand side
+        // TODO: member access assignment
+        match expr {
+            Expression::Variable { name, expression_id } => {
+                // `x += y` is the same as `x = x + y`. The read of `x` gets its own ID so the resolver can
+                // resolve it separately from the write, like it would if `x = x + y` was written out
+                let value = match operator {
+                    Some(operator) => Box::new(Expression::BinaryOperation {
+                        left: Box::new(Expression::Variable { name, expression_id: self.get_id_at(expression_id) }),
+                        operator,
+                        right: value,
+                        expression_id: operator_id
+                    }),
+                    None => value
+                };
+                Ok(Expression::Assignment {
                     name,
                     value,
                     expression_id
-                });
-            } else {
-                return Err(ParseError::UnexpectedToken {
-                    expected: Some(TokenType::Identifier("".to_string())),
-                    found: self.peek().clone(),
-                    message: Some("Expected an identifier for assignment".to_string())
-                });
-            }
+                })
+            },
+            Expression::Index { object, index, expression_id } => {
+                Ok(Expression::IndexAssignment { object, index, operator, value, expression_id })
+            },
+            _ => Err(self.unexpected_token(Some(TokenType::Identifier(Symbol::intern(""))), "Expected an identifier for assignment"))
         }
-        Ok(expr)
+    }
+
+    fn parse_range_or_lower(&mut self) -> Result<Expression, ParseError> {
+        let start = self.parse_logical_or_or_lower()?;
+        let inclusive = match self.peek_type() {
+            TokenType::DotDot => false,
+            TokenType::DotDotEqual => true,
+            _ => return Ok(start)
+        };
+        self.advance(); // Consume the range operator
+        // Ranges don't chain, so the end is parsed one level lower
+        let end = self.parse_logical_or_or_lower()?;
+        Ok(Expression::Range { start: Box::new(start), end: Box::new(end), inclusive })
     }
 
     fn parse_logical_or_or_lower(&mut self) -> Result<Expression, ParseError> {
@@ -650,7 +1255,7 @@ impl<'a> Parser<'a> {
     fn parse_comparison_or_lower(&mut self) -> Result<Expression, ParseError> {
         parse_precedence_binary!(
             self,
-            parse_term_or_lower,
+            parse_bitwise_or_or_lower,
             (TokenType::OpenAngleBracket, BinaryOperator::LessThan),
             (TokenType::CloseAngleBracket, BinaryOperator::GreaterThan),
             (TokenType::LessThanEqualOperator, BinaryOperator::LessThanOrEqual),
@@ -658,6 +1263,74 @@ impl<'a> Parser<'a> {
         )
     }
 
+    fn parse_bitwise_or_or_lower(&mut self) -> Result<Expression, ParseError> {
+        parse_precedence_binary!(
+            self,
+            parse_bitwise_xor_or_lower,
+            (TokenType::Pipe, BinaryOperator::BitwiseOr)
+        )
+    }
+
+    fn parse_bitwise_xor_or_lower(&mut self) -> Result<Expression, ParseError> {
+        parse_precedence_binary!(
+            self,
+            parse_bitwise_and_or_lower,
+            (TokenType::BitwiseXorOperator, BinaryOperator::BitwiseXor)
+        )
+    }
+
+    fn parse_bitwise_and_or_lower(&mut self) -> Result<Expression, ParseError> {
+        parse_precedence_binary!(
+            self,
+            parse_shift_or_lower,
+            (TokenType::BitwiseAndOperator, BinaryOperator::BitwiseAnd)
+        )
+    }
+
+    fn parse_shift_or_lower(&mut self) -> Result<Expression, ParseError> {
+        let mut expr = self.parse_term_or_lower()?;
+        let depth = self.depth;
+        let result = loop {
+            let Some(operator) = self.peek_shift_operator() else {
+                break Ok(expr);
+            };
+            self.advance(); // Consume the first angle bracket
+            let expression_id = self.get_id(); // Errors point at the operator
+            self.advance(); // Consume the second angle bracket
+
+            // Each operator nests everything before it one level deeper, so long chains count toward the limit
+            self.depth += 1;
+            let right = match self.nested(Self::parse_term_or_lower) {
+                Ok(right) => Box::new(right),
+                Err(error) => break Err(error)
+            };
+            expr = Expression::BinaryOperation {
+                left: Box::new(expr),
+                operator,
+                right,
+                expression_id
+            };
+        };
+        self.depth = depth;
+        result
+    }
+
+    /// Returns the shift operator at the current token, if there is one. Shifts are tokenized as two angle brackets,
+    /// since `>>` also closes nested generics, so only brackets right next to each other count.
+    fn peek_shift_operator(&self) -> Option<BinaryOperator> {
+        let operator = match self.peek_type() {
+            TokenType::OpenAngleBracket => BinaryOperator::ShiftLeft,
+            TokenType::CloseAngleBracket => BinaryOperator::ShiftRight,
+            _ => return None
+        };
+        let (first, second) = (self.peek(), self.peek_at(1)?);
+        if second.token_type == first.token_type && second.line == first.line && second.column == first.column + 1 {
+            Some(operator)
+        } else {
+            None
+        }
+    }
+
     fn parse_term_or_lower(&mut self) -> Result<Expression, ParseError> {
         parse_precedence_binary!(
             self,
@@ -670,75 +1343,168 @@ impl<'a> Parser<'a> {
     fn parse_factor_or_lower(&mut self) -> Result<Expression, ParseError> {
         parse_precedence_binary!(
             self,
-            parse_unary_or_lower,
+            parse_cast_or_lower,
             (TokenType::MultiplyOperator, BinaryOperator::Multiply),
             (TokenType::DivideOperator, BinaryOperator::Divide),
+            (TokenType::FloorDivideOperator, BinaryOperator::FloorDivide),
             (TokenType::ModuloOperator, BinaryOperator::Modulus),
         )
     }
 
+    /// Casts bind tighter than binary operators but looser than unary ones, so `-x as u8` casts `-x`.
+    fn parse_cast_or_lower(&mut self) -> Result<Expression, ParseError> {
+        let mut expr = self.parse_unary_or_lower()?;
+        let depth = self.depth;
+        let result = loop {
+            if !self.advance_if_contextual("as") {
+                break Ok(expr);
+            }
+            // Like binary operators, each cast nests everything before it one level deeper
+            self.depth += 1;
+            match self.parse_type() {
+                Ok(target) => expr = Expression::Cast { value: Box::new(expr), target },
+                Err(error) => break Err(error)
+            }
+        };
+        self.depth = depth;
+        result
+    }
+
     fn parse_unary_or_lower(&mut self) -> Result<Expression, ParseError> {
         parse_precedence_unary!(
             self,
+            parse_unary_or_lower,
             parse_call_or_lower,
             (TokenType::NotOperator, UnaryOperator::Not),
             (TokenType::SubtractOperator, UnaryOperator::Negate),
+            (TokenType::BitwiseNotOperator, UnaryOperator::BitwiseNot),
         )
     }
 
     fn parse_call_or_lower(&mut self) -> Result<Expression, ParseError> {
         let mut expr = self.parse_primary_or_lower()?;
 
-        // TODO: Array access and assignment
-        while !self.is_eof() {
-            if self.advance_if(TokenType::OpenParenthesis) {
-                expr = self.parse_function_call_after_paren(expr)?; // Parse function call
-            } else if self.advance_if(TokenType::Dot) {
-                let name = self.expect_identifier()?; // Expect an identifier after the dot
-                expr = Expression::MemberAccess { object: Box::new(expr), member: name };
-            } else {
-                break; // No more function calls or member accesses
+        let depth = self.depth;
+        let result = loop {
+            if !matches!(self.peek_type(), TokenType::OpenParenthesis | TokenType::OpenSquareBracket | TokenType::Dot | TokenType::QuestionDot) {
+                break Ok(expr); // No more function calls, indexes, or member accesses
             }
-        }
+            // Like binary operators, each call, index, or member access nests everything before it one level deeper
+            self.depth += 1;
+            match self.nested(|parser| parser.parse_postfix(expr)) {
+                Ok(postfix) => expr = postfix,
+                Err(error) => break Err(error)
+            }
+        };
+        self.depth = depth;
+        let expr = result?;
 
+        self.check_increment(&expr)?;
         Ok(expr)
     }
 
+    /// Parses a function call, index, or member access of `expr`, starting at its opening bracket or dot.
+    fn parse_postfix(&mut self, expr: Expression) -> Result<Expression, ParseError> {
+        if self.advance_if(TokenType::OpenParenthesis) {
+            self.parse_function_call_after_paren(expr)
+        } else if self.advance_if(TokenType::OpenSquareBracket) {
+            let expression_id = self.get_id();
+            let index = Box::new(self.parse_expression()?);
+            self.expect(TokenType::CloseSquareBracket, "Unmatched open square bracket")?;
+            Ok(Expression::Index { object: Box::new(expr), index, expression_id })
+        } else {
+            let optional = self.is_match(TokenType::QuestionDot);
+            self.advance(); // Consume the dot
+            let member = self.expect_identifier()?; // Expect an identifier after the dot
+            Ok(Expression::MemberAccess { object: Box::new(expr), member, optional })
+        }
+    }
+
+    /// `i++` would otherwise fail with a confusing error about the second `+`, so it gets a targeted one instead.
+    /// Only a variable, index, or member followed by two of the same sign and then the end of the expression counts,
+    /// since `5--3` and `a - -b` are fine.
+    fn check_increment(&self, expr: &Expression) -> Result<(), ParseError> {
+        if !matches!(expr, Expression::Variable { .. } | Expression::Index { .. } | Expression::MemberAccess { .. }) {
+            return Ok(());
+        }
+        let operator = match self.peek_type() {
+            TokenType::AddOperator => '+',
+            TokenType::SubtractOperator => '-',
+            _ => return Ok(())
+        };
+        let ends_expression = match self.peek_at(2) {
+            Some(token) => matches!(
+                token.token_type,
+                TokenType::Semicolon | TokenType::Comma | TokenType::CloseParenthesis | TokenType::CloseSquareBracket | TokenType::CloseCurlyBracket
+            ),
+            None => true
+        };
+        if !self.check_ahead(1, self.peek_type().clone()) || !ends_expression {
+            return Ok(());
+        }
+
+        Err(self.unexpected_token(None, &format!("Saffron has no {0}{0} operator; use {0}= 1", operator)))
+    }
+
     fn parse_function_call_after_paren(&mut self, callee: Expression) -> Result<Expression, ParseError> {
+        let expression_id = self.get_id();
         let mut args = Vec::new();
         while !self.is_eof() && self.peek().token_type != TokenType::CloseParenthesis {
-            args.push(self.parse_expression()?);
-            if self.is_match(TokenType::Comma) {
-                self.advance(); // Consume the comma
+            if self.advance_if(TokenType::Ellipsis) {
+                args.push(Expression::Spread(Box::new(self.parse_expression()?)));
             } else {
+                args.push(self.parse_expression()?);
+            }
+            if !self.advance_if(TokenType::Comma) {
                 break; // No more arguments
             }
         }
         self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?;
+        let close_line = self.tokens[self.current - 1].line;
+
+        // A block directly after the arguments is a trailing lambda, passed as the last argument.
+        // It has to start on the same line as the closing parenthesis, so a block on the next line stays a block.
+        if self.is_match(TokenType::OpenCurlyBracket) && self.peek().line == close_line {
+            args.push(self.parse_lambda()?);
+        }
+
         Ok(Expression::FunctionCall {
             callee: Box::new(callee),
-            args
+            args,
+            expression_id
         })
     }
 
     fn parse_primary_or_lower(&mut self) -> Result<Expression, ParseError> {
-        match self.peek().token_type.clone() {
+        match *self.peek_type() {
             // Simple literals
-            TokenType::IntegerLiteral(ref value) => {
+            TokenType::IntegerLiteral(value) => {
+                self.advance(); // Consume the number
+                Ok(Expression::IntegerLiteral(value))
+            },
+            TokenType::FloatLiteral(value) => {
                 self.advance(); // Consume the number
-                Ok(Expression::NumberLiteral(*value as f64)) // Convert to f64
+                Ok(Expression::NumberLiteral(value)) // Already f64
             },
-            TokenType::FloatLiteral(ref value) => {
+            TokenType::TypedIntegerLiteral(value, ref literal_type) => {
+                let literal_type = literal_type.as_ref().clone();
                 self.advance(); // Consume the number
-                Ok(Expression::NumberLiteral(*value)) // Already f64
+                Ok(Expression::TypedIntegerLiteral(value, literal_type))
+            },
+            TokenType::TypedFloatLiteral(value, ref literal_type) => {
+                let literal_type = literal_type.as_ref().clone();
+                self.advance(); // Consume the number
+                Ok(Expression::TypedNumberLiteral(value, literal_type))
             },
             TokenType::StringLiteral(ref value) => {
+                // The string is stored in the AST, so this is the one place it's cloned
+                let value = value.clone();
                 self.advance(); // Consume the string
-                Ok(Expression::StringLiteral(value.clone()))
+                Ok(Expression::StringLiteral(value))
             },
-            TokenType::CharLiteral(ref value) => {
+            TokenType::CharLiteral(value) => {
                 self.advance(); // Consume the char
-                Ok(Expression::CharLiteral(value.clone()))
+                Ok(Expression::CharLiteral(value))
             },
             TokenType::TrueValue => {
                 self.advance(); // Consume 'true'
@@ -749,24 +1515,58 @@ impl<'a> Parser<'a> {
                 Ok(Expression::BooleanLiteral(false))
             },
 
-            TokenType::Identifier(ref name) => {
+            TokenType::Identifier(name) => {
                 self.advance(); // Consume the identifier
-                Ok(Expression::Variable { name: name.clone(), expression_id: self.get_id() })
+                Ok(Expression::Variable { name, expression_id: self.get_id() })
             },
 
             TokenType::OpenParenthesis => {
                 self.advance(); // Consume the open parenthesis
                 let expr = self.parse_expression()?;
+
+                // A comma after the first element makes this a tuple rather than a grouping
+                if self.advance_if(TokenType::Comma) {
+                    let mut elements = vec![expr];
+                    elements.extend(self.parse_expression_list(TokenType::CloseParenthesis, "Unmatched open parentheses")?);
+                    return Ok(Expression::Tuple(elements));
+                }
+
                 self.expect(TokenType::CloseParenthesis, "Unmatched open parentheses")?; // Expect a close parenthesis
                 Ok(expr)
             },
+            TokenType::OpenSquareBracket => {
+                self.advance(); // Consume the open square bracket
+                if self.advance_if(TokenType::CloseSquareBracket) {
+                    return Ok(Expression::ArrayLiteral(Vec::new()));
+                }
+
+                let first = self.parse_expression()?;
+                if self.advance_if_contextual("for") {
+                    let binding = self.expect_identifier()?;
+                    if !self.advance_if_contextual("in") {
+                        return Err(self.unexpected_token(None, "Expected in after comprehension variable"));
+                    }
+                    let iterable = Box::new(self.parse_expression()?);
+                    let condition = if self.advance_if(TokenType::IfKeyword) {
+                        Some(Box::new(self.parse_expression()?))
+                    } else {
+                        None
+                    };
+                    self.expect(TokenType::CloseSquareBracket, "Unmatched open square bracket")?;
+                    return Ok(Expression::ArrayComprehension { element: Box::new(first), binding, iterable, condition });
+                }
+
+                let mut elements = vec![first];
+                if self.advance_if(TokenType::Comma) {
+                    elements.extend(self.parse_expression_list(TokenType::CloseSquareBracket, "Unmatched open square bracket")?);
+                } else {
+                    self.expect(TokenType::CloseSquareBracket, "Unmatched open square bracket")?;
+                }
+                Ok(Expression::ArrayLiteral(elements))
+            },
 
             _ => {
-                Err(ParseError::UnexpectedToken {
-                    expected: None,
-                    found: self.peek().clone(),
-                    message: Some("Expected an expression".to_string())
-                })
+                Err(self.unexpected_token(None, "Expected an expression"))
             }
         }
     }
@@ -774,6 +1574,8 @@ impl<'a> Parser<'a> {
 
 #[cfg(test)]
 mod tests {
+    use std::hash::Hasher;
+
     use super::*;
     use crate::tokenizer::Tokenizer;
 
@@ -796,25 +1598,618 @@ mod tests {
         "#, parse_expression), 
             Expression::BinaryOperation {
                 left: Box::new(Expression::BinaryOperation {
-                    left: Box::new(Expression::NumberLiteral(1.0)),
+                    left: Box::new(Expression::IntegerLiteral(1)),
                     operator: BinaryOperator::Add,
+                    expression_id: ExpressionId(1),
                     right: Box::new(Expression::BinaryOperation {
-                        left: Box::new(Expression::NumberLiteral(2.0)),
+                        left: Box::new(Expression::IntegerLiteral(2)),
                         operator: BinaryOperator::Multiply,
-                        right: Box::new(Expression::NumberLiteral(3.0))
+                        expression_id: ExpressionId(2),
+                        right: Box::new(Expression::IntegerLiteral(3))
                     })
                 }),
                 operator: BinaryOperator::Subtract,
+                expression_id: ExpressionId(3),
                 right: Box::new(Expression::BinaryOperation {
                     left: Box::new(Expression::BinaryOperation {
-                        left: Box::new(Expression::NumberLiteral(4.0)),
+                        left: Box::new(Expression::IntegerLiteral(4)),
                         operator: BinaryOperator::Divide,
-                        right: Box::new(Expression::NumberLiteral(5.0))
+                        expression_id: ExpressionId(4),
+                        right: Box::new(Expression::IntegerLiteral(5))
                     }),
                     operator: BinaryOperator::Modulus,
-                    right: Box::new(Expression::NumberLiteral(6.0))
+                    expression_id: ExpressionId(5),
+                    right: Box::new(Expression::IntegerLiteral(6))
                 })
             }
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_trailing_lambda() {
+        assert_eq!(parse!(r#"
+            forEach(arr) { |x| print(x) }
+        "#, parse_expression),
+            Expression::FunctionCall {
+                callee: Box::new(Expression::Variable { name: Symbol::intern("forEach"), expression_id: ExpressionId(1) }),
+                args: vec![
+                    Expression::Variable { name: Symbol::intern("arr"), expression_id: ExpressionId(3) },
+                    Expression::Lambda {
+                        params: vec!["x".to_string()],
+                        body: Rc::new(Expression::Block(vec![
+                            Statement::Expression {
+                                expression: Box::new(Expression::FunctionCall {
+                                    callee: Box::new(Expression::Variable { name: Symbol::intern("print"), expression_id: ExpressionId(4) }),
+                                    args: vec![Expression::Variable { name: Symbol::intern("x"), expression_id: ExpressionId(6) }],
+                                    expression_id: ExpressionId(5)
+                                }),
+                                result: true
+                            }
+                        ]))
+                    }
+                ],
+                expression_id: ExpressionId(2)
+            }
+        );
+    }
+
+    #[test]
+    fn test_trailing_lambda_on_next_line() {
+        let expression = parse!(r#"
+            run()
+            { 1 }
+        "#, parse_expression);
+        assert!(matches!(expression, Expression::FunctionCall { ref args, .. } if args.is_empty()));
+    }
+
+    #[test]
+    fn test_trailing_lambda_without_parameters() {
+        assert_eq!(parse!(r#"
+            run() { 1 }
+        "#, parse_expression),
+            Expression::FunctionCall {
+                callee: Box::new(Expression::Variable { name: Symbol::intern("run"), expression_id: ExpressionId(1) }),
+                args: vec![
+                    Expression::Lambda {
+                        params: vec![],
+                        body: Rc::new(Expression::Block(vec![
+                            Statement::Expression {
+                                expression: Box::new(Expression::IntegerLiteral(1)),
+                                result: true
+                            }
+                        ]))
+                    }
+                ],
+                expression_id: ExpressionId(2)
+            }
+        );
+    }
+
+    #[test]
+    fn test_lookahead() {
+        let mut tokenizer = Tokenizer::new("{ |x| x }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+
+        assert!(parser.check_ahead(0, TokenType::OpenCurlyBracket));
+        assert!(parser.check_ahead(1, TokenType::Pipe));
+        assert!(!parser.check_ahead(1, TokenType::OrOperator));
+        assert!(parser.peek_at(6).is_none());
+        assert!(!parser.check_ahead(6, TokenType::CloseCurlyBracket));
+
+        parser.advance();
+        assert!(parser.check_ahead(3, TokenType::Identifier(Symbol::intern("x"))));
+        assert!(parser.check_ahead(4, TokenType::CloseCurlyBracket));
+    }
+
+    #[test]
+    fn test_parse_from_stream() {
+        let source = r#"
+            func main() -> i32 {
+                let values: [i32] = [i32, 1 + 2 + 3 + 4 + 5 + 6]{0};
+                values[0]
+            }
+        "#;
+        let mut tokenizer = Tokenizer::new(source.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program().unwrap();
+
+        let mut streamed = Parser::from_stream(Tokenizer::new(source.to_string()).map(Result::unwrap));
+        assert_eq!(streamed.parse_program().unwrap(), program);
+        assert_eq!(streamed.take_spans(), parser.take_spans());
+    }
+
+    #[test]
+    fn test_stream_end_of_input() {
+        let mut parser = Parser::from_stream(Tokenizer::new("1 +\n  2 +".to_string()).map(Result::unwrap));
+        assert_eq!(parser.parse_expression(), Err(ParseError::UnexpectedEndOfInput));
+        assert_eq!((parser.peek().line, parser.peek().column), (2, 5));
+    }
+
+    #[test]
+    fn test_array_literals_and_tuples() {
+        assert_eq!(parse!("[1, (2, 3)]", parse_expression),
+            Expression::ArrayLiteral(vec![
+                Expression::IntegerLiteral(1),
+                Expression::Tuple(vec![Expression::IntegerLiteral(2), Expression::IntegerLiteral(3)])
+            ])
+        );
+
+        // A brace after the brackets makes this a typed array creation instead
+        assert_eq!(parse!("[i32, 2]{0}", parse_expression),
+            Expression::Array {
+                array_type: Type::I32,
+                size: Box::new(Expression::IntegerLiteral(2)),
+                initial_value: Box::new(Expression::IntegerLiteral(0))
+            }
+        );
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        for (with_comma, without_comma) in [
+            ("f(a, b,)", "f(a, b)"),
+            ("f(a,) { || 1 }", "f(a) { || 1 }"),
+            ("[1, 2,]", "[1, 2]"),
+            ("{ \"a\": 1, }", "{ \"a\": 1 }"),
+            ("new P<i32, str,> { x: 1, y: 2, }", "new P<i32, str> { x: 1, y: 2 }")
+        ] {
+            assert_eq!(parse!(with_comma, parse_expression), parse!(without_comma, parse_expression), "{}", with_comma);
+        }
+
+        assert_eq!(
+            parse!("func f<A, B,>(a: A, b: B,) -> A { a }", parse_declaration),
+            parse!("func f<A, B>(a: A, b: B) -> A { a }", parse_declaration)
+        );
+    }
+
+    #[test]
+    fn test_type_display() {
+        for source in ["Map<Key, [(i32, bool)]>", "[[Point<f64>]]", "func(u8, char) -> [i64]", "()", "[i32?]?"] {
+            assert_eq!(parse!(source, parse_type).to_string(), source);
+        }
+    }
+
+    #[test]
+    fn test_fnv_hasher() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = ast::FnvHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn test_ast_hash() {
+        let parse = |input: &str| {
+            let mut tokenizer = Tokenizer::new(input.to_string());
+            let tokens = tokenizer.tokenize().unwrap();
+            let program = Parser::new(tokens).parse_program().unwrap();
+            program
+        };
+        let hash = |input: &str| ast::ast_hash(&parse(input));
+
+        let source = "func main() -> i32 { let x: i32 = 1 + 2; x * 3 }";
+        let original = hash(source);
+        assert_eq!(hash("func main() -> i32 {\n    // Comment\n    let x: i32 = 1+2;\n    x*3\n}\n"), original);
+        assert_ne!(hash("func main() -> i32 { let x: i32 = 1 + 2; x * 4 }"), original);
+        assert_ne!(hash("func main() -> i32 { let y: i32 = 1 + 2; y * 3 }"), original);
+        assert_ne!(hash("func main() -> i32 { let x: i32 = 1 - 2; x * 3 }"), original);
+
+        // An earlier function shifts the expression IDs in `main`, but not its hash
+        let shifted = parse(&format!("func f() -> i32 {{ -1 }} {}", source)).declarations.remove(1);
+        assert_ne!(shifted, parse(source).declarations[0]);
+        assert_eq!(ast::ast_hash(&Program { declarations: vec![shifted] }), original);
+    }
+
+    #[test]
+    fn test_literals_and_statements() {
+        assert_eq!(parse!(r#"
+            {
+                const s: string = "hi";
+                let c: char = 'x';
+                return 1.5;
+            }
+        "#, parse_expression),
+            Expression::Block(vec![
+                Statement::VariableDeclaration {
+                    mutability: VariableMutability::Immutable,
+                    name: "s".to_string(),
+                    variable_type: Type::Identifier { name: "string".to_string(), generics: vec![] },
+                    value: Box::new(Expression::StringLiteral("hi".to_string())),
+                    expression_id: ExpressionId(1)
+                },
+                Statement::VariableDeclaration {
+                    mutability: VariableMutability::Mutable,
+                    name: "c".to_string(),
+                    variable_type: Type::Character,
+                    value: Box::new(Expression::CharLiteral('x')),
+                    expression_id: ExpressionId(2)
+                },
+                Statement::Return(Some(Box::new(Expression::NumberLiteral(1.5))))
+            ])
+        );
+    }
+
+    #[test]
+    fn test_global_variables() {
+        let program = parse!("const PI: f64 = 3.5; func main() -> i32 { const N: i32 = 1; N }", parse_program);
+        assert_eq!(program.declarations[0], Declaration::GlobalVariable {
+            mutability: VariableMutability::Immutable,
+            name: "PI".to_string(),
+            variable_type: Type::F64,
+            value: Box::new(Expression::NumberLiteral(3.5))
+        });
+        // Inside a function, it's still a local variable
+        let Declaration::Function { body, .. } = &program.declarations[1] else {
+            panic!("Expected a function, got {:?}", program.declarations[1]);
+        };
+        assert!(matches!(body.as_ref(), Expression::Block(statements) if matches!(statements[0], Statement::VariableDeclaration { .. })));
+    }
+
+    #[test]
+    fn test_const_functions() {
+        let program = parse!("const func square(n: i32) -> i32 { n * n } func main() -> i32 { const N: i32 = 1; const func one() -> i32 { 1 } N }", parse_program);
+        assert!(matches!(&program.declarations[0], Declaration::Function { name, is_const: true, .. } if name == "square"));
+        assert!(matches!(&program.declarations[1], Declaration::Function { name, is_const: false, .. } if name == "main"));
+
+        let Declaration::Function { body, .. } = &program.declarations[1] else {
+            panic!("Expected a function, got {:?}", program.declarations[1]);
+        };
+        let Expression::Block(statements) = body.as_ref() else {
+            panic!("Expected a block, got {:?}", body);
+        };
+        assert!(matches!(statements[0], Statement::VariableDeclaration { .. }));
+        assert!(matches!(&statements[1], Statement::Declaration(Declaration::Function { is_const: true, .. })));
+    }
+
+    #[test]
+    fn test_map_literal() {
+        assert_eq!(parse!(r#"{ "a": 1, b: 2, }"#, parse_expression),
+            Expression::MapLiteral(vec![
+                (Expression::StringLiteral("a".to_string()), Expression::IntegerLiteral(1)),
+                (Expression::Variable { name: Symbol::intern("b"), expression_id: ExpressionId(1) }, Expression::IntegerLiteral(2))
+            ])
+        );
+
+        // Without the `key:` shape, a brace is still a block
+        assert_eq!(parse!("{ b }", parse_expression),
+            Expression::Block(vec![
+                Statement::Expression {
+                    expression: Box::new(Expression::Variable { name: Symbol::intern("b"), expression_id: ExpressionId(1) }),
+                    result: true
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_index_assignment() {
+        assert_eq!(parse!(r#"m["c"] = 3"#, parse_expression),
+            Expression::IndexAssignment {
+                object: Box::new(Expression::Variable { name: Symbol::intern("m"), expression_id: ExpressionId(1) }),
+                index: Box::new(Expression::StringLiteral("c".to_string())),
+                operator: None,
+                value: Box::new(Expression::IntegerLiteral(3)),
+                expression_id: ExpressionId(2)
+            }
+        );
+    }
+
+    #[test]
+    fn test_cast() {
+        assert_eq!(parse!("-x as u8 * 2", parse_expression),
+            Expression::BinaryOperation {
+                left: Box::new(Expression::Cast {
+                    value: Box::new(Expression::UnaryOperation {
+                        operator: UnaryOperator::Negate,
+                        operand: Box::new(Expression::Variable { name: Symbol::intern("x"), expression_id: ExpressionId(2) }),
+                        expression_id: ExpressionId(1)
+                    }),
+                    target: Type::U8
+                }),
+                operator: BinaryOperator::Multiply,
+                right: Box::new(Expression::IntegerLiteral(2)),
+                expression_id: ExpressionId(3)
+            }
+        );
+        assert_eq!(parse!("1.5 as f32 as i64", parse_expression),
+            Expression::Cast {
+                value: Box::new(Expression::Cast { value: Box::new(Expression::NumberLiteral(1.5)), target: Type::F32 }),
+                target: Type::I64
+            }
+        );
+    }
+
+    #[test]
+    fn test_else_if() {
+        let variable = |name: &str, id: u32| Box::new(Expression::Variable { name: Symbol::intern(name), expression_id: ExpressionId(id) });
+        assert_eq!(parse!("if (a) 1 else if (b) 2 else 3", parse_expression),
+            Expression::If {
+                condition: variable("a", 1),
+                then_branch: Box::new(Expression::IntegerLiteral(1)),
+                else_branch: Some(Box::new(Expression::If {
+                    condition: variable("b", 2),
+                    then_branch: Box::new(Expression::IntegerLiteral(2)),
+                    else_branch: Some(Box::new(Expression::IntegerLiteral(3)))
+                }))
+            }
+        );
+    }
+
+    #[test]
+    fn test_large_integer_literal() {
+        // 2^53 + 1, which a float would round to 2^53
+        assert_eq!(parse!("9007199254740993", parse_expression), Expression::IntegerLiteral(9007199254740993));
+        assert_eq!(parse!("9223372036854775807", parse_expression), Expression::IntegerLiteral(i64::MAX));
+    }
+
+    #[test]
+    fn test_enum_and_match() {
+        let program = parse!("enum Shape { Circle(f64), Rect(f64, f64), Empty, }", parse_program);
+        assert_eq!(program.declarations[0], Declaration::Enum {
+            name: "Shape".to_string(),
+            variants: vec![
+                EnumVariant { name: "Circle".to_string(), fields: vec![Type::F64] },
+                EnumVariant { name: "Rect".to_string(), fields: vec![Type::F64, Type::F64] },
+                EnumVariant { name: "Empty".to_string(), fields: vec![] }
+            ],
+            generic_args: vec![]
+        });
+
+        let program = parse!("enum Result<T, E> { Ok(T), Err(E) }", parse_program);
+        let Declaration::Enum { generic_args, variants, .. } = &program.declarations[0] else {
+            panic!("Expected an enum, got {:?}", program.declarations[0]);
+        };
+        assert_eq!(generic_args, &vec!["T".to_string(), "E".to_string()]);
+        assert_eq!(variants[1].fields, vec![Type::Identifier { name: "E".to_string(), generics: vec![] }]);
+
+        assert_eq!(parse!("match (s) { Shape.Rect(w, h) => w, _ => 0 }", parse_expression),
+            Expression::Match {
+                value: Box::new(Expression::Variable { name: Symbol::intern("s"), expression_id: ExpressionId(1) }),
+                arms: vec![
+                    MatchArm {
+                        pattern: Pattern::Variant {
+                            enum_name: "Shape".to_string(),
+                            variant: "Rect".to_string(),
+                            bindings: vec!["w".to_string(), "h".to_string()]
+                        },
+                        body: Box::new(Expression::Variable { name: Symbol::intern("w"), expression_id: ExpressionId(2) })
+                    },
+                    MatchArm { pattern: Pattern::Wildcard, body: Box::new(Expression::IntegerLiteral(0)) }
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn test_optional_return_type() {
+        for (input, expected) in [("func main() { print(1); }", Type::Nil), ("func main() -> nil { }", Type::Nil), ("func main() -> i32 { 0 }", Type::I32)] {
+            let program = parse!(input, parse_program);
+            let Declaration::Function { return_type, .. } = &program.declarations[0] else {
+                panic!("Expected a function, got {:?}", program.declarations[0]);
+            };
+            assert_eq!(return_type, &expected, "{}", input);
+        }
+
+        let program = parse!("interface Named { func name() -> string; func rename(name: string); }", parse_program);
+        let Declaration::Interface { methods, .. } = &program.declarations[0] else {
+            panic!("Expected an interface, got {:?}", program.declarations[0]);
+        };
+        assert_eq!(methods[1].return_type, Type::Nil);
+    }
+
+    #[test]
+    fn test_array_comprehension() {
+        let variable = |name: &str, id: u32| Box::new(Expression::Variable { name: Symbol::intern(name), expression_id: ExpressionId(id) });
+        assert_eq!(parse!("[x * 2 for x in values]", parse_expression), Expression::ArrayComprehension {
+            element: Box::new(Expression::BinaryOperation {
+                left: variable("x", 1),
+                operator: BinaryOperator::Multiply,
+                right: Box::new(Expression::IntegerLiteral(2)),
+                expression_id: ExpressionId(2)
+            }),
+            binding: "x".to_string(),
+            iterable: variable("values", 3),
+            condition: None
+        });
+
+        let Expression::ArrayComprehension { condition, .. } = parse!("[x for x in values if x > 0]", parse_expression) else {
+            panic!("Expected an array comprehension");
+        };
+        assert!(matches!(condition.as_deref(), Some(Expression::BinaryOperation { operator: BinaryOperator::GreaterThan, .. })));
+
+        // Without `for`, it's still an ordinary array
+        assert_eq!(parse!("[]", parse_expression), Expression::ArrayLiteral(vec![]));
+        assert_eq!(parse!("[1]", parse_expression), Expression::ArrayLiteral(vec![Expression::IntegerLiteral(1)]));
+    }
+
+    #[test]
+    fn test_optional_types() {
+        assert_eq!(parse!("i32?", parse_type), Type::Optional(Box::new(Type::I32)));
+        assert_eq!(parse!("[Point?]", parse_type), Type::Array(Box::new(Type::Optional(Box::new(Type::Identifier { name: "Point".to_string(), generics: vec![] })))));
+        assert_eq!(parse!("func(i32?) -> i32", parse_type), Type::Function {
+            params: vec![Type::Optional(Box::new(Type::I32))],
+            return_type: Box::new(Type::I32)
+        });
+
+        let Expression::MemberAccess { object, member, optional: false } = parse!("point?.x.y", parse_expression) else {
+            panic!("Expected a member access");
+        };
+        assert_eq!(member, "y");
+        let Expression::MemberAccess { object, member, optional: true } = *object else {
+            panic!("Expected an optional member access");
+        };
+        assert_eq!(member, "x");
+        assert!(matches!(*object, Expression::Variable { .. }));
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(parse!("'a'..='c'", parse_expression), Expression::Range {
+            start: Box::new(Expression::CharLiteral('a')),
+            end: Box::new(Expression::CharLiteral('c')),
+            inclusive: true
+        });
+        let Expression::Range { start, end, inclusive: false } = parse!("0..n + 1", parse_expression) else {
+            panic!("Expected an exclusive range");
+        };
+        assert_eq!(*start, Expression::IntegerLiteral(0));
+        assert!(matches!(*end, Expression::BinaryOperation { operator: BinaryOperator::Add, .. }));
+    }
+
+    #[test]
+    fn test_spread_arguments() {
+        let Expression::FunctionCall { args, .. } = parse!("sum3(1, ...values, ...[2])", parse_expression) else {
+            panic!("Expected a function call");
+        };
+        assert_eq!(args.len(), 3);
+        assert_eq!(args[0], Expression::IntegerLiteral(1));
+        assert!(matches!(&args[1], Expression::Spread(value) if matches!(value.as_ref(), Expression::Variable { name, .. } if name.as_str() == "values")));
+        assert_eq!(args[2], Expression::Spread(Box::new(Expression::ArrayLiteral(vec![Expression::IntegerLiteral(2)]))));
+
+        // Spreads are only allowed in argument lists
+        let mut tokenizer = Tokenizer::new("[...values]".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        assert!(Parser::new(tokens).parse_expression().is_err());
+    }
+
+    #[test]
+    fn test_interface() {
+        let program = parse!("interface Shape { func area() -> f64; func scale(by: f64) -> Shape; } struct Square : Shape, Named { side: f64; }", parse_program);
+        assert_eq!(program.declarations[0], Declaration::Interface {
+            name: "Shape".to_string(),
+            methods: vec![
+                InterfaceMethod { name: "area".to_string(), params: vec![], return_type: Type::F64 },
+                InterfaceMethod {
+                    name: "scale".to_string(),
+                    params: vec![FunctionParameter { name: "by".to_string(), param_type: Type::F64 }],
+                    return_type: Type::Identifier { name: "Shape".to_string(), generics: vec![] }
+                }
+            ]
+        });
+        let Declaration::Struct { interfaces, .. } = &program.declarations[1] else {
+            panic!("Expected a struct, got {:?}", program.declarations[1]);
+        };
+        assert_eq!(interfaces, &vec!["Shape".to_string(), "Named".to_string()]);
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        for input in ["func add(", "func add(a: i32, b", "func add() -> i32 { 1 +", "func add() -> i32 { let x: i32 = "] {
+            let mut tokenizer = Tokenizer::new(input.to_string());
+            let tokens = tokenizer.tokenize().unwrap();
+            let mut parser = Parser::new(tokens);
+
+            assert_eq!(parser.parse_program(), None);
+            assert_eq!(parser.errors, vec![ParseError::UnexpectedEndOfInput], "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_block_error_recovery() {
+        // Each error is skipped up to the end of its statement, including the braces in it, without leaving the block
+        let input = "func main() -> i32 {\n    if (a > ) { a = 2; };\n    let b: i32 = a * ;\n    b\n}\nfunc other() -> i32 { 0 }";
+        let mut tokenizer = Tokenizer::new(input.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+
+        assert_eq!(parser.parse_program(), None);
+        let errors: Vec<(usize, usize)> = parser.errors().iter().filter_map(|error| error.token()).map(|token| (token.line, token.column)).collect();
+        assert_eq!(errors, vec![(2, 13), (3, 22)]);
+        assert_eq!(parser.errors().len(), 2);
+
+        // An error right before the closing brace doesn't swallow it
+        let input = "func main() -> i32 {\n    let a: i32 = (1 + ;\n    let b: i32 = }\nfunc other() -> i32 { 0 }";
+        let mut tokenizer = Tokenizer::new(input.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+
+        assert_eq!(parser.parse_program(), None);
+        let errors: Vec<(usize, usize)> = parser.errors().iter().filter_map(|error| error.token()).map(|token| (token.line, token.column)).collect();
+        assert_eq!(errors, vec![(2, 23), (3, 18)]);
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        // In debug builds, the default stack limit is reached well before the allowed nesting depth.
+        // `main` runs everything on a larger thread with a larger limit for the same reason.
+        let thread = std::thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(|| {
+            let input = format!("{}1{}", "(".repeat(10_000), ")".repeat(10_000));
+            let mut tokenizer = Tokenizer::new(input);
+            let tokens = tokenizer.tokenize().unwrap();
+            let mut parser = Parser::new(tokens);
+            parser.set_max_stack(48 * 1024 * 1024);
+
+            match parser.parse_snippet() {
+                Err(errors) => assert_eq!(errors, vec![ParseError::UnexpectedToken {
+                    expected: None,
+                    found: tokens[DEFAULT_MAX_DEPTH].clone(),
+                    message: Some("Expression nesting too deep".to_string())
+                }]),
+                Ok(_) => panic!("Expected a nesting error")
+            }
+        }).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        // Deeply nested expressions, unary operators, operator chains, postfix chains, and types fail to parse instead of
+        // overflowing the stack, even on a thread with the default stack size
+        for input in [
+            format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000)),
+            format!("{}1", "- ".repeat(100_000)),
+            format!("{}true", "!".repeat(100_000)),
+            format!("0{}", " + 1".repeat(100_000)),
+            format!("0{}", " << 1".repeat(100_000)),
+            format!("1 as {}i32{}", "[".repeat(100_000), "]".repeat(100_000)),
+            // Postfix chains and casts nest too
+            format!("a{}", "[0]".repeat(200_000)),
+            format!("a{}", ".x".repeat(200_000)),
+            format!("main{}", "()".repeat(200_000)),
+            format!("0{}", " as i32".repeat(200_000))
+        ] {
+            let mut tokenizer = Tokenizer::new(input);
+            let tokens = tokenizer.tokenize().unwrap();
+            let mut parser = Parser::new(tokens);
+
+            match parser.parse_snippet() {
+                Err(errors) => assert!(errors.iter().any(|error| matches!(
+                    error,
+                    ParseError::UnexpectedToken { message: Some(message), .. } if message == "Expression nesting too deep"
+                )), "{:?}", errors),
+                Ok(_) => panic!("Expected a nesting error")
+            }
+        }
+    }
+
+    #[test]
+    fn test_increment_operator() {
+        let tokens = Tokenizer::new("func f() -> i32 {\n    i++;\n    i\n}".to_string()).tokenize().unwrap().clone();
+        let mut parser = Parser::new(&tokens);
+        assert_eq!(parser.parse_program(), None);
+        assert_eq!(parser.errors(), &[ParseError::UnexpectedToken {
+            expected: None,
+            found: tokens[8].clone(),
+            message: Some("Saffron has no ++ operator; use += 1".to_string())
+        }]);
+        assert_eq!((tokens[8].line, tokens[8].column), (2, 6));
+
+        for source in ["a[0]--", "f(p.x--)"] {
+            let tokens = Tokenizer::new(source.to_string()).tokenize().unwrap().clone();
+            let errors = Parser::new(&tokens).parse_snippet().unwrap_err();
+            assert_eq!(errors[0].message(), "Unexpected token: SubtractOperator. Saffron has no -- operator; use -= 1", "{}", source);
+        }
+
+        // Repeated signs that aren't after a variable, or are followed by an operand, are still fine
+        for source in ["5--3", "a - -b", "--x", "a--b"] {
+            let tokens = Tokenizer::new(source.to_string()).tokenize().unwrap().clone();
+            assert!(Parser::new(&tokens).parse_snippet().is_ok(), "{}", source);
+        }
+    }
+}
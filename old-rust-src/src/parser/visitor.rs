@@ -0,0 +1,282 @@
+use super::ast::{Declaration, Expression, LoopType, Program, Statement, StructElement, Type};
+
+/// A read-only traversal over the AST.
+/// Every method has a default implementation that recurses into the node's children through the matching
+/// `walk_*` function, so implementors only need to override the nodes they care about. An overriding method
+/// can call the `walk_*` function itself to keep recursing.
+pub trait Visitor: Sized {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) {
+        walk_declaration(self, declaration);
+    }
+
+    fn visit_struct_element(&mut self, element: &StructElement) {
+        walk_struct_element(self, element);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        walk_type(self, ty);
+    }
+}
+
+pub fn walk_program<V: Visitor>(visitor: &mut V, program: &Program) {
+    for declaration in &program.declarations {
+        visitor.visit_declaration(declaration);
+    }
+}
+
+pub fn walk_declaration<V: Visitor>(visitor: &mut V, declaration: &Declaration) {
+    match declaration {
+        Declaration::Function { params, return_type, body, .. } => {
+            for param in params {
+                visitor.visit_type(&param.param_type);
+            }
+            visitor.visit_type(return_type);
+            visitor.visit_expression(body);
+        },
+        Declaration::Struct { elements, .. } => {
+            for element in elements {
+                visitor.visit_struct_element(element);
+            }
+        },
+        Declaration::Interface { methods, .. } => {
+            for method in methods {
+                for param in &method.params {
+                    visitor.visit_type(&param.param_type);
+                }
+                visitor.visit_type(&method.return_type);
+            }
+        },
+        Declaration::TypeDeclaration { alias, .. } => {
+            visitor.visit_type(alias);
+        },
+        Declaration::Import { .. } => {},
+        Declaration::Enum { variants, .. } => {
+            for variant in variants {
+                for field in &variant.fields {
+                    visitor.visit_type(field);
+                }
+            }
+        },
+        Declaration::GlobalVariable { variable_type, value, .. } => {
+            visitor.visit_type(variable_type);
+            visitor.visit_expression(value);
+        }
+    }
+}
+
+pub fn walk_struct_element<V: Visitor>(visitor: &mut V, element: &StructElement) {
+    match element {
+        StructElement::Declaration(declaration) => visitor.visit_declaration(declaration),
+        StructElement::Field { field_type, .. } => visitor.visit_type(field_type)
+    }
+}
+
+pub fn walk_statement<V: Visitor>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Declaration(declaration) => visitor.visit_declaration(declaration),
+        Statement::Expression { expression, .. } => visitor.visit_expression(expression),
+        Statement::VariableDeclaration { variable_type, value, .. } => {
+            visitor.visit_type(variable_type);
+            visitor.visit_expression(value);
+        },
+        Statement::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expression(value);
+            }
+        },
+        Statement::Break | Statement::Continue => {}
+    }
+}
+
+pub fn walk_expression<V: Visitor>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Block(statements) => {
+            for statement in statements {
+                visitor.visit_statement(statement);
+            }
+        },
+        Expression::IntegerLiteral(_) | Expression::NumberLiteral(_) | Expression::TypedIntegerLiteral(..) | Expression::TypedNumberLiteral(..) |
+        Expression::StringLiteral(_) | Expression::CharLiteral(_) |
+        Expression::BooleanLiteral(_) | Expression::Variable { .. } => {},
+        Expression::ArrayLiteral(elements) | Expression::Tuple(elements) => {
+            for element in elements {
+                visitor.visit_expression(element);
+            }
+        },
+        Expression::MapLiteral(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expression(key);
+                visitor.visit_expression(value);
+            }
+        },
+        Expression::Range { start, end, .. } => {
+            visitor.visit_expression(start);
+            visitor.visit_expression(end);
+        },
+        Expression::ArrayComprehension { element, iterable, condition, .. } => {
+            visitor.visit_expression(iterable);
+            if let Some(condition) = condition {
+                visitor.visit_expression(condition);
+            }
+            visitor.visit_expression(element);
+        },
+        Expression::FunctionCall { callee, args, .. } => {
+            visitor.visit_expression(callee);
+            for arg in args {
+                visitor.visit_expression(arg);
+            }
+        },
+        Expression::Spread(value) => {
+            visitor.visit_expression(value);
+        },
+        Expression::Lambda { body, .. } => {
+            visitor.visit_expression(body);
+        },
+        Expression::BinaryOperation { left, right, .. } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        },
+        Expression::UnaryOperation { operand, .. } => {
+            visitor.visit_expression(operand);
+        },
+        Expression::Assignment { value, .. } => {
+            visitor.visit_expression(value);
+        },
+        Expression::MemberAccess { object, .. } => {
+            visitor.visit_expression(object);
+        },
+        Expression::Index { object, index, .. } => {
+            visitor.visit_expression(object);
+            visitor.visit_expression(index);
+        },
+        Expression::IndexAssignment { object, index, value, .. } => {
+            visitor.visit_expression(object);
+            visitor.visit_expression(index);
+            visitor.visit_expression(value);
+        },
+        Expression::Array { array_type, size, initial_value } => {
+            visitor.visit_type(array_type);
+            visitor.visit_expression(size);
+            visitor.visit_expression(initial_value);
+        },
+        Expression::StructCreation { struct_type, fields } => {
+            visitor.visit_type(struct_type);
+            for (_, value) in fields {
+                visitor.visit_expression(value);
+            }
+        },
+        Expression::Cast { value, target } => {
+            visitor.visit_expression(value);
+            visitor.visit_type(target);
+        },
+        Expression::If { condition, then_branch, else_branch } => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_expression(else_branch);
+            }
+        },
+        Expression::Match { value, arms } => {
+            visitor.visit_expression(value);
+            for arm in arms {
+                visitor.visit_expression(&arm.body);
+            }
+        },
+        Expression::Loop(LoopType::While { condition, body }) => {
+            visitor.visit_expression(condition);
+            visitor.visit_expression(body);
+        },
+        Expression::Loop(LoopType::Infinite { body }) => {
+            visitor.visit_expression(body);
+        },
+        Expression::Loop(LoopType::DoWhile { body, condition }) => {
+            visitor.visit_expression(body);
+            visitor.visit_expression(condition);
+        },
+        Expression::Loop(LoopType::Iterator { iterable: value, body, .. } | LoopType::WhileLet { expr: value, body, .. }) => {
+            visitor.visit_expression(value);
+            visitor.visit_expression(body);
+        }
+    }
+}
+
+pub fn walk_type<V: Visitor>(visitor: &mut V, ty: &Type) {
+    match ty {
+        Type::Identifier { generics, .. } => {
+            for generic in generics {
+                visitor.visit_type(generic);
+            }
+        },
+        Type::Function { params, return_type } => {
+            for param in params {
+                visitor.visit_type(param);
+            }
+            visitor.visit_type(return_type);
+        },
+        Type::Array(element_type) | Type::Optional(element_type) => {
+            visitor.visit_type(element_type);
+        },
+        Type::Tuple(element_types) => {
+            for element_type in element_types {
+                visitor.visit_type(element_type);
+            }
+        },
+        Type::U8 | Type::U16 | Type::U32 | Type::U64 |
+        Type::I8 | Type::I16 | Type::I32 | Type::I64 |
+        Type::F32 | Type::F64 |
+        Type::Boolean | Type::Character | Type::Nil => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, tokenizer::Tokenizer};
+
+    struct CallCounter {
+        calls: usize
+    }
+
+    impl Visitor for CallCounter {
+        fn visit_expression(&mut self, expression: &Expression) {
+            if let Expression::FunctionCall { .. } = expression {
+                self.calls += 1;
+            }
+            walk_expression(self, expression);
+        }
+    }
+
+    #[test]
+    fn test_count_function_calls() {
+        let input = r#"
+            func main() -> i32 {
+                print(add(1, 2));
+                let x: i32 = if (check(x)) { 1 } else { 2 };
+                loop (true) {
+                    print(x);
+                }
+            }
+        "#;
+
+        let mut tokenizer = Tokenizer::new(input.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse_program().unwrap();
+
+        let mut counter = CallCounter { calls: 0 };
+        counter.visit_program(&program);
+        assert_eq!(counter.calls, 4);
+    }
+}
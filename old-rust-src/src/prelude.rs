@@ -0,0 +1,41 @@
+use crate::{parser::{ast::Program, Parser}, tokenizer::Tokenizer};
+
+/// The source of the prelude: declarations written in Saffron that are loaded before every program,
+/// like the `Option` and `Result` enums. Functions that need Rust are natives in the interpreter instead.
+pub const SOURCE: &str = include_str!("prelude.saffron");
+
+/// Parses the prelude, returning its declarations and the last expression ID they use.
+/// The program it's loaded with should be parsed with IDs after that one (see `Parser::start_ids_after`).
+pub fn parse() -> (Program, u32) {
+    let tokens = Tokenizer::new(SOURCE.to_string()).map(|token| token.expect("The prelude should tokenize"));
+    let mut parser = Parser::from_stream(tokens);
+    let program = parser.parse_program().expect("The prelude should parse");
+    (program, parser.last_id())
+}
+
+/// Puts the prelude's declarations before a program's.
+pub fn with_prelude(prelude: Program, program: Program) -> Program {
+    Program { declarations: prelude.declarations.into_iter().chain(program.declarations).collect() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::{resolver::Resolver, value::Value, Interpreter};
+
+    use super::*;
+
+    #[test]
+    fn test_prelude_functions() {
+        let (prelude, last_id) = parse();
+        let mut tokenizer = Tokenizer::new("func main() -> i32 { let x: i32 = 5; unwrap_or(Option.Some(x), 0) + max(1, abs(-2)) }".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.start_ids_after(last_id);
+        let program = with_prelude(prelude, parser.parse_program().unwrap());
+
+        let mut interpreter = Interpreter::new();
+        Resolver::new(&mut interpreter).resolve_program(&program).unwrap();
+        interpreter.load(&program).unwrap();
+        assert_eq!(interpreter.call_function("main", vec![]), Ok(Value::Integer(7)));
+    }
+}
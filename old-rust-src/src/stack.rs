@@ -0,0 +1,42 @@
+/// How many bytes of stack the parser, resolver, and interpreter can each use by default.
+/// Threads spawned by the standard library get 2 MiB, so this leaves room for whatever called them.
+pub const DEFAULT_MAX_STACK: usize = 1536 * 1024;
+
+/// Limits how much stack a recursive walk over a program can use, so deeply nested input fails cleanly
+/// instead of overflowing the stack. How much each level of nesting takes differs a lot between debug and
+/// release builds, so this measures the stack itself rather than counting levels.
+#[derive(Debug, Clone, Copy)]
+pub struct StackLimit {
+    /// Roughly where the stack was when the walk started
+    start: usize,
+    max: usize
+}
+
+impl StackLimit {
+    /// Creates a limit of `max` bytes, measured from where the stack is now.
+    pub fn new(max: usize) -> Self {
+        StackLimit { start: stack_position(), max }
+    }
+
+    /// Sets how many bytes of stack can be used.
+    pub fn set_max(&mut self, max: usize) {
+        self.max = max;
+    }
+
+    /// Measures from where the stack is now, for when a new walk starts.
+    pub fn restart(&mut self) {
+        self.start = stack_position();
+    }
+
+    /// Returns whether more than the maximum has been used since the walk started.
+    pub fn exceeded(&self) -> bool {
+        stack_position().abs_diff(self.start) > self.max
+    }
+}
+
+/// Returns the address of a local variable, which is roughly how deep the stack currently is.
+#[inline(never)]
+fn stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
@@ -0,0 +1,79 @@
+use std::{collections::HashSet, hash::{Hash, Hasher}, sync::{LazyLock, Mutex}};
+
+/// An interned identifier. Symbols for the same name share the same string, so comparing and hashing them
+/// only looks at its address, and reading the name back doesn't need the interner at all.
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static str);
+
+/// Every name that has been interned. Names are leaked so that symbols can hold them as `&'static str`;
+/// each distinct name is only ever stored once, so this is bounded by the identifiers in the source.
+static INTERNER: LazyLock<Mutex<HashSet<&'static str>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+impl Symbol {
+    /// Returns the symbol for a name, interning it if it hasn't been seen before.
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = INTERNER.lock().unwrap();
+        if let Some(&name) = interner.get(name) {
+            return Symbol(name);
+        }
+
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        interner.insert(name);
+        Symbol(name)
+    }
+
+    /// Returns the name this symbol was interned from.
+    pub fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.0, state);
+    }
+}
+
+/// Symbols are debug-printed as their names, since the numbers are meaningless on their own.
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+        let a = Symbol::intern("interned_name");
+        let owned = String::from("interned_name");
+        let b = Symbol::intern(&owned);
+        let c = Symbol::intern("other_name");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.as_str(), "interned_name");
+        assert_eq!(c.to_string(), "other_name");
+        assert_eq!(format!("{:?}", c), "\"other_name\"");
+
+        // Symbols are interned the same way from any thread
+        let d = std::thread::spawn(|| Symbol::intern("interned_name")).join().unwrap();
+        assert_eq!(a, d);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{fs, io::{self, Write}, path::{Path, PathBuf}};
+
+use crate::{interpreter::{resolver::Resolver, EvalError, Interpreter, InterpreterControl}, parser::{ast::Declaration, Parser}, tokenizer::Tokenizer, SOURCE_EXTENSION};
+
+/// How many test files passed and failed.
+#[derive(Debug, Default, PartialEq)]
+pub struct TestSummary {
+    pub passed: usize,
+    pub failed: usize
+}
+
+impl std::fmt::Display for TestSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} passed, {} failed", self.passed, self.failed)
+    }
+}
+
+/// Runs every `.saffron` file in a directory, in name order, writing a line for each to `output` and returning the counts.
+/// A file fails if anything in it fails, such as an `assert`; the remaining files still run.
+pub fn run_tests(dir: &Path, output: &mut dyn Write) -> io::Result<TestSummary> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    paths.retain(|path| path.is_file() && path.extension().is_some_and(|extension| extension == SOURCE_EXTENSION));
+    paths.sort();
+
+    let mut summary = TestSummary::default();
+    for path in paths {
+        match run_test_file(&path) {
+            Ok(()) => {
+                writeln!(output, "PASS {}", path.display())?;
+                summary.passed += 1;
+            },
+            Err(message) => {
+                writeln!(output, "FAIL {}: {}", path.display(), message)?;
+                summary.failed += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// Runs a single test file. If it declares functions whose names start with `test`, each of them is called;
+/// otherwise its `main` function is.
+pub fn run_test_file(path: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+
+    let mut tokenizer = Tokenizer::new(source);
+    let tokens = tokenizer.tokenize().map_err(|e| EvalError::Tokenize(e).to_string())?;
+
+    let mut parser = Parser::new(tokens);
+    let program = parser.parse_program().ok_or_else(|| {
+        let errors: Vec<String> = parser.errors().iter().map(|error| error.to_string()).collect();
+        format!("Failed to parse the program: {}", errors.join("; "))
+    })?;
+
+    let mut interpreter = Interpreter::new();
+    Resolver::new(&mut interpreter).resolve_program(&program)?;
+    interpreter.add_spans(parser.take_spans());
+
+    let mut tests: Vec<&str> = program.declarations.iter().filter_map(|declaration| match declaration {
+        Declaration::Function { name, params, .. } if name.starts_with("test") && params.is_empty() => Some(name.as_str()),
+        _ => None
+    }).collect();
+    if tests.is_empty() {
+        tests.push("main");
+    }
+
+    let located = |interpreter: &Interpreter, error: InterpreterControl| {
+        let message = EvalError::Runtime(error).to_string();
+        match interpreter.error_span() {
+            Some(span) => format!("{} | {}:{}", message, path.display(), span),
+            None => message
+        }
+    };
+
+    interpreter.load(&program).map_err(|error| located(&interpreter, error))?;
+    for test in tests {
+        interpreter.call_function(test, vec![]).map_err(|error| format!("{}: {}", test, located(&interpreter, error)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_tests() {
+        let dir = std::env::temp_dir().join(format!("saffron-test-runner-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("passing.saffron"), r#"
+            func test_math() -> i32 {
+                assert_eq(1 + 1, 2);
+                assert(2 < 3)
+            }
+
+            func test_strings() -> i32 {
+                assert_eq("a" + "b", "ab")
+            }
+        "#).unwrap();
+        fs::write(dir.join("failing.saffron"), r#"
+            func main() -> i32 {
+                assert_eq(1 + 1, 3)
+            }
+        "#).unwrap();
+        fs::write(dir.join("notes.txt"), "Not a test").unwrap();
+
+        let mut output = Vec::new();
+        let summary = run_tests(&dir, &mut output);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(summary.unwrap(), TestSummary { passed: 1, failed: 1 });
+        assert_eq!(String::from_utf8(output).unwrap(), format!(
+            "FAIL {}: main: Runtime error: Assertion failed: 2 != 3 | {}:3:26\nPASS {}\n",
+            dir.join("failing.saffron").display(), dir.join("failing.saffron").display(), dir.join("passing.saffron").display()
+        ));
+    }
+
+    #[test]
+    fn test_failure_message() {
+        let dir = std::env::temp_dir().join(format!("saffron-test-failure-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("failing.saffron");
+        fs::write(&path, "func test_it() -> i32 {\n    assert(false)\n}\n").unwrap();
+
+        let result = run_test_file(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result, Err(format!("test_it: Runtime error: Assertion failed | {}:2:11", path.display())));
+    }
+}
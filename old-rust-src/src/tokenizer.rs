@@ -1,5 +1,7 @@
 use std::{collections::{HashMap, VecDeque}, sync::LazyLock};
 
+use crate::{parser::ast::{Span, Type}, symbol::Symbol};
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
@@ -7,13 +9,23 @@ pub struct Token {
     pub column: usize
 }
 
+/// A line comment, kept so tooling can attach it to the code around it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+    /// The text after the slashes, without surrounding whitespace
+    pub text: String,
+    /// If this is a `///` doc comment
+    pub doc: bool,
+    pub line: usize,
+    pub column: usize
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum TokenType {
     // keywords
     ImportKeyword, // import
     FunctionKeyword, // func
     StructKeyword, // struct
-    TypeKeyword, // type
     NewKeyword, // new
 
     ReturnKeyword, // return
@@ -32,30 +44,49 @@ pub enum TokenType {
     StringLiteral(String), // "hello", "world", etc.
     IntegerLiteral(i64), // 0, 1, 2, etc.
     FloatLiteral(f64), // 0.0, 0.1, 0.2, etc.
+    // The types are boxed so that these don't make every token larger
+    TypedIntegerLiteral(i64, Box<Type>), // 42u8, 100i64, etc.
+    TypedFloatLiteral(f64, Box<Type>), // 3.14f32, 2f64, etc.
     CharLiteral(char), // 'a', 'b', 'c', etc.
 
-    Identifier(String), // variable names, function names, etc.
+    Identifier(Symbol), // variable names, function names, etc.
 
     // operators
     AddOperator, // +
     SubtractOperator, // -
     MultiplyOperator, // *
     DivideOperator, // /
+    FloorDivideOperator, // ~/
     ModuloOperator, // %
     AssignmentOperator, // =
+    AddAssignOperator, // +=
+    SubtractAssignOperator, // -=
+    MultiplyAssignOperator, // *=
+    DivideAssignOperator, // /=
+    ModuloAssignOperator, // %=
 
     AndOperator, // &&
     OrOperator, // ||
     NotOperator, // !
 
-    // TODO: Bitwise operators
+    // `|` is the `Pipe` token, and `<<` and `>>` are two angle brackets so `>>` can still close nested generics
+    BitwiseAndOperator, // &
+    BitwiseXorOperator, // ^
+    BitwiseNotOperator, // ~
     
     Semicolon, // ;
     Comma, // ,
     Dot, // .
+    Ellipsis, // ...
+    DotDot, // ..
+    DotDotEqual, // ..=
     Colon, // :
     Arrow, // ->
+    FatArrow, // =>
     Pipeline, // |>
+    Pipe, // |
+    QuestionMark, // ?
+    QuestionDot, // ?.
 
     // comparison
     EqualOperator, // ==
@@ -72,7 +103,10 @@ pub enum TokenType {
     OpenSquareBracket, // [
     CloseSquareBracket, // ]
     OpenAngleBracket, // <
-    CloseAngleBracket // >
+    CloseAngleBracket, // >
+
+    // The tokenizer never produces this; the parser sees it when it looks past the last token
+    EndOfFile
 }
 
 impl TokenType {
@@ -87,7 +121,6 @@ impl TokenType {
             TokenType::BreakKeyword => "break".to_string(),
             TokenType::ContinueKeyword => "continue".to_string(),
             TokenType::StructKeyword => "struct".to_string(),
-            TokenType::TypeKeyword => "type".to_string(),
             TokenType::NewKeyword => "new".to_string(),
 
             TokenType::TrueValue => "true".to_string(),
@@ -99,9 +132,11 @@ impl TokenType {
             TokenType::StringLiteral(value) => format!("\"{}\"", value),
             TokenType::IntegerLiteral(value) => value.to_string(),
             TokenType::FloatLiteral(value) => value.to_string(),
+            TokenType::TypedIntegerLiteral(value, suffix) => format!("{}{}", value, suffix),
+            TokenType::TypedFloatLiteral(value, suffix) => format!("{}{}", value, suffix),
             TokenType::CharLiteral(value) => format!("'{}'", value),
 
-            TokenType::Identifier(value) => value.clone(),
+            TokenType::Identifier(value) => value.to_string(),
 
             _ => {
                 if let Some(symbol) = SYMBOLS.iter().find(|(_, v)| v == &self) {
@@ -116,13 +151,14 @@ impl TokenType {
     }
 }
 
+/// Words that are never identifiers. Keywords added later, like `type`, `as`, `enum`, `match`, `interface`, `for`, `in`,
+/// and `while`, are tokenized as identifiers instead, and the parser only treats them as keywords where it expects them.
 static KEYWORDS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
     let mut keywords = HashMap::new();
 
     keywords.insert("import", TokenType::ImportKeyword);
     keywords.insert("func", TokenType::FunctionKeyword);
     keywords.insert("struct", TokenType::StructKeyword);
-    keywords.insert("type", TokenType::TypeKeyword);
     keywords.insert("new", TokenType::NewKeyword);
     
     keywords.insert("return", TokenType::ReturnKeyword);
@@ -148,8 +184,15 @@ static SYMBOLS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
     symbols.insert("-", TokenType::SubtractOperator);
     symbols.insert("*", TokenType::MultiplyOperator);
     symbols.insert("/", TokenType::DivideOperator);
+    // `//` already starts a comment, so floor division is spelled `~/`
+    symbols.insert("~/", TokenType::FloorDivideOperator);
     symbols.insert("%", TokenType::ModuloOperator);
     symbols.insert("=", TokenType::AssignmentOperator);
+    symbols.insert("+=", TokenType::AddAssignOperator);
+    symbols.insert("-=", TokenType::SubtractAssignOperator);
+    symbols.insert("*=", TokenType::MultiplyAssignOperator);
+    symbols.insert("/=", TokenType::DivideAssignOperator);
+    symbols.insert("%=", TokenType::ModuloAssignOperator);
 
     symbols.insert(">=", TokenType::GreaterThanEqualOperator);
     symbols.insert("<=", TokenType::LessThanEqualOperator);
@@ -159,13 +202,23 @@ static SYMBOLS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
     symbols.insert("&&", TokenType::AndOperator);
     symbols.insert("||", TokenType::OrOperator);
     symbols.insert("!", TokenType::NotOperator);
+    symbols.insert("&", TokenType::BitwiseAndOperator);
+    symbols.insert("^", TokenType::BitwiseXorOperator);
+    symbols.insert("~", TokenType::BitwiseNotOperator);
     
     symbols.insert(";", TokenType::Semicolon);
     symbols.insert(",", TokenType::Comma);
     symbols.insert(".", TokenType::Dot);
+    symbols.insert("...", TokenType::Ellipsis);
+    symbols.insert("..", TokenType::DotDot);
+    symbols.insert("..=", TokenType::DotDotEqual);
     symbols.insert(":", TokenType::Colon);
     symbols.insert("->", TokenType::Arrow);
+    symbols.insert("=>", TokenType::FatArrow);
     symbols.insert("|>", TokenType::Pipeline);
+    symbols.insert("|", TokenType::Pipe);
+    symbols.insert("?", TokenType::QuestionMark);
+    symbols.insert("?.", TokenType::QuestionDot);
 
     symbols.insert("(", TokenType::OpenParenthesis);
     symbols.insert(")", TokenType::CloseParenthesis);
@@ -179,12 +232,62 @@ static SYMBOLS: LazyLock<HashMap<&str, TokenType>> = LazyLock::new(|| {
     symbols
 });
 
+/// Turns source text into tokens. Tokens can be streamed one at a time through the `Iterator`
+/// implementation (which `Parser::from_stream` parses from),
+/// or collected all at once with `tokenize`.
 pub struct Tokenizer {
     characters: VecDeque<char>,
     current_line: usize,
     current_column: usize,
+    /// Where the token currently being scanned started
+    start_line: usize,
+    start_column: usize,
+    /// Set after an error so the iterator stops instead of continuing from an inconsistent position
+    failed: bool,
+
+    tokens: Vec<Token>,
+    comments: Vec<Comment>
+}
+
+/// An error produced while tokenizing, with where the token that failed to scan started.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenizeError {
+    UnexpectedChar { ch: char, line: usize, column: usize },
+    /// A number that doesn't parse, like `3.14.15` or one too large for an integer
+    InvalidNumber { text: String, line: usize, column: usize },
+    /// Letters right after a number that aren't a type it can have, like the `abc` in `42abc` or the `u8` in `3.14u8`
+    InvalidSuffix { suffix: String, line: usize, column: usize },
+    UnterminatedString { line: usize, column: usize },
+    /// A `/*` without a matching `*/`, with where the outermost comment started
+    UnterminatedComment { line: usize, column: usize },
+    EmptyCharLiteral { line: usize, column: usize }
+}
 
-    tokens: Vec<Token>
+impl TokenizeError {
+    /// Returns where the token that failed to scan started.
+    pub fn span(&self) -> Span {
+        let (TokenizeError::UnexpectedChar { line, column, .. } |
+            TokenizeError::InvalidNumber { line, column, .. } |
+            TokenizeError::InvalidSuffix { line, column, .. } |
+            TokenizeError::UnterminatedString { line, column } |
+            TokenizeError::UnterminatedComment { line, column } |
+            TokenizeError::EmptyCharLiteral { line, column }) = self;
+        Span { line: *line, column: *column }
+    }
+}
+
+impl std::fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenizeError::UnexpectedChar { ch, .. } => write!(f, "Unexpected character: '{}'", ch),
+            TokenizeError::InvalidNumber { text, .. } if text.contains('.') => write!(f, "Invalid float value: {}", text),
+            TokenizeError::InvalidNumber { text, .. } => write!(f, "Invalid integer value: {}", text),
+            TokenizeError::InvalidSuffix { suffix, .. } => write!(f, "Invalid number suffix: {}", suffix),
+            TokenizeError::UnterminatedString { .. } => write!(f, "Unterminated string literal"),
+            TokenizeError::UnterminatedComment { .. } => write!(f, "Unterminated block comment"),
+            TokenizeError::EmptyCharLiteral { .. } => write!(f, "Empty character literal")
+        }
+    }
 }
 
 impl Tokenizer {
@@ -194,24 +297,28 @@ impl Tokenizer {
             characters,
             current_line: 1,
             current_column: 1,
-            tokens: Vec::<Token>::new()
+            start_line: 1,
+            start_column: 1,
+            failed: false,
+            tokens: Vec::<Token>::new(),
+            comments: Vec::new()
         }
     }
 
-    fn next_if<F>(&mut self, predicate: F) -> Option<char> where F: Fn(char) -> bool {
+    fn advance_if<F>(&mut self, predicate: F) -> Option<char> where F: Fn(char) -> bool {
         if let Some(&c) = self.peek() {
             if predicate(c) {
-                return self.next();
+                return self.advance();
             }
         }
         None
     }
 
     fn peek(&self) -> Option<&char> {
-        self.characters.get(0)
+        self.characters.front()
     }
 
-    fn next(&mut self) -> Option<char> {
+    fn advance(&mut self) -> Option<char> {
         if let Some(c) = self.characters.pop_front() {
             self.current_column += 1;
             if c == '\n' {
@@ -225,42 +332,72 @@ impl Tokenizer {
     }
 
     fn skip_whitespace(&mut self) {
-        while self.next_if(|c| c.is_whitespace()).is_some() {}
+        while self.advance_if(|c| c.is_whitespace()).is_some() {}
     }
 
-    fn add_token(&mut self, token_type: TokenType) {
-        self.tokens.push(Token {
+    fn make_token(&self, token_type: TokenType) -> Token {
+        Token {
             token_type,
-            line: self.current_line,
-            column: self.current_column
-        });
+            line: self.start_line,
+            column: self.start_column
+        }
     }
 
-    pub fn tokenize(&mut self) -> Result<&Vec<Token>, String> {
-        while self.peek().is_some() {
+    /// Tokenizes the rest of the input, returning every token.
+    pub fn tokenize(&mut self) -> Result<&Vec<Token>, TokenizeError> {
+        while let Some(token) = self.next() {
+            self.tokens.push(token?);
+        }
+        Ok(&self.tokens)
+    }
+
+    /// Returns the tokens collected by `tokenize`.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// Returns the line comments scanned so far, in order.
+    pub fn comments(&self) -> &[Comment] {
+        &self.comments
+    }
+
+    /// Returns where the token that failed to scan started, if tokenizing has failed.
+    pub fn error_span(&self) -> Option<Span> {
+        self.failed.then_some(Span { line: self.start_line, column: self.start_column })
+    }
+
+    /// Scans the next token, returning None at the end of the input.
+    pub fn next_token(&mut self) -> Result<Option<Token>, TokenizeError> {
+        // Skip a hash-bang line (e.g. `#!/usr/bin/env saffron`) at the very start of the file so scripts can be executable
+        if self.current_line == 1 && self.current_column == 1 && self.peek() == Some(&'#') && self.characters.get(1) == Some(&'!') {
+            while self.advance_if(|c| c != '\n').is_some() {}
+        }
+
+        loop {
             self.skip_whitespace();
+            self.start_line = self.current_line;
+            self.start_column = self.current_column;
 
-            match self.next() {
-                None => break,
+            let token_type = match self.advance() {
+                None => return Ok(None),
 
                 // Keywords and identifiers
-                Some(c) if c.is_alphabetic() || c == '_' => {
+                Some(c) if is_identifier_start(c) => {
                     let mut identifier = String::new();
                     identifier.push(c);
 
                     while let Some(&next_char) = self.peek() {
-                        if next_char.is_alphanumeric() || next_char == '_' {
-                            identifier.push(self.next().unwrap());
+                        if is_identifier_continue(next_char) {
+                            identifier.push(self.advance().unwrap());
                         } else {
                             break;
                         }
                     }
 
                     if let Some(tok) = KEYWORDS.get(identifier.as_str()) {
-                        let token: TokenType = tok.clone();
-                        self.add_token(token);
+                        tok.clone()
                     } else {
-                        self.add_token(TokenType::Identifier(identifier));
+                        TokenType::Identifier(Symbol::intern(&identifier))
                     }
                 },
 
@@ -269,38 +406,47 @@ impl Tokenizer {
                     number.push(c);
 
                     while let Some(&next_char) = self.peek() {
-                        if next_char.is_numeric() || next_char == '.' {
-                            number.push(self.next().unwrap());
+                        // A second dot starts a range, like `1..5`
+                        if next_char.is_numeric() || (next_char == '.' && self.characters.get(1) != Some(&'.')) {
+                            number.push(self.advance().unwrap());
                         } else {
                             break;
                         }
                     }
                     
+                    // A suffix starts with a letter, but can contain digits after that, like `u8`
                     let mut suffix = String::new();
                     while let Some(&next_char) = self.peek() {
-                        if next_char.is_alphabetic() {
-                            suffix.push(self.next().unwrap());
+                        if next_char.is_alphabetic() || (!suffix.is_empty() && next_char.is_alphanumeric()) {
+                            suffix.push(self.advance().unwrap());
                         } else {
                             break;
                         }
                     }
 
-                    // For now, no suffixes are allowed
                     if !suffix.is_empty() {
-                        return Err(format!("Invalid number suffix: {}", suffix));
-                    }
-
-                    if number.contains('.') {
+                        let suffix_type = match suffix_type(&suffix) {
+                            // Integer types can't hold a float
+                            Some(suffix_type) if !(number.contains('.') && suffix_type.integer_range().is_some()) => suffix_type,
+                            _ => return Err(TokenizeError::InvalidSuffix { suffix, line: self.start_line, column: self.start_column })
+                        };
+                        let invalid_number = || TokenizeError::InvalidNumber { text: number.clone(), line: self.start_line, column: self.start_column };
+                        if suffix_type.integer_range().is_some() {
+                            TokenType::TypedIntegerLiteral(number.parse::<i64>().map_err(|_| invalid_number())?, Box::new(suffix_type))
+                        } else {
+                            TokenType::TypedFloatLiteral(number.parse::<f64>().map_err(|_| invalid_number())?, Box::new(suffix_type))
+                        }
+                    } else if number.contains('.') {
                         if let Ok(value) = number.parse::<f64>() {
-                            self.add_token(TokenType::FloatLiteral(value));
+                            TokenType::FloatLiteral(value)
                         } else {
-                            return Err(format!("Invalid float value: {}", number));
+                            return Err(TokenizeError::InvalidNumber { text: number, line: self.start_line, column: self.start_column });
                         }
                     } else {
                         if let Ok(value) = number.parse::<i64>() {
-                            self.add_token(TokenType::IntegerLiteral(value));
+                            TokenType::IntegerLiteral(value)
                         } else {
-                            return Err(format!("Invalid integer value: {}", number));
+                            return Err(TokenizeError::InvalidNumber { text: number, line: self.start_line, column: self.start_column });
                         }
                     }
                 },
@@ -312,105 +458,184 @@ impl Tokenizer {
 
                     while let Some(&next_char) = self.peek() {
                         if next_char.is_numeric() {
-                            number.push(self.next().unwrap());
+                            number.push(self.advance().unwrap());
                         } else {
                             break;
                         }
                     }
 
                     if let Ok(value) = number.parse::<f64>() {
-                        self.add_token(TokenType::FloatLiteral(value));
+                        TokenType::FloatLiteral(value)
                     } else {
-                        return Err(format!("Invalid float value: {}", number));
+                        return Err(TokenizeError::InvalidNumber { text: number, line: self.start_line, column: self.start_column });
                     }
                 },
 
                 // Handle comments
                 Some('/') if self.peek().is_some_and(|&c| c == '/') => {
-                    // Skip the rest of the line
-                    while self.next_if(|c| c != '\n').is_some() {}
+                    // Skip the rest of the line, keeping it for tooling
+                    let mut text = String::new();
+                    while let Some(c) = self.advance_if(|c| c != '\n') {
+                        text.push(c);
+                    }
+                    let text = &text[1..]; // Skip the second slash
+                    // `////` and longer are decorative lines, not doc comments
+                    let doc = text.starts_with('/') && !text.starts_with("//");
+                    self.comments.push(Comment {
+                        text: text.strip_prefix('/').filter(|_| doc).unwrap_or(text).trim().to_string(),
+                        doc,
+                        line: self.start_line,
+                        column: self.start_column
+                    });
+                    continue;
                 },
                 Some('/') if self.peek().is_some_and(|&c| c == '*') => {
-                    // Skip block comments
-                    self.next(); // Consume the '*'
-                    while let Some(&c) = self.peek() {
-                        if c == '*' {
-                            self.next(); // Consume the '*'
-                            if self.peek() == Some(&'/') {
-                                self.next(); // Consume the '/'
-                                break;
-                            }
-                        } else {
-                            self.next(); // Consume the character
+                    // Skip block comments, which nest so commenting out code that has one in it works
+                    self.advance(); // Consume the '*'
+                    let mut depth = 1;
+                    while depth > 0 {
+                        match self.advance() {
+                            Some('*') if self.peek() == Some(&'/') => {
+                                self.advance(); // Consume the '/'
+                                depth -= 1;
+                            },
+                            Some('/') if self.peek() == Some(&'*') => {
+                                self.advance(); // Consume the '*'
+                                depth += 1;
+                            },
+                            Some(_) => {},
+                            None => return Err(TokenizeError::UnterminatedComment { line: self.start_line, column: self.start_column })
                         }
                     }
+                    continue;
                 },
 
                 // Strings
                 Some('"') => {
                     // TODO: Escape sequences
                     let mut string_value = String::new();
-                    while let Some(&c) = self.peek() {
+                    loop {
+                        let Some(&c) = self.peek() else {
+                            return Err(TokenizeError::UnterminatedString { line: self.start_line, column: self.start_column });
+                        };
                         if c == '"' {
-                            self.next(); // Consume the closing quote
+                            self.advance(); // Consume the closing quote
                             break;
                         } else if c == '\\' {
-                            self.next(); // Consume the backslash
+                            self.advance(); // Consume the backslash
                             if let Some(&escaped_char) = self.peek() {
                                 string_value.push(escaped_char);
-                                self.next(); // Consume the escaped character
+                                self.advance(); // Consume the escaped character
                             }
                         } else {
                             string_value.push(c);
-                            self.next(); // Consume the character
+                            self.advance(); // Consume the character
                         }
                     }
-                    self.add_token(TokenType::StringLiteral(string_value));
+                    TokenType::StringLiteral(string_value)
                 },
 
                 // Handle character literals
                 Some('\'') => {
-                    if let Some(&next_char) = self.peek() {
-                        if next_char != '\'' {
-                            self.add_token(TokenType::CharLiteral(next_char));
-                            self.next(); // Consume the character
-                        } else {
-                            return Err("Empty character literal".to_string());
-                        }
+                    let token = match self.peek() {
+                        Some(&'\'') => return Err(TokenizeError::EmptyCharLiteral { line: self.start_line, column: self.start_column }),
+                        Some(&next_char) => {
+                            let token = self.make_token(TokenType::CharLiteral(next_char));
+                            self.advance(); // Consume the character
+                            Some(token)
+                        },
+                        None => None
+                    };
+                    self.advance(); // Consume the closing quote
+                    match token {
+                        Some(token) => return Ok(Some(token)),
+                        None => continue
                     }
-                    self.next(); // Consume the closing quote
                 }
 
                 // Handle symbols and operators
                 Some(c) => {
                     if let Some(&next_char) = self.peek() {
+                        // Check for 3-character symbols
+                        if let Some(&third_char) = self.characters.get(1) {
+                            let three_char_symbol = format!("{}{}{}", c, next_char, third_char);
+                            if let Some(tok) = SYMBOLS.get(three_char_symbol.as_str()) {
+                                let token = self.make_token(tok.clone());
+                                self.advance(); // Consume the second character
+                                self.advance(); // Consume the third character
+                                return Ok(Some(token));
+                            }
+                        }
+
                         // Check for 2-character symbols
                         let two_char_symbol = format!("{}{}", c, next_char);
                         if let Some(tok) = SYMBOLS.get(two_char_symbol.as_str()) {
-                            let token: TokenType = tok.clone();
-                            self.add_token(token);
-                            self.next(); // Consume the second character
-                            continue;
+                            let token = self.make_token(tok.clone());
+                            self.advance(); // Consume the second character
+                            return Ok(Some(token));
                         }
                     }
                     
                     if let Some(tok) = SYMBOLS.get(c.to_string().as_str()) {
                         // Check for single-character symbols
-                        let token: TokenType = tok.clone();
-                        self.add_token(token);
-                        continue;
+                        tok.clone()
+                    } else {
+                        return Err(TokenizeError::UnexpectedChar { ch: c, line: self.start_line, column: self.start_column });
                     }
-                    
-                    return Err(format!("Unexpected character: '{}'", c));
                 }
-            }
+            };
+
+            return Ok(Some(self.make_token(token_type)));
         }
+    }
+}
 
-        Ok(&self.tokens)
+/// Identifiers follow Unicode's identifier rules (the same ones Rust uses): they start with a letter (from any
+/// script) or an underscore, and continue with letters, digits, combining marks, and connector punctuation like `_`.
+/// Emoji, symbols, and punctuation aren't part of identifiers.
+fn is_identifier_start(c: char) -> bool {
+    unicode_ident::is_xid_start(c) || c == '_'
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+impl Iterator for Tokenizer {
+    type Item = Result<Token, TokenizeError>;
+
+    /// Produces one token at a time without tokenizing the rest of the input. After an error, it returns None.
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let result = self.next_token().transpose();
+        if let Some(Err(_)) = result {
+            self.failed = true;
+        }
+        result
     }
 }
 
 
+/// Returns the type a number suffix like `u8` or `f32` gives its literal, or None if it isn't a numeric type.
+fn suffix_type(suffix: &str) -> Option<Type> {
+    match suffix {
+        "u8" => Some(Type::U8),
+        "u16" => Some(Type::U16),
+        "u32" => Some(Type::U32),
+        "u64" => Some(Type::U64),
+        "i8" => Some(Type::I8),
+        "i16" => Some(Type::I16),
+        "i32" => Some(Type::I32),
+        "i64" => Some(Type::I64),
+        "f32" => Some(Type::F32),
+        "f64" => Some(Type::F64),
+        _ => None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -429,22 +654,22 @@ mod tests {
 
         assert_eq!(tokens.len(), 19);
         assert_eq!(tokens[0].token_type, TokenType::ImportKeyword);
-        assert_eq!(tokens[1].token_type, TokenType::Identifier("hello".to_string()));
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("hello")));
         assert_eq!(tokens[2].token_type, TokenType::Dot);
-        assert_eq!(tokens[3].token_type, TokenType::Identifier("world".to_string()));
+        assert_eq!(tokens[3].token_type, TokenType::Identifier(Symbol::intern("world")));
         assert_eq!(tokens[4].token_type, TokenType::Semicolon);
         assert_eq!(tokens[5].token_type, TokenType::FunctionKeyword);
-        assert_eq!(tokens[6].token_type, TokenType::Identifier("add".to_string()));
+        assert_eq!(tokens[6].token_type, TokenType::Identifier(Symbol::intern("add")));
         assert_eq!(tokens[7].token_type, TokenType::OpenParenthesis);
-        assert_eq!(tokens[8].token_type, TokenType::Identifier("a".to_string()));
+        assert_eq!(tokens[8].token_type, TokenType::Identifier(Symbol::intern("a")));
         assert_eq!(tokens[9].token_type, TokenType::Comma);
-        assert_eq!(tokens[10].token_type, TokenType::Identifier("b".to_string()));
+        assert_eq!(tokens[10].token_type, TokenType::Identifier(Symbol::intern("b")));
         assert_eq!(tokens[11].token_type, TokenType::CloseParenthesis);
         assert_eq!(tokens[12].token_type, TokenType::OpenCurlyBracket);
         assert_eq!(tokens[13].token_type, TokenType::ReturnKeyword);
-        assert_eq!(tokens[14].token_type, TokenType::Identifier("a".to_string()));
+        assert_eq!(tokens[14].token_type, TokenType::Identifier(Symbol::intern("a")));
         assert_eq!(tokens[15].token_type, TokenType::AddOperator);
-        assert_eq!(tokens[16].token_type, TokenType::Identifier("b".to_string()));
+        assert_eq!(tokens[16].token_type, TokenType::Identifier(Symbol::intern("b")));
         assert_eq!(tokens[17].token_type, TokenType::Semicolon);
         assert_eq!(tokens[18].token_type, TokenType::CloseCurlyBracket);
     }
@@ -471,12 +696,12 @@ mod tests {
 
     #[test]
     fn test_float_literals() {
-        let input = r#"3.14"#;
+        let input = r#"2.5"#;
         let mut tokenizer = Tokenizer::new(input.to_string());
         let tokens = tokenizer.tokenize().unwrap();
 
         assert_eq!(tokens.len(), 1);
-        assert_eq!(tokens[0].token_type, TokenType::FloatLiteral(3.14));
+        assert_eq!(tokens[0].token_type, TokenType::FloatLiteral(2.5));
 
         let input = r#".5"#;
         let mut tokenizer = Tokenizer::new(input.to_string());
@@ -503,6 +728,28 @@ mod tests {
         assert_eq!(tokens[0].token_type, TokenType::IntegerLiteral(42));
     }
 
+    #[test]
+    fn test_ranges() {
+        let input = r#"1..5 'a'..='z' f(...args)"#;
+        let mut tokenizer = Tokenizer::new(input.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+
+        let expected = vec![
+            TokenType::IntegerLiteral(1),
+            TokenType::DotDot,
+            TokenType::IntegerLiteral(5),
+            TokenType::CharLiteral('a'),
+            TokenType::DotDotEqual,
+            TokenType::CharLiteral('z'),
+            TokenType::Identifier(Symbol::intern("f")),
+            TokenType::OpenParenthesis,
+            TokenType::Ellipsis,
+            TokenType::Identifier(Symbol::intern("args")),
+            TokenType::CloseParenthesis
+        ];
+        assert_eq!(tokens.iter().map(|token| token.token_type.clone()).collect::<Vec<_>>(), expected);
+    }
+
     #[test]
     fn test_keywords() {
         let input = r#"import func return if else loop const let break continue"#;
@@ -522,9 +769,51 @@ mod tests {
         assert_eq!(tokens[9].token_type, TokenType::ContinueKeyword);
     }
 
+    #[test]
+    fn test_contextual_keywords() {
+        // These are only keywords where the parser expects them, so they're tokenized as identifiers
+        let input = "as enum match interface for in while";
+        let mut tokenizer = Tokenizer::new(input.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+
+        assert_eq!(tokens.len(), 7);
+        for (token, name) in tokens.iter().zip(input.split(' ')) {
+            assert_eq!(token.token_type, TokenType::Identifier(Symbol::intern(name)));
+        }
+    }
+
+    #[test]
+    fn test_number_suffixes() {
+        for (input, expected) in [
+            ("42u8", TokenType::TypedIntegerLiteral(42, Box::new(Type::U8))),
+            ("42u16", TokenType::TypedIntegerLiteral(42, Box::new(Type::U16))),
+            ("42u32", TokenType::TypedIntegerLiteral(42, Box::new(Type::U32))),
+            ("42u64", TokenType::TypedIntegerLiteral(42, Box::new(Type::U64))),
+            ("42i8", TokenType::TypedIntegerLiteral(42, Box::new(Type::I8))),
+            ("42i16", TokenType::TypedIntegerLiteral(42, Box::new(Type::I16))),
+            ("42i32", TokenType::TypedIntegerLiteral(42, Box::new(Type::I32))),
+            ("100i64", TokenType::TypedIntegerLiteral(100, Box::new(Type::I64))),
+            ("1.25f32", TokenType::TypedFloatLiteral(1.25, Box::new(Type::F32))),
+            ("1.5f64", TokenType::TypedFloatLiteral(1.5, Box::new(Type::F64))),
+            // An integer with a float suffix is a float
+            ("2f64", TokenType::TypedFloatLiteral(2.0, Box::new(Type::F64)))
+        ] {
+            let mut tokenizer = Tokenizer::new(input.to_string());
+            let tokens = tokenizer.tokenize().unwrap();
+            assert_eq!(tokens.len(), 1, "{}", input);
+            assert_eq!(tokens[0].token_type, expected, "{}", input);
+            assert_eq!(tokens[0].token_type.reverse_format(), input, "{}", input);
+        }
+
+        for (input, suffix, column) in [("let x = 42u7;", "u7", 9), ("let x = 3.14u8;", "u8", 9), ("1 + 2i", "i", 5)] {
+            let mut tokenizer = Tokenizer::new(input.to_string());
+            assert_eq!(tokenizer.tokenize(), Err(TokenizeError::InvalidSuffix { suffix: suffix.to_string(), line: 1, column }), "{}", input);
+        }
+    }
+
     #[test]
     fn test_operators() {
-        let input = r#"+ - * / % = && || ! == != >= <= < >"#;
+        let input = r#"+ - * / ~/ % = && || ! == != >= <= < >"#;
         let mut tokenizer = Tokenizer::new(input.to_string());
         let tokens = tokenizer.tokenize().unwrap();
 
@@ -533,6 +822,7 @@ mod tests {
             TokenType::SubtractOperator,
             TokenType::MultiplyOperator,
             TokenType::DivideOperator,
+            TokenType::FloorDivideOperator,
             TokenType::ModuloOperator,
             TokenType::AssignmentOperator,
             TokenType::AndOperator,
@@ -558,8 +848,33 @@ mod tests {
         let mut tokenizer = Tokenizer::new(input.to_string());
         let result = tokenizer.tokenize();
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Unexpected character: '$'".to_string());
+        assert_eq!(result, Err(TokenizeError::UnexpectedChar { ch: '$', line: 1, column: 1 }));
+        assert_eq!(result.unwrap_err().to_string(), "Unexpected character: '$'");
+    }
+
+    #[test]
+    fn test_shebang() {
+        let input = "#!/usr/bin/env saffron\nprint(1);";
+        let mut tokenizer = Tokenizer::new(input.to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+
+        let mut expected_tokenizer = Tokenizer::new("print(1);".to_string());
+        let expected = expected_tokenizer.tokenize().unwrap();
+
+        assert_eq!(tokens.len(), expected.len());
+        for (token, expected) in tokens.iter().zip(expected.iter()) {
+            assert_eq!(token.token_type, expected.token_type);
+        }
+        assert_eq!(tokens[0].line, 2);
+    }
+
+    #[test]
+    fn test_shebang_only_on_first_line() {
+        let input = "print(1);\n#!/usr/bin/env saffron";
+        let mut tokenizer = Tokenizer::new(input.to_string());
+        let result = tokenizer.tokenize();
+
+        assert_eq!(result, Err(TokenizeError::UnexpectedChar { ch: '#', line: 2, column: 1 }));
     }
 
     #[test]
@@ -568,8 +883,8 @@ mod tests {
         let mut tokenizer = Tokenizer::new(input.to_string());
         let result = tokenizer.tokenize();
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Empty character literal".to_string());
+        assert_eq!(result, Err(TokenizeError::EmptyCharLiteral { line: 1, column: 1 }));
+        assert_eq!(result.unwrap_err().to_string(), "Empty character literal");
     }
 
     #[test]
@@ -578,8 +893,8 @@ mod tests {
         let mut tokenizer = Tokenizer::new(input.to_string());
         let result = tokenizer.tokenize();
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Invalid float value: 3.14.15".to_string());
+        assert_eq!(result, Err(TokenizeError::InvalidNumber { text: "3.14.15".to_string(), line: 1, column: 1 }));
+        assert_eq!(result.unwrap_err().to_string(), "Invalid float value: 3.14.15");
     }
 
     #[test]
@@ -588,7 +903,123 @@ mod tests {
         let mut tokenizer = Tokenizer::new(input.to_string());
         let result = tokenizer.tokenize();
 
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Invalid number suffix: abc".to_string());
+        assert_eq!(result, Err(TokenizeError::InvalidSuffix { suffix: "abc".to_string(), line: 1, column: 1 }));
+        assert_eq!(result.unwrap_err().to_string(), "Invalid number suffix: abc");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_streaming() {
+        let input = "let x = 1; $ 2";
+        let mut tokenizer = Tokenizer::new(input.to_string());
+
+        // Each call only scans as far as the next token
+        let first = tokenizer.next().unwrap().unwrap();
+        assert_eq!(first.token_type, TokenType::LetKeyword);
+        assert_eq!(tokenizer.characters.iter().collect::<String>(), " x = 1; $ 2");
+
+        let rest: Vec<Result<TokenType, TokenizeError>> = tokenizer.map(|token| token.map(|token| token.token_type)).collect();
+        assert_eq!(rest, vec![
+            Ok(TokenType::Identifier(Symbol::intern("x"))),
+            Ok(TokenType::AssignmentOperator),
+            Ok(TokenType::IntegerLiteral(1)),
+            Ok(TokenType::Semicolon),
+            // Iteration stops after the first error
+            Err(TokenizeError::UnexpectedChar { ch: '$', line: 1, column: 12 })
+        ]);
+    }
+
+    #[test]
+    fn test_streaming_matches_tokenize() {
+        let input = r#"
+            // A comment
+            func add(a: i32, b: f64) -> f64 {
+                let total: f64 = a + b * .5;
+                total += 1;
+                if (total >= 10) { "big" } else { 'c' }
+            }
+        "#;
+
+        let mut tokenizer = Tokenizer::new(input.to_string());
+        let tokens = tokenizer.tokenize().unwrap().clone();
+        let streamed: Vec<Token> = Tokenizer::new(input.to_string()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(streamed, tokens);
+    }
+
+    #[test]
+    fn test_repeated_identifiers() {
+        let input = "alpha beta_1 alpha ".repeat(10_000);
+        let mut tokenizer = Tokenizer::new(input);
+        let tokens = tokenizer.tokenize().unwrap();
+
+        assert_eq!(tokens.len(), 30_000);
+        let alpha = Symbol::intern("alpha");
+        let beta = Symbol::intern("beta_1");
+        for (i, token) in tokens.iter().enumerate() {
+            let expected = if i % 3 == 1 { beta } else { alpha };
+            assert_eq!(token.token_type, TokenType::Identifier(expected));
+        }
+        assert_eq!(tokens[1].token_type.reverse_format(), "beta_1");
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let mut tokenizer = Tokenizer::new("café _x1 变量".to_string());
+        let tokens: Vec<TokenType> = tokenizer.tokenize().unwrap().iter().map(|token| token.token_type.clone()).collect();
+        assert_eq!(tokens, vec![
+            TokenType::Identifier(Symbol::intern("café")),
+            TokenType::Identifier(Symbol::intern("_x1")),
+            TokenType::Identifier(Symbol::intern("变量"))
+        ]);
+
+        // Identifiers can't start with a digit
+        let mut tokenizer = Tokenizer::new("2foo".to_string());
+        assert_eq!(tokenizer.tokenize(), Err(TokenizeError::InvalidSuffix { suffix: "foo".to_string(), line: 1, column: 1 }));
+
+        // Emoji aren't part of identifiers
+        let mut tokenizer = Tokenizer::new("foo😀".to_string());
+        assert_eq!(tokenizer.tokenize(), Err(TokenizeError::UnexpectedChar { ch: '😀', line: 1, column: 4 }));
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let mut tokenizer = Tokenizer::new("let s = \"abc\\\"".to_string());
+        assert_eq!(tokenizer.tokenize(), Err(TokenizeError::UnterminatedString { line: 1, column: 9 }));
+    }
+
+    #[test]
+    fn test_nested_block_comments() {
+        let mut tokenizer = Tokenizer::new("1 /* outer /* inner */ still comment */ 2".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        assert_eq!(tokens.iter().map(|token| token.token_type.clone()).collect::<Vec<_>>(), vec![
+            TokenType::IntegerLiteral(1),
+            TokenType::IntegerLiteral(2)
+        ]);
+
+        let mut tokenizer = Tokenizer::new("/* a /* b /* c */ b */ a */ x /**/".to_string());
+        let tokens = tokenizer.tokenize().unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type, TokenType::Identifier(Symbol::intern("x")));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        let mut tokenizer = Tokenizer::new("let x = 1;
+  /* outer /* inner */ never closed".to_string());
+        let error = tokenizer.tokenize().unwrap_err();
+        assert_eq!(error, TokenizeError::UnterminatedComment { line: 2, column: 3 });
+        assert_eq!(error.to_string(), "Unterminated block comment");
+    }
+
+    #[test]
+    fn test_repeated_signs() {
+        // `--` is two minus signs, so `5--3` and `--x` work; the parser explains that `i--` isn't an operator
+        let mut tokenizer = Tokenizer::new("5--3".to_string());
+        let tokens: Vec<TokenType> = tokenizer.tokenize().unwrap().iter().map(|token| token.token_type.clone()).collect();
+        assert_eq!(tokens, vec![
+            TokenType::IntegerLiteral(5),
+            TokenType::SubtractOperator,
+            TokenType::SubtractOperator,
+            TokenType::IntegerLiteral(3)
+        ]);
+    }
+}
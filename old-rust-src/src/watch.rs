@@ -0,0 +1,151 @@
+use std::{path::{Path, PathBuf}, sync::mpsc::{self, Receiver, Sender}, time::{Duration, Instant}};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{parser::ast::{Declaration, Program}, SOURCE_EXTENSION};
+
+/// How long to wait for changes to settle before re-running, so that an editor saving several times
+/// in quick succession only triggers one run.
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Returns the files a program's imports refer to: `import a.b;` refers to `a/b.saffron` next to the program.
+pub fn import_paths(program: &Program, base: &Path) -> Vec<PathBuf> {
+    program.declarations.iter().filter_map(|declaration| match declaration {
+        Declaration::Import { path, .. } => Some(base.join(path.join("/")).with_extension(SOURCE_EXTENSION)),
+        _ => None
+    }).collect()
+}
+
+/// Watches a set of source files and reports when any of them change.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    debounce: Duration,
+    /// The watched files, with their directories canonicalized to match the paths in events
+    files: Vec<PathBuf>,
+    directories: Vec<PathBuf>
+}
+
+impl FileWatcher {
+    pub fn new(debounce: Duration) -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        Self::with_channel(debounce, sender, events)
+    }
+
+    /// Creates a watcher that reads its events from `events`, which the underlying watcher sends to through `sender`.
+    /// Tests keep their own sender too, so they can send events without waiting on the file system.
+    fn with_channel(debounce: Duration, sender: Sender<notify::Result<Event>>, events: Receiver<notify::Result<Event>>) -> notify::Result<Self> {
+        Ok(FileWatcher {
+            watcher: notify::recommended_watcher(sender)?,
+            events,
+            debounce,
+            files: Vec::new(),
+            directories: Vec::new()
+        })
+    }
+
+    /// Replaces the set of watched files.
+    /// Their directories are watched rather than the files themselves, since many editors save by replacing the file.
+    /// Files in directories that don't exist are skipped.
+    pub fn watch(&mut self, files: &[PathBuf]) -> notify::Result<()> {
+        for directory in self.directories.drain(..) {
+            self.watcher.unwatch(&directory)?;
+        }
+        self.files.clear();
+
+        for file in files {
+            let (Some(directory), Some(name)) = (file.parent(), file.file_name()) else {
+                continue;
+            };
+            let directory = if directory.as_os_str().is_empty() { Path::new(".") } else { directory };
+            let Ok(directory) = directory.canonicalize() else {
+                continue;
+            };
+
+            if !self.directories.contains(&directory) {
+                self.watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+                self.directories.push(directory.clone());
+            }
+            self.files.push(directory.join(name));
+        }
+        Ok(())
+    }
+
+    /// Blocks until one of the watched files changes and returns true.
+    /// Returns false if the timeout passes first, or if the watcher stops.
+    pub fn wait_for_change(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let event = match deadline {
+                Some(deadline) => self.events.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok(),
+                None => self.events.recv().ok()
+            };
+            match event {
+                Some(Ok(event)) => if self.is_change(&event) {
+                    // Swallow the rest of a burst of changes, so it only counts once
+                    while self.events.recv_timeout(self.debounce).is_ok() {}
+                    return true;
+                },
+                // Errors from the underlying watcher are transient, so keep waiting
+                Some(Err(_)) => {},
+                None => return false
+            }
+        }
+    }
+
+    /// Returns true if an event changed one of the watched files.
+    /// Other events, like the file being opened to run it, are ignored.
+    fn is_change(&self, event: &Event) -> bool {
+        (event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove())
+            && event.paths.iter().any(|path| self.files.contains(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use notify::{event::{AccessKind, CreateKind, ModifyKind, RemoveKind}, EventKind};
+
+    use crate::parser::ast::ExpressionId;
+
+    use super::*;
+
+    #[test]
+    fn test_rerun_after_change() {
+        let dir = std::env::temp_dir().join(format!("saffron-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.saffron");
+        fs::write(&file, "func main() -> i32 { 1 }").unwrap();
+
+        // Events are sent directly, so the test doesn't depend on how quickly the file system reports them
+        let (sender, events) = mpsc::channel();
+        let mut watcher = FileWatcher::with_channel(Duration::ZERO, sender.clone(), events).unwrap();
+        watcher.watch(std::slice::from_ref(&file)).unwrap();
+        let watched = dir.canonicalize().unwrap().join("main.saffron");
+        let send = |kind: EventKind, path: &Path| sender.send(Ok(Event::new(kind).add_path(path.to_path_buf()))).unwrap();
+
+        // Reading the file (as running it does) and changing other files in the same directory are ignored
+        send(EventKind::Access(AccessKind::Read), &watched);
+        send(EventKind::Modify(ModifyKind::Any), &watched.with_file_name("notes.txt"));
+        sender.send(Err(notify::Error::generic("Transient error"))).unwrap();
+        assert!(!watcher.wait_for_change(Some(Duration::ZERO)));
+
+        // A burst of changes only counts once
+        send(EventKind::Modify(ModifyKind::Any), &watched);
+        send(EventKind::Remove(RemoveKind::File), &watched);
+        send(EventKind::Create(CreateKind::File), &watched);
+        assert!(watcher.wait_for_change(Some(Duration::ZERO)));
+        assert!(!watcher.wait_for_change(Some(Duration::ZERO)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_import_paths() {
+        let program = Program {
+            declarations: vec![Declaration::Import { path: vec!["util".to_string(), "math".to_string()], expression_id: ExpressionId(1) }]
+        };
+        assert_eq!(import_paths(&program, Path::new("src")), vec![PathBuf::from("src/util/math.saffron")]);
+    }
+}
@@ -0,0 +1,44 @@
+use std::{fs, process::{Command, Output}};
+
+/// Where `check` writes the program with this name.
+fn path(name: &str) -> String {
+    std::env::temp_dir().join(format!("saffron-check-{}-{}.saffron", name, std::process::id())).display().to_string()
+}
+
+/// Writes a program to a temporary file and runs `--check` on it.
+fn check(name: &str, source: &str) -> Output {
+    let path = path(name);
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_SaffronLanguage")).arg("--check").arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn test_check_reports_errors() {
+    let output = check("error", "func main() -> i32 {\n    let b: u8 = 256;\n    0\n}\n");
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), format!(
+        "Error: 256 doesn't fit in u8 b, which holds 0 to 255.\n --> {}:2:9\n  |\n2 |     let b: u8 = 256;\n  |         ^\n",
+        path("error")
+    ));
+}
+
+#[test]
+fn test_check_reports_every_error() {
+    let output = check("errors", "func main() -> i32 {\n    let b: u8 = 256;\n    let c: i8 = -129;\n    b + c\n}\n");
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), format!(
+        "Error: 256 doesn't fit in u8 b, which holds 0 to 255.\n --> {0}:2:9\n  |\n2 |     let b: u8 = 256;\n  |         ^\n\
+        Error: -129 doesn't fit in i8 c, which holds -128 to 127.\n --> {0}:3:9\n  |\n3 |     let c: i8 = -129;\n  |         ^\n",
+        path("errors")
+    ));
+}
+
+#[test]
+fn test_check_does_not_run() {
+    let output = check("valid", "func main() -> i32 {\n    print(\"ran\");\n    0\n}\n");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
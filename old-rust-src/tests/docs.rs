@@ -0,0 +1,37 @@
+use std::{fs, process::Command};
+
+#[test]
+fn test_emit_docs() {
+    let path = std::env::temp_dir().join(format!("saffron-docs-{}.saffron", std::process::id()));
+    fs::write(&path, "/// Adds two numbers.
+/// Overflow is an error.
+func add(a: i32, b: i32) -> i32 { a + b }
+
+/// A point on the plane
+struct Point {
+    x: i32; // Across
+    y: i32;
+}
+
+func undocumented() -> i32 { 0 }
+
+func main() -> i32 {
+    print(\"ran\");
+    add(1, 2)
+}
+").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_SaffronLanguage")).arg("--emit-docs").arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    // Only documented declarations are listed, and the program isn't run
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "func add (line 3)
+    Adds two numbers.
+    Overflow is an error.
+struct Point (line 6)
+    A point on the plane
+field Point.x (line 7)
+    Across
+");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
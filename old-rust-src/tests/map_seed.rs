@@ -0,0 +1,25 @@
+use std::{fs, process::Command};
+
+#[test]
+fn test_map_seed() {
+    let path = std::env::temp_dir().join(format!("saffron-map-seed-{}.saffron", std::process::id()));
+    fs::write(&path, "func main() -> i32 {
+    let m: Map<str, i32> = { \"one\": 1, \"two\": 2, \"three\": 3 };
+    m[\"four\"] = 4;
+    print(m);
+    print(m[\"two\"] + m[\"four\"]);
+    0
+}
+").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_SaffronLanguage")).args(["--no-cache", "--map-seed", "12345"]).arg(&path).output().unwrap();
+    let invalid = Command::new(env!("CARGO_BIN_EXE_SaffronLanguage")).args(["--no-cache", "--map-seed", "first"]).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    // A different seed only changes the order maps iterate in, not what they hold
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "{four: 4, one: 1, three: 3, two: 2}\n6\nProgram executed successfully.\n");
+
+    // Seeds are numbers
+    assert!(!invalid.status.success());
+    assert!(String::from_utf8_lossy(&invalid.stderr).contains("invalid value 'first' for '--map-seed"));
+}
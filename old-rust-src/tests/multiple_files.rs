@@ -0,0 +1,60 @@
+use std::{fs, path::PathBuf, process::{Command, Output}};
+
+/// Writes each file to a temporary directory and runs them together as one program.
+fn run(name: &str, files: &[(&str, &str)]) -> Output {
+    let dir = std::env::temp_dir().join(format!("saffron-files-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = files.iter().map(|(file, source)| {
+        let path = dir.join(file);
+        fs::write(&path, source).unwrap();
+        path
+    }).collect();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_SaffronLanguage")).arg("--no-cache").args(&paths).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    output
+}
+
+#[test]
+fn test_functions_from_another_file() {
+    let output = run("call", &[
+        ("main.saffron", "func main() -> i32 {\n    print(double(21));\n    0\n}\n"),
+        ("math.saffron", "func double(x: i32) -> i32 {\n    x * 2\n}\n")
+    ]);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\nProgram executed successfully.\n");
+}
+
+#[test]
+fn test_errors_name_their_file() {
+    let output = run("error", &[
+        ("main.saffron", "func main() -> i32 {\n    fail(0)\n}\n"),
+        ("fail.saffron", "func fail(x: i32) -> i32 {\n\n    10 / x\n}\n")
+    ]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Runtime error: "), "{}", stderr);
+    assert!(stderr.trim_end().ends_with("fail.saffron:3:8"), "{}", stderr);
+
+    let output = run("parse", &[
+        ("main.saffron", "func main() -> i32 { 0 }\n"),
+        ("broken.saffron", "func broken() -> i32 {\n    1 +\n}\n")
+    ]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("broken.saffron:3:1"));
+}
+
+#[test]
+fn test_one_main() {
+    let output = run("two-mains", &[
+        ("a.saffron", "func main() -> i32 { 0 }\n"),
+        ("b.saffron", "func main() -> i32 { 1 }\n")
+    ]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Error: More than one file declares main: "), "{}", stderr);
+    assert!(stderr.contains("a.saffron") && stderr.contains("b.saffron"));
+
+    let output = run("no-main", &[
+        ("a.saffron", "func one() -> i32 { 1 }\n"),
+        ("b.saffron", "func two() -> i32 { 2 }\n")
+    ]);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Error: None of the input files declares main.\n");
+}
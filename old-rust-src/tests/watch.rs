@@ -0,0 +1,51 @@
+use std::{fs, io::{BufRead, BufReader}, process::{Command, Stdio}, sync::mpsc, thread, time::{Duration, Instant}};
+
+#[test]
+fn test_watch_reruns_after_change() {
+    let dir = std::env::temp_dir().join(format!("saffron-watch-run-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("main.saffron");
+    fs::write(&file, "func main() -> i32 {\n    print(\"first\");\n    0\n}\n").unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_SaffronLanguage"))
+        .args(["--no-cache", "--watch"]).arg(&file)
+        .stdout(Stdio::piped()).stderr(Stdio::null())
+        .spawn().unwrap();
+    let stdout = child.stdout.take().unwrap();
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    // Each run clears the terminal first, so the escape codes come before the program's output
+    let printed = |text: &str, timeout: Duration| {
+        let deadline = Instant::now() + timeout;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match lines.recv_timeout(remaining) {
+                Ok(line) if line.ends_with(text) => return true,
+                Ok(_) => {},
+                Err(_) => return false
+            }
+        }
+        false
+    };
+
+    let first = printed("first", Duration::from_secs(30));
+    // The file can change before the watcher starts watching it, so keep changing it until the program runs again.
+    // File systems can be slow to report changes, so this waits for a generous amount of time.
+    let start = Instant::now();
+    let mut rerun = false;
+    while first && !rerun && start.elapsed() < Duration::from_secs(30) {
+        fs::write(&file, "func main() -> i32 {\n    print(\"second\");\n    0\n}\n").unwrap();
+        rerun = printed("second", Duration::from_secs(1));
+    }
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(first);
+    assert!(rerun);
+}